@@ -0,0 +1,584 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use kormir::bitcoin::secp256k1::rand::thread_rng;
+use kormir::bitcoin::secp256k1::{Secp256k1, SecretKey};
+use kormir::format::{
+    decode_announcement, decode_attestation, encode_announcement, encode_attestation,
+    format_events, EventFormat,
+};
+use kormir::nostr::{EventId, JsonUtil};
+use kormir::numeric::{Decimal, Rounding};
+use kormir::offline::{
+    create_sign_request, import_sign_response, ProposedOutcome, SignRequest, SignResponse,
+};
+use kormir::policy::SigningPolicy;
+use kormir::secret::{parse_nsec, Secret};
+use kormir::sqlite::SqliteStorage;
+use kormir::storage::{EventState, EventStatus, Storage};
+use kormir::verify::{verify_announcement, verify_attestation};
+use kormir::{AttestationScheme, Oracle, OracleAnnouncement, OracleAttestation};
+use nostr_sdk::Client;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const KEY_FILE: &str = "key";
+const DB_FILE: &str = "kormir.db";
+
+/// Runs a DLC oracle from the command line, keeping its key and events in a
+/// local directory
+#[derive(Debug, Parser)]
+#[command(name = "kormir-cli", version, about)]
+struct Cli {
+    /// Directory of the oracle's key and database [default: ~/.kormir]
+    #[arg(long, env = "KORMIR_DATA_DIR", global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Nostr relays to publish announcements and attestations to, nothing is
+    /// published without them
+    #[arg(
+        long = "relay",
+        env = "KORMIR_RELAYS",
+        value_delimiter = ' ',
+        global = true
+    )]
+    relays: Vec<String>,
+
+    /// Encoding of printed announcements, attestations and events: json, hex
+    /// or tlv
+    #[arg(long, default_value_t = EventFormat::Hex, global = true)]
+    format: EventFormat,
+
+    /// How new events are signed: legacy (sha256 of the messages) or tagged
+    /// (BIP 340 tagged hashes). Existing events keep their scheme.
+    #[arg(
+        long,
+        env = "KORMIR_ATTESTATION_SCHEME",
+        default_value_t = AttestationScheme::Legacy,
+        global = true
+    )]
+    attestation_scheme: AttestationScheme,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Creates the oracle's key, or imports it from KORMIR_KEY
+    Init {
+        /// nsec or hex of an existing key
+        #[arg(long, env = "KORMIR_KEY", hide_env_values = true)]
+        nsec: Option<String>,
+    },
+    /// Prints the oracle's public key
+    Pubkey,
+    /// Creates an enum event and prints its announcement
+    CreateEnum {
+        #[arg(long)]
+        event_id: String,
+        /// A possible outcome, repeat for each one
+        #[arg(long = "outcome", required = true)]
+        outcomes: Vec<String>,
+        /// Unix timestamp the outcome is known at
+        #[arg(long)]
+        maturity: u32,
+    },
+    /// Creates a numeric event and prints its announcement
+    CreateNumeric {
+        #[arg(long)]
+        event_id: String,
+        #[arg(long, default_value_t = 2)]
+        base: u16,
+        #[arg(long, default_value_t = 18)]
+        num_digits: u16,
+        /// Whether the outcome can be negative
+        #[arg(long)]
+        signed: bool,
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        precision: i32,
+        #[arg(long)]
+        unit: String,
+        /// Unix timestamp the outcome is known at
+        #[arg(long)]
+        maturity: u32,
+    },
+    /// Signs the outcome of an enum event and prints its attestation
+    SignEnum {
+        #[arg(long)]
+        event_id: String,
+        #[arg(long)]
+        outcome: String,
+        /// Sign before the event's maturity
+        #[arg(long)]
+        early: bool,
+    },
+    /// Signs the outcome of a numeric event and prints its attestation
+    SignNumeric {
+        #[arg(long)]
+        event_id: String,
+        /// The raw outcome, already scaled by the announced precision
+        #[arg(long, allow_negative_numbers = true, required_unless_present = "value")]
+        outcome: Option<i64>,
+        /// A decimal value to sign instead, scaled to the announced precision
+        #[arg(long, allow_hyphen_values = true, conflicts_with = "outcome")]
+        value: Option<Decimal>,
+        /// How the value is rounded: floor, ceil, toward_zero,
+        /// half_away_from_zero or half_even
+        #[arg(long, default_value_t = Rounding::HalfAwayFromZero)]
+        rounding: Rounding,
+        /// Sign the nearest representable outcome for a value out of range
+        #[arg(long)]
+        clamp: bool,
+        /// Sign before the event's maturity
+        #[arg(long)]
+        early: bool,
+    },
+    /// Prints a request to sign an outcome on an offline host, as JSON. Does
+    /// not need the key.
+    SignRequest {
+        #[arg(long)]
+        event_id: String,
+        /// The outcome of an enum event
+        #[arg(long, required_unless_present = "numeric_outcome")]
+        outcome: Option<String>,
+        /// The outcome of a numeric event
+        #[arg(long, allow_negative_numbers = true, conflicts_with = "outcome")]
+        numeric_outcome: Option<i64>,
+        /// Sign before the event's maturity
+        #[arg(long)]
+        early: bool,
+    },
+    /// Signs a request file on the offline host and prints the response, as JSON
+    SignOffline { request: PathBuf },
+    /// Verifies and saves the attestation of a response file from the offline
+    /// host, then publishes it. Does not need the key.
+    ImportAttestation { response: PathBuf },
+    /// Lists the oracle's events
+    List {
+        /// Only list events in this state: created, announced, signed or
+        /// cancelled
+        #[arg(long)]
+        state: Option<EventState>,
+        /// Only list events with this status: pending, matured, overdue,
+        /// signed or cancelled
+        #[arg(long)]
+        status: Option<EventStatus>,
+    },
+    /// Publishes an event's announcement, and its attestation once signed,
+    /// if they were not published yet
+    Publish {
+        #[arg(long)]
+        event_id: String,
+    },
+    /// Decodes hex of an announcement or its TLV encoding and prints it as JSON
+    DecodeAnnouncement { hex: String },
+    /// Decodes hex of an attestation or its TLV encoding and prints it as JSON
+    DecodeAttestation { hex: String },
+    /// Verifies an announcement, and the attestation for it if given
+    Verify {
+        #[arg(long)]
+        announcement: String,
+        #[arg(long)]
+        attestation: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    pretty_env_logger::try_init()?;
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Init { nsec } => {
+            let data_dir = data_dir(cli.data_dir)?;
+            let signing_key = match nsec {
+                Some(nsec) => parse_nsec(&Secret::new(nsec))?,
+                None => SecretKey::new(&mut thread_rng()),
+            };
+            init_key(&data_dir, &signing_key)?;
+            let oracle = open_oracle(&data_dir, cli.attestation_scheme)?;
+            println!("{}", hex::encode(oracle.public_key().serialize()));
+        }
+        Command::Pubkey => {
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            println!("{}", hex::encode(oracle.public_key().serialize()));
+        }
+        Command::CreateEnum {
+            event_id,
+            outcomes,
+            maturity,
+        } => {
+            if maturity < now() {
+                anyhow::bail!("Event maturity epoch must be in the future");
+            }
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            let ann = oracle
+                .create_enum_event(event_id.clone(), outcomes, maturity)
+                .await?;
+            log::info!("Created enum event: {event_id}");
+            publish(&oracle, &cli.relays, &event_id).await?;
+            print_announcement(&ann, cli.format)?;
+        }
+        Command::CreateNumeric {
+            event_id,
+            base,
+            num_digits,
+            signed,
+            precision,
+            unit,
+            maturity,
+        } => {
+            if num_digits == 0 {
+                anyhow::bail!("Number of digits must be greater than 0");
+            }
+            if maturity < now() {
+                anyhow::bail!("Event maturity epoch must be in the future");
+            }
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            let ann = oracle
+                .create_numeric_event(
+                    event_id.clone(),
+                    base,
+                    num_digits,
+                    signed,
+                    precision,
+                    unit,
+                    maturity,
+                )
+                .await?;
+            log::info!("Created numeric event: {event_id}");
+            publish(&oracle, &cli.relays, &event_id).await?;
+            print_announcement(&ann, cli.format)?;
+        }
+        Command::SignEnum {
+            event_id,
+            outcome,
+            early,
+        } => {
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            let att = if early {
+                oracle
+                    .sign_enum_event_early(event_id.clone(), outcome)
+                    .await?
+            } else {
+                oracle.sign_enum_event(event_id.clone(), outcome).await?
+            };
+            log::info!("Signed enum event: {event_id}");
+            publish(&oracle, &cli.relays, &event_id).await?;
+            print_attestation(&att, cli.format)?;
+        }
+        Command::SignNumeric {
+            event_id,
+            outcome,
+            value,
+            rounding,
+            clamp,
+            early,
+        } => {
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            let outcome = match (outcome, value) {
+                (Some(outcome), None) => outcome,
+                (None, Some(value)) => {
+                    oracle
+                        .decimal_outcome(&event_id, value, rounding, clamp)
+                        .await?
+                }
+                _ => anyhow::bail!("Pass either --outcome or --value"),
+            };
+            let att = if early {
+                oracle
+                    .sign_numeric_event_early(event_id.clone(), outcome)
+                    .await?
+            } else {
+                oracle.sign_numeric_event(event_id.clone(), outcome).await?
+            };
+            log::info!("Signed numeric event: {event_id}");
+            publish(&oracle, &cli.relays, &event_id).await?;
+            print_attestation(&att, cli.format)?;
+        }
+        Command::SignRequest {
+            event_id,
+            outcome,
+            numeric_outcome,
+            early,
+        } => {
+            let outcome = match (outcome, numeric_outcome) {
+                (Some(outcome), None) => ProposedOutcome::Enum(outcome),
+                (None, Some(outcome)) => ProposedOutcome::Numeric(outcome),
+                _ => anyhow::bail!("Pass either --outcome or --numeric-outcome"),
+            };
+            let storage = open_storage(&data_dir(cli.data_dir)?)?;
+            let request = create_sign_request(&storage, event_id, outcome, early).await?;
+            println!("{}", serde_json::to_string_pretty(&request)?);
+        }
+        Command::SignOffline { request } => {
+            let request: SignRequest = read_json(&request)?;
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            let response = oracle.sign_request(&request).await?;
+            log::info!("Signed event: {}", response.event_id);
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Command::ImportAttestation { response } => {
+            let response: SignResponse = read_json(&response)?;
+            let storage = open_storage(&data_dir(cli.data_dir)?)?;
+            let att = import_sign_response(&storage, &response).await?;
+            log::info!("Imported attestation for event: {}", response.event_id);
+            publish_signed_attestation(&storage, &cli.relays, response).await?;
+            print_attestation(&att, cli.format)?;
+        }
+        Command::List { state, status } => {
+            let storage = open_storage(&data_dir(cli.data_dir)?)?;
+            let mut events = storage.list_events().await?;
+            let now = now();
+            if let Some(state) = state {
+                events.retain(|e| e.state == state);
+            }
+            if let Some(status) = status {
+                events.retain(|e| e.status(now) == status);
+            }
+            events.sort_by_key(|e| e.announcement.oracle_event.event_maturity_epoch);
+            let events = format_events(&events, cli.format, now);
+            println!("{}", serde_json::to_string_pretty(&events)?);
+        }
+        Command::Publish { event_id } => {
+            if cli.relays.is_empty() {
+                anyhow::bail!("No relays to publish to, set --relay or KORMIR_RELAYS");
+            }
+            let oracle = open_oracle(&data_dir(cli.data_dir)?, cli.attestation_scheme)?;
+            publish(&oracle, &cli.relays, &event_id).await?;
+        }
+        Command::DecodeAnnouncement { hex } => {
+            let ann = decode_announcement(&hex).context("Invalid announcement")?;
+            print_announcement(&ann, EventFormat::Json)?;
+        }
+        Command::DecodeAttestation { hex } => {
+            let att = decode_attestation(&hex).context("Invalid attestation")?;
+            print_attestation(&att, EventFormat::Json)?;
+        }
+        Command::Verify {
+            announcement,
+            attestation,
+        } => {
+            let secp = Secp256k1::verification_only();
+            let ann = decode_announcement(&announcement).context("Invalid announcement")?;
+            match attestation {
+                Some(attestation) => {
+                    let att = decode_attestation(&attestation).context("Invalid attestation")?;
+                    verify_attestation(&secp, &ann, &att)?;
+                    println!("Attestation is valid");
+                }
+                None => {
+                    verify_announcement(&secp, &ann)?;
+                    println!("Announcement is valid");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn data_dir(data_dir: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    if let Some(data_dir) = data_dir {
+        return Ok(data_dir);
+    }
+    let home = std::env::var_os("HOME").context("HOME is not set, pass --data-dir")?;
+    Ok(Path::new(&home).join(".kormir"))
+}
+
+/// Writes the key file, refusing to replace an existing key
+fn init_key(data_dir: &Path, signing_key: &SecretKey) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(data_dir)?;
+    let path = data_dir.join(KEY_FILE);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    let hex = Secret::new(hex::encode(signing_key.secret_bytes()));
+    file.write_all(hex.expose_secret().as_bytes())?;
+    Ok(())
+}
+
+/// Opens the oracle in `data_dir`, making sure its database belongs to the key
+fn open_oracle(
+    data_dir: &Path,
+    attestation_scheme: AttestationScheme,
+) -> anyhow::Result<Oracle<SqliteStorage>> {
+    let path = data_dir.join(KEY_FILE);
+    let key = std::fs::read_to_string(&path)
+        .map(Secret::new)
+        .with_context(|| format!("Failed to read {}, run init first", path.display()))?;
+    let mut bytes = Secret::new([0u8; 32]);
+    hex::decode_to_slice(key.expose_secret().trim(), bytes.expose_secret_mut())
+        .with_context(|| format!("Invalid key in {}", path.display()))?;
+    let signing_key = SecretKey::from_slice(bytes.expose_secret())?;
+
+    let storage = open_storage(data_dir)?;
+    // refuse to sign early unless asked with --early
+    let oracle = Oracle::from_signing_key(storage, signing_key)?
+        .with_signing_policy(SigningPolicy::RequireOverride)
+        .with_attestation_scheme(attestation_scheme);
+
+    let pubkey = oracle.public_key();
+    match oracle.storage.oracle_public_key()? {
+        None => oracle.storage.set_oracle_public_key(pubkey)?,
+        Some(db_pubkey) if db_pubkey == pubkey => {}
+        Some(db_pubkey) => anyhow::bail!(
+            "Database's oracle pubkey ({}) does not match the key ({})",
+            hex::encode(db_pubkey.serialize()),
+            hex::encode(pubkey.serialize()),
+        ),
+    }
+
+    Ok(oracle)
+}
+
+/// Opens the database without the key, e.g. on the online host of an oracle
+/// that signs offline
+fn open_storage(data_dir: &Path) -> anyhow::Result<SqliteStorage> {
+    std::fs::create_dir_all(data_dir)?;
+    Ok(SqliteStorage::open(data_dir.join(DB_FILE))?)
+}
+
+/// Publishes the attestation event the offline host signed, unless it was
+/// published before
+async fn publish_signed_attestation(
+    storage: &SqliteStorage,
+    relays: &[String],
+    response: SignResponse,
+) -> anyhow::Result<()> {
+    if relays.is_empty() {
+        return Ok(());
+    }
+    let Some(event) = response.attestation_event else {
+        log::warn!("No attestation event to publish, the announcement was not published");
+        return Ok(());
+    };
+    let data = storage
+        .get_event(response.event_id.clone())
+        .await?
+        .context("Event not found")?;
+    if data.attestation_event_id.is_some() {
+        return Ok(());
+    }
+
+    let client = Client::default();
+    client.add_relays(relays.iter().map(|r| r.as_str())).await?;
+    client.connect().await;
+
+    log::debug!("Broadcasting nostr event: {}", event.as_json());
+    let id = event.id;
+    client.send_event(event).await?;
+    storage
+        .add_attestation_event_id(response.event_id, id.to_hex())
+        .await?;
+    log::info!("Published attestation: {}", id.to_hex());
+
+    client.disconnect().await?;
+    Ok(())
+}
+
+/// Publishes the event's announcement and attestation to the relays, unless
+/// they were published before
+async fn publish(
+    oracle: &Oracle<SqliteStorage>,
+    relays: &[String],
+    event_id: &str,
+) -> anyhow::Result<()> {
+    if relays.is_empty() {
+        return Ok(());
+    }
+
+    let data = oracle
+        .storage
+        .get_event(event_id.to_string())
+        .await?
+        .context("Event not found")?;
+
+    let client = Client::default();
+    client.add_relays(relays.iter().map(|r| r.as_str())).await?;
+    client.connect().await;
+
+    let announcement_event_id = match &data.announcement_event_id {
+        Some(id) => EventId::from_hex(id)?,
+        None => {
+            let event = kormir::nostr_events::create_announcement_event(
+                oracle.nostr_public_key(),
+                &data.announcement,
+                relays,
+            );
+            let event = oracle.sign_nostr_event(event).await?;
+            log::debug!("Broadcasting nostr event: {}", event.as_json());
+
+            let id = event.id;
+            client.send_event(event).await?;
+            oracle
+                .storage
+                .add_announcement_event_id(event_id.to_string(), id.to_hex())
+                .await?;
+            if data.state == EventState::Created {
+                oracle.mark_announced(event_id.to_string()).await?;
+            }
+            log::info!("Published announcement: {}", id.to_hex());
+            id
+        }
+    };
+
+    if let (Some(att), None) = (data.attestation(), &data.attestation_event_id) {
+        let event = kormir::nostr_events::create_attestation_event(
+            oracle.nostr_public_key(),
+            &att,
+            announcement_event_id,
+        );
+        let event = oracle.sign_nostr_event(event).await?;
+        log::debug!("Broadcasting nostr event: {}", event.as_json());
+
+        let id = event.id;
+        client.send_event(event).await?;
+        oracle
+            .storage
+            .add_attestation_event_id(event_id.to_string(), id.to_hex())
+            .await?;
+        log::info!("Published attestation: {}", id.to_hex());
+    }
+
+    client.disconnect().await?;
+    Ok(())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid {}", path.display()))
+}
+
+fn print_announcement(ann: &OracleAnnouncement, format: EventFormat) -> anyhow::Result<()> {
+    match format {
+        EventFormat::Json => println!("{}", serde_json::to_string_pretty(ann)?),
+        EventFormat::Hex => println!("{}", encode_announcement(ann, false)),
+        EventFormat::Tlv => println!("{}", encode_announcement(ann, true)),
+    }
+    Ok(())
+}
+
+fn print_attestation(att: &OracleAttestation, format: EventFormat) -> anyhow::Result<()> {
+    match format {
+        EventFormat::Json => println!("{}", serde_json::to_string_pretty(att)?),
+        EventFormat::Hex => println!("{}", encode_attestation(att, false)),
+        EventFormat::Tlv => println!("{}", encode_attestation(att, true)),
+    }
+    Ok(())
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
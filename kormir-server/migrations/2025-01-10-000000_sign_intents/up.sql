@@ -0,0 +1,8 @@
+-- Outcomes the oracle has committed to signing for an event,
+-- written before any signature is produced so an event can never be signed twice
+CREATE TABLE sign_intents
+(
+    event_id   TEXT      NOT NULL PRIMARY KEY REFERENCES events (event_id),
+    outcomes   TEXT[]    NOT NULL,
+    created_at timestamp NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- Lifecycle state of an event: created, announced, signed or cancelled
+ALTER TABLE events
+    ADD COLUMN state TEXT NOT NULL DEFAULT 'created';
+
+UPDATE events
+SET state = 'announced'
+WHERE announcement_event_id IS NOT NULL;
+
+UPDATE events
+SET state = 'signed'
+WHERE event_id IN (SELECT event_id FROM event_nonces WHERE signature IS NOT NULL);
@@ -0,0 +1,4 @@
+drop table key_handovers;
+
+ALTER TABLE events
+    DROP COLUMN oracle_public_key;
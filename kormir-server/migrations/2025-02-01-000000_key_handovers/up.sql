@@ -0,0 +1,16 @@
+-- Key the oracle announced each event with, so events of a rotated key stay signable
+ALTER TABLE events
+    ADD COLUMN oracle_public_key BYTEA;
+
+UPDATE events
+SET oracle_public_key = (SELECT pubkey FROM oracle_metadata WHERE singleton_constant);
+
+-- Statements signed by an old oracle key handing over to a new one
+CREATE TABLE key_handovers
+(
+    old_pubkey   BYTEA     NOT NULL PRIMARY KEY,
+    new_pubkey   BYTEA     NOT NULL,
+    effective_at BIGINT    NOT NULL,
+    signature    BYTEA     NOT NULL,
+    created_at   timestamp NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- How each event's announcement and outcomes are hashed before signing: legacy or tagged
+ALTER TABLE events
+    ADD COLUMN attestation_scheme TEXT NOT NULL DEFAULT 'legacy';
@@ -0,0 +1,37 @@
+//! Holds the oracle's key and signs for a `kormir-server` started with
+//! `KORMIR_SIGNER_SOCKET`, so the server never sees the key.
+
+use kormir::journal::NonceJournal;
+use kormir::remote_signer::{bind, serve};
+use kormir::secret::{parse_nsec, take_env_secret};
+use kormir::signer::{LocalSigner, Signer};
+
+fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    pretty_env_logger::try_init()?;
+
+    let kormir_key = take_env_secret("KORMIR_KEY").expect("KORMIR_KEY must be set");
+    let signing_key = parse_nsec(&kormir_key)?;
+    let signer = LocalSigner::from_signing_key(signing_key)?;
+
+    let path = std::env::var("KORMIR_SIGNER_SOCKET").expect("KORMIR_SIGNER_SOCKET must be set");
+    // remove a stale socket from a previous run
+    if std::fs::metadata(&path).is_ok() {
+        std::fs::remove_file(&path)?;
+    }
+    let listener = bind(&path)?;
+
+    // every nonce the signer used, it refuses to sign anything else with them
+    let journal_path =
+        std::env::var("KORMIR_SIGNER_JOURNAL").expect("KORMIR_SIGNER_JOURNAL must be set");
+    let journal = NonceJournal::open(&journal_path)?;
+
+    println!(
+        "Signer for {} listening on {path}",
+        hex::encode(signer.public_key().serialize())
+    );
+
+    serve(listener, signer, journal)?;
+
+    Ok(())
+}
@@ -1,81 +1,182 @@
-use crate::models::oracle_metadata::OracleMetadata;
 use crate::models::{PostgresStorage, MIGRATIONS};
 use crate::routes::*;
+use crate::signer::ServerSigner;
+use crate::storage::ServerStorage;
 use axum::http::{StatusCode, Uri};
 use axum::routing::{get, post};
 use axum::{Extension, Router};
-use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::key::XOnlyPublicKey;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::PgConnection;
 use diesel_migrations::MigrationHarness;
-use kormir::Oracle;
-use nostr::Keys;
+use kormir::data_source::FileDataSource;
+use kormir::metadata::{SignedOracleMetadata, WELL_KNOWN_PATH};
+use kormir::policy::SigningPolicy;
+use kormir::remote_signer::RemoteSigner;
+use kormir::scheduler::{Scheduler, SchedulerConfig, SchedulerReport};
+use kormir::secret::{parse_nsec, take_env_secret, Secret};
+use kormir::signer::{LocalSigner, Signer};
+use kormir::sqlite::SqliteStorage;
+use kormir::{AttestationScheme, NonceDerivation, Oracle};
 use nostr_sdk::Client;
 
 mod models;
 mod routes;
+mod signer;
+mod storage;
 
 #[derive(Clone)]
 pub struct State {
-    oracle: Oracle<PostgresStorage>,
+    oracle: Oracle<ServerStorage, ServerSigner>,
     client: Client,
+    /// Bearer token of the `/admin` routes, they are disabled without it
+    admin_token: Option<Secret<String>>,
+    /// Encrypts the `/admin/backup` exports, backups are disabled without it
+    backup_passphrase: Option<Secret<String>>,
+    /// Served at `/.well-known/dlc-oracle.json` and published as the nostr profile
+    metadata: SignedOracleMetadata,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+/// Secrets taken out of the environment before the runtime starts any threads
+struct EnvSecrets {
+    kormir_key: Option<Secret<String>>,
+    retired_keys: Option<Secret<String>>,
+    admin_token: Option<Secret<String>>,
+    backup_passphrase: Option<Secret<String>>,
+}
+
+fn main() -> anyhow::Result<()> {
     // Load .env file
     dotenv::dotenv().ok();
     pretty_env_logger::try_init()?;
 
+    let secrets = EnvSecrets {
+        kormir_key: take_env_secret("KORMIR_KEY"),
+        retired_keys: take_env_secret("KORMIR_RETIRED_KEYS"),
+        admin_token: take_env_secret("KORMIR_ADMIN_TOKEN"),
+        backup_passphrase: take_env_secret("KORMIR_BACKUP_PASSPHRASE"),
+    };
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(secrets))
+}
+
+async fn run(secrets: EnvSecrets) -> anyhow::Result<()> {
     // get values key from env
-    let pg_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let port: u16 = std::env::var("KORMIR_PORT")
         .ok()
         .map(|p| p.parse::<u16>())
         .transpose()?
         .unwrap_or(8080);
 
-    // DB management
-    let manager = ConnectionManager::<PgConnection>::new(&pg_url);
-    let db_pool = Pool::builder()
-        .max_size(10)
-        .test_on_check_out(true)
-        .build(manager)
-        .expect("Could not build connection pool");
-
-    // run migrations
-    let mut conn = db_pool.get()?;
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("migrations could not run");
+    // keep the keys in separate signer processes when given their sockets,
+    // keys the oracle used before can still sign the events announced with them
+    let (signer, retired_signers) = match std::env::var("KORMIR_SIGNER_SOCKET") {
+        Ok(path) => {
+            if secrets.retired_keys.is_some() {
+                anyhow::bail!(
+                    "KORMIR_RETIRED_KEYS can not be used with KORMIR_SIGNER_SOCKET, \
+                     serve retired keys with kormir-signer and set KORMIR_RETIRED_SIGNER_SOCKETS"
+                );
+            }
+            let signer = ServerSigner::Remote(RemoteSigner::connect(path).await?);
+            let mut retired_signers = vec![];
+            let paths = std::env::var("KORMIR_RETIRED_SIGNER_SOCKETS").unwrap_or_default();
+            for path in paths.split_whitespace() {
+                retired_signers.push(ServerSigner::Remote(RemoteSigner::connect(path).await?));
+            }
+            (signer, retired_signers)
+        }
+        Err(_) => {
+            let kormir_key = secrets.kormir_key.expect("KORMIR_KEY must be set");
+            let signing_key = parse_nsec(&kormir_key)?;
+            let signer = ServerSigner::Local(LocalSigner::from_signing_key(signing_key)?);
+            let retired_keys = secrets.retired_keys.unwrap_or_default();
+            let retired_signers = retired_keys
+                .expose_secret()
+                .split_whitespace()
+                .map(|key| {
+                    let signing_key = parse_nsec(&Secret::new(key.to_string()))?;
+                    Ok(ServerSigner::Local(LocalSigner::from_signing_key(
+                        signing_key,
+                    )?))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            (signer, retired_signers)
+        }
+    };
 
-    let secp = Secp256k1::new();
-    let kormir_key = &std::env::var("KORMIR_KEY").expect("KORMIR_KEY must be set");
-    let secret_bytes = Keys::parse(kormir_key)?.secret_key()?.secret_bytes();
-    let signing_key = SecretKey::from_slice(&secret_bytes)?;
+    let pubkey = signer.public_key();
 
-    let pubkey = signing_key.x_only_public_key(&secp).0;
+    let storage = match db_url.strip_prefix("sqlite:") {
+        Some(path) => ServerStorage::Sqlite(open_sqlite_storage(path)?),
+        None => ServerStorage::Postgres(open_postgres_storage(&db_url, pubkey)?),
+    };
 
-    // check oracle metadata, if it doesn't exist, create it
-    let metadata = OracleMetadata::get(&mut conn)?;
-    match metadata {
-        Some(metadata) => {
-            if metadata.pubkey() != pubkey {
+    // a new key takes over once the old one signs a handover with POST /admin/key-handover
+    match storage.oracle_public_key().await? {
+        None => storage.set_oracle_public_key(pubkey).await?,
+        Some(db_pubkey) if db_pubkey == pubkey => {}
+        Some(db_pubkey) => {
+            if !retired_signers.iter().any(|s| s.public_key() == db_pubkey) {
                 anyhow::bail!(
-                    "Database's oracle pubkey ({}) does not match signing key ({})",
-                    hex::encode(metadata.pubkey().serialize()),
+                    "Database's oracle pubkey ({}) does not match signing key ({}), \
+                     add a signer for it to hand over to the new key",
+                    hex::encode(db_pubkey.serialize()),
                     hex::encode(pubkey.serialize()),
                 );
             }
+            log::warn!(
+                "Oracle has not been handed over from {} to {} yet, \
+                 sign the handover with POST /admin/key-handover",
+                hex::encode(db_pubkey.serialize()),
+                hex::encode(pubkey.serialize()),
+            );
         }
-        None => {
-            OracleMetadata::upsert(&mut conn, pubkey)?;
-        }
     }
 
-    let oracle = Oracle::from_signing_key(
-        PostgresStorage::new(db_pool, signing_key.x_only_public_key(&secp).0)?,
-        signing_key,
-    )?;
+    // the oracle's name is kept in the database, rename it when asked to
+    if let Ok(name) = std::env::var("KORMIR_NAME") {
+        storage.set_oracle_name(&name).await?;
+    }
+    let name = storage.oracle_name().await?.unwrap_or("Kormir".to_string());
+
+    let nonce_derivation = match std::env::var("KORMIR_NONCE_DERIVATION").as_deref() {
+        Ok("event") => NonceDerivation::EventDerived,
+        Ok("indexed") | Err(_) => NonceDerivation::Indexed,
+        Ok(other) => anyhow::bail!("Invalid KORMIR_NONCE_DERIVATION: {other}"),
+    };
+
+    // only applies to new events, existing ones keep the scheme they were announced with
+    let attestation_scheme = match std::env::var("KORMIR_ATTESTATION_SCHEME") {
+        Ok(scheme) => scheme
+            .parse::<AttestationScheme>()
+            .map_err(|_| anyhow::anyhow!("Invalid KORMIR_ATTESTATION_SCHEME: {scheme}"))?,
+        Err(_) => AttestationScheme::Legacy,
+    };
+
+    // signs whenever asked by default, like the library
+    let signing_policy = match std::env::var("KORMIR_SIGNING_POLICY").as_deref() {
+        Ok("permissive") | Err(_) => SigningPolicy::Permissive,
+        Ok("override") => SigningPolicy::RequireOverride,
+        Ok("after-maturity") => SigningPolicy::AfterMaturity,
+        Ok(other) => match other.strip_prefix("grace:").map(|s| s.parse::<u32>()) {
+            Some(Ok(grace_period)) => SigningPolicy::GraceWindow { grace_period },
+            _ => anyhow::bail!("Invalid KORMIR_SIGNING_POLICY: {other}"),
+        },
+    };
+    log::info!("Signing policy: {signing_policy:?}");
+
+    let oracle = retired_signers.into_iter().fold(
+        Oracle::from_signer(storage, signer)
+            .with_nonce_derivation(nonce_derivation)
+            .with_attestation_scheme(attestation_scheme)
+            .with_signing_policy(signing_policy),
+        |oracle, retired| oracle.with_retired_signer(retired),
+    );
 
     let relays = std::env::var("KORMIR_RELAYS")
         .unwrap_or("wss://relay.damus.io".to_string())
@@ -83,11 +184,52 @@ async fn main() -> anyhow::Result<()> {
         .map(|s| s.to_string())
         .collect::<Vec<_>>();
 
-    let client = Client::new(oracle.nostr_keys());
+    let mut metadata = oracle.metadata(name);
+    metadata.description = std::env::var("KORMIR_DESCRIPTION").ok();
+    metadata.contact = std::env::var("KORMIR_CONTACT").ok();
+    metadata.relays = relays.clone();
+    let metadata = oracle.sign_metadata(metadata).await?;
+
+    let client = Client::default();
     client.add_relays(relays).await?;
     client.connect().await;
 
-    let state = State { oracle, client };
+    let admin_token = secrets.admin_token;
+    if admin_token.is_none() {
+        log::info!("Admin routes are disabled, set KORMIR_ADMIN_TOKEN to enable them");
+    }
+    let backup_passphrase = secrets.backup_passphrase;
+
+    let state = State {
+        oracle,
+        client,
+        admin_token,
+        backup_passphrase,
+        metadata,
+    };
+
+    if let Err(e) = publish_metadata(&state).await {
+        log::error!("Failed to publish oracle metadata: {e}");
+    }
+
+    // attest to matured events automatically when given an outcomes file
+    if let Ok(path) = std::env::var("KORMIR_OUTCOMES_FILE") {
+        let interval: u64 = std::env::var("KORMIR_SCHEDULER_INTERVAL")
+            .ok()
+            .map(|p| p.parse::<u64>())
+            .transpose()?
+            .unwrap_or(60);
+        let scheduler = Scheduler::new(
+            state.oracle.clone(),
+            FileDataSource::new(path),
+            SchedulerConfig::default(),
+        );
+        tokio::spawn(run_scheduler(
+            state.clone(),
+            scheduler,
+            std::time::Duration::from_secs(interval),
+        ));
+    }
 
     let addr: std::net::SocketAddr = format!("0.0.0.0:{port}")
         .parse()
@@ -96,6 +238,8 @@ async fn main() -> anyhow::Result<()> {
     let server_router = Router::new()
         .route("/health-check", get(health_check))
         .route("/pubkey", get(get_pubkey))
+        .route("/key-history", get(get_key_history))
+        .route(WELL_KNOWN_PATH, get(get_metadata))
         .route("/list-events", get(list_events))
         .route("/announcement/:event_id", get(get_oracle_announcement))
         .route("/attestation/:event_id", get(get_oracle_attestation))
@@ -103,6 +247,11 @@ async fn main() -> anyhow::Result<()> {
         .route("/sign-enum", post(sign_enum_event))
         .route("/create-numeric", post(create_numeric_event))
         .route("/sign-numeric", post(sign_numeric_event))
+        .route("/create-batch", post(create_batch))
+        .route("/sign-batch", post(sign_batch))
+        .route("/cancel-event", post(cancel_event))
+        .route("/admin/backup", get(export_backup).post(import_backup))
+        .route("/admin/key-handover", post(hand_over_key))
         .fallback(fallback)
         .layer(Extension(state));
 
@@ -124,6 +273,74 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn open_postgres_storage(pg_url: &str, pubkey: XOnlyPublicKey) -> anyhow::Result<PostgresStorage> {
+    // DB management
+    let manager = ConnectionManager::<PgConnection>::new(pg_url);
+    let db_pool = Pool::builder()
+        .max_size(10)
+        .test_on_check_out(true)
+        .build(manager)
+        .expect("Could not build connection pool");
+
+    // run migrations
+    let mut conn = db_pool.get()?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .expect("migrations could not run");
+
+    PostgresStorage::new(db_pool, pubkey)
+}
+
+fn open_sqlite_storage(path: &str) -> anyhow::Result<SqliteStorage> {
+    let path = path.strip_prefix("//").unwrap_or(path);
+    Ok(SqliteStorage::open(path)?)
+}
+
+async fn run_scheduler(
+    state: State,
+    scheduler: Scheduler<ServerStorage, FileDataSource, ServerSigner>,
+    interval: std::time::Duration,
+) {
+    loop {
+        match scheduler.tick_now().await {
+            Ok(reports) => {
+                for report in reports {
+                    match report {
+                        SchedulerReport::Attested(att) => {
+                            log::info!("Scheduler attested to event: {}", att.event_id);
+                            let event_id = att.event_id.clone();
+                            if let Err(e) = publish_attestation(&state, event_id, &att).await {
+                                log::error!("Failed to publish attestation: {e}");
+                            }
+                        }
+                        SchedulerReport::Unresolved { event_id } => {
+                            log::debug!("Outcome for event {event_id} is not known yet");
+                        }
+                        SchedulerReport::Failed {
+                            event_id,
+                            error,
+                            attempts,
+                        } => {
+                            log::warn!("Failed to attest event {event_id} ({attempts}): {error}");
+                        }
+                        SchedulerReport::GaveUp {
+                            event_id,
+                            error,
+                            attempts,
+                        } => {
+                            log::error!(
+                                "Gave up attesting event {event_id} after {attempts} attempts: {error}"
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => log::error!("Scheduler failed to list events: {e}"),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
 async fn fallback(uri: Uri) -> (StatusCode, String) {
     (StatusCode::NOT_FOUND, format!("No route for {uri}"))
 }
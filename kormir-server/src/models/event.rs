@@ -1,7 +1,10 @@
+use bitcoin::key::XOnlyPublicKey;
 use bitcoin::secp256k1::schnorr::Signature;
 use diesel::prelude::*;
 use dlc_messages::oracle_msgs::OracleEvent;
 use kormir::lightning::util::ser::Readable;
+use kormir::storage::EventState;
+use kormir::AttestationScheme;
 use nostr::EventId;
 use serde::{Deserialize, Serialize};
 
@@ -31,6 +34,9 @@ pub struct Event {
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
     pub event_id: String,
+    state: String,
+    oracle_public_key: Option<Vec<u8>>,
+    attestation_scheme: String,
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -41,6 +47,8 @@ pub struct NewEvent<'a> {
     pub oracle_event: Vec<u8>,
     pub name: &'a str,
     pub is_enum: bool,
+    pub oracle_public_key: Vec<u8>,
+    pub attestation_scheme: String,
 }
 
 impl Event {
@@ -48,6 +56,14 @@ impl Event {
         Signature::from_slice(&self.announcement_signature).expect("invalid signature")
     }
 
+    /// The key the event was announced with, unknown for events from
+    /// before key rotation was supported
+    pub fn oracle_public_key(&self) -> Option<XOnlyPublicKey> {
+        self.oracle_public_key
+            .as_ref()
+            .map(|pk| XOnlyPublicKey::from_slice(pk).expect("invalid pubkey"))
+    }
+
     pub fn announcement_event_id(&self) -> Option<EventId> {
         self.announcement_event_id
             .as_ref()
@@ -87,6 +103,39 @@ impl Event {
             .optional()?)
     }
 
+    pub fn state(&self) -> EventState {
+        self.state.parse().expect("invalid event state")
+    }
+
+    pub fn attestation_scheme(&self) -> AttestationScheme {
+        self.attestation_scheme
+            .parse()
+            .expect("invalid attestation scheme")
+    }
+
+    /// Sets the lifecycle state of the event, returns false if it does not exist
+    pub fn set_state(
+        conn: &mut PgConnection,
+        event_id: String,
+        state: EventState,
+    ) -> anyhow::Result<bool> {
+        let updated = diesel::update(events::table.find(event_id))
+            .set(events::state.eq(state.to_string()))
+            .execute(conn)?;
+        Ok(updated > 0)
+    }
+
+    /// Records `pubkey` as the announcing key of every event that has none
+    pub fn set_missing_oracle_public_key(
+        conn: &mut PgConnection,
+        pubkey: XOnlyPublicKey,
+    ) -> anyhow::Result<()> {
+        diesel::update(events::table.filter(events::oracle_public_key.is_null()))
+            .set(events::oracle_public_key.eq(pubkey.serialize().to_vec()))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub fn list(conn: &mut PgConnection) -> anyhow::Result<Vec<Self>> {
         Ok(events::table.load::<Self>(conn)?)
     }
@@ -0,0 +1,69 @@
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use diesel::prelude::*;
+use kormir::rotation::KeyHandover;
+use serde::{Deserialize, Serialize};
+
+use super::schema::key_handovers;
+
+#[derive(
+    Queryable,
+    Insertable,
+    Identifiable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    PartialEq,
+)]
+#[diesel(primary_key(old_pubkey))]
+#[diesel(table_name = key_handovers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct KeyHandoverRow {
+    old_pubkey: Vec<u8>,
+    new_pubkey: Vec<u8>,
+    effective_at: i64,
+    signature: Vec<u8>,
+    created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = key_handovers)]
+struct NewKeyHandover {
+    old_pubkey: Vec<u8>,
+    new_pubkey: Vec<u8>,
+    effective_at: i64,
+    signature: Vec<u8>,
+}
+
+impl KeyHandoverRow {
+    pub fn handover(&self) -> KeyHandover {
+        KeyHandover {
+            old_public_key: XOnlyPublicKey::from_slice(&self.old_pubkey).expect("invalid pubkey"),
+            new_public_key: XOnlyPublicKey::from_slice(&self.new_pubkey).expect("invalid pubkey"),
+            effective_at: self.effective_at as u32,
+            signature: Signature::from_slice(&self.signature).expect("invalid signature"),
+        }
+    }
+
+    pub fn insert(conn: &mut PgConnection, handover: &KeyHandover) -> anyhow::Result<()> {
+        let new = NewKeyHandover {
+            old_pubkey: handover.old_public_key.serialize().to_vec(),
+            new_pubkey: handover.new_public_key.serialize().to_vec(),
+            effective_at: handover.effective_at as i64,
+            signature: handover.signature.serialize().to_vec(),
+        };
+        diesel::insert_into(key_handovers::table)
+            .values(&new)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// All handovers, oldest first
+    pub fn list(conn: &mut PgConnection) -> anyhow::Result<Vec<Self>> {
+        Ok(key_handovers::table
+            .order(key_handovers::effective_at.asc())
+            .load::<Self>(conn)?)
+    }
+}
@@ -1,5 +1,8 @@
 use crate::models::event::{Event, NewEvent};
 use crate::models::event_nonce::{EventNonce, NewEventNonce};
+use crate::models::key_handover::KeyHandoverRow;
+use crate::models::oracle_metadata::OracleMetadata;
+use crate::models::sign_intent::SignIntent;
 use anyhow::anyhow;
 use bitcoin::secp256k1::schnorr::Signature;
 use bitcoin::secp256k1::XOnlyPublicKey;
@@ -9,15 +12,19 @@ use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
 use kormir::error::Error;
 use kormir::lightning::util::ser::Writeable;
-use kormir::storage::{OracleEventData, Storage};
+use kormir::rotation::KeyHandover;
+use kormir::storage::{EventState, OracleEventData, Storage};
+use kormir::AttestationScheme;
 use nostr::EventId;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
 mod event;
 mod event_nonce;
+mod key_handover;
 pub mod oracle_metadata;
 mod schema;
+mod sign_intent;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
@@ -43,51 +50,6 @@ impl PostgresStorage {
         })
     }
 
-    pub async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
-        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
-
-        conn.transaction::<_, anyhow::Error, _>(|conn| {
-            let events = Event::list(conn)?;
-
-            let mut oracle_events = Vec::with_capacity(events.len());
-            for event in events {
-                let mut event_nonces = EventNonce::get_by_event_id(conn, event.event_id.clone())?;
-                event_nonces.sort_by_key(|nonce| nonce.index);
-
-                let indexes = event_nonces
-                    .iter()
-                    .map(|nonce| nonce.index as u32)
-                    .collect::<Vec<_>>();
-
-                let signatures = event_nonces
-                    .into_iter()
-                    .flat_map(|nonce| nonce.outcome_and_sig())
-                    .collect();
-
-                let announcement_event_id =
-                    event.announcement_event_id().map(|ann| ann.to_string());
-                let attestation_event_id = event.attestation_event_id().map(|att| att.to_string());
-
-                let data = OracleEventData {
-                    event_id: event.oracle_event().event_id,
-                    announcement: OracleAnnouncement {
-                        announcement_signature: event.announcement_signature(),
-                        oracle_public_key: self.oracle_public_key,
-                        oracle_event: event.oracle_event(),
-                    },
-                    indexes,
-                    signatures,
-                    announcement_event_id,
-                    attestation_event_id,
-                };
-                oracle_events.push(data);
-            }
-
-            Ok(oracle_events)
-        })
-        .map_err(|_| Error::StorageFailure)
-    }
-
     // pub fn get_oracle_event_by_event_id(
     //     &self,
     //     event_id: String,
@@ -125,6 +87,69 @@ impl PostgresStorage {
     //     }))
     // }
 
+    /// The oracle public key the database was created for, if any
+    pub async fn oracle_public_key(&self) -> Result<Option<XOnlyPublicKey>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        let metadata = OracleMetadata::get(&mut conn).map_err(|e| {
+            log::error!("Failed to get oracle metadata: {}", e);
+            Error::StorageFailure
+        })?;
+        Ok(metadata.map(|m| m.pubkey()))
+    }
+
+    /// Binds the database to the given oracle public key
+    pub async fn set_oracle_public_key(&self, pubkey: XOnlyPublicKey) -> Result<(), Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        OracleMetadata::upsert(&mut conn, pubkey).map_err(|e| {
+            log::error!("Failed to set oracle metadata: {}", e);
+            Error::StorageFailure
+        })
+    }
+
+    /// The oracle's name, if the database was bound to its key
+    pub async fn oracle_name(&self) -> Result<Option<String>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        let metadata = OracleMetadata::get(&mut conn).map_err(|e| {
+            log::error!("Failed to get oracle metadata: {}", e);
+            Error::StorageFailure
+        })?;
+        Ok(metadata.map(|m| m.name))
+    }
+
+    /// Renames the oracle
+    pub async fn set_oracle_name(&self, name: &str) -> Result<(), Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        OracleMetadata::set_name(&mut conn, name).map_err(|e| {
+            log::error!("Failed to set oracle name: {}", e);
+            Error::StorageFailure
+        })
+    }
+
+    /// Records a handover and moves the database to its new key, events
+    /// without a recorded key are marked as announced by the old one.
+    pub async fn save_key_handover(&self, handover: &KeyHandover) -> Result<(), Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            Event::set_missing_oracle_public_key(conn, handover.old_public_key)?;
+            KeyHandoverRow::insert(conn, handover)?;
+            OracleMetadata::set_pubkey(conn, handover.new_public_key)
+        })
+        .map_err(|e| {
+            log::error!("Failed to save key handover: {}", e);
+            Error::StorageFailure
+        })
+    }
+
+    /// All key handovers of the oracle, oldest first
+    pub async fn key_handovers(&self) -> Result<Vec<KeyHandover>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        let rows = KeyHandoverRow::list(&mut conn).map_err(|e| {
+            log::error!("Failed to list key handovers: {}", e);
+            Error::StorageFailure
+        })?;
+        Ok(rows.iter().map(|row| row.handover()).collect())
+    }
+
     pub async fn add_announcement_event_id(
         &self,
         event_id: String,
@@ -165,6 +190,7 @@ impl PostgresStorage {
 }
 
 impl Storage for PostgresStorage {
+    #[allow(clippy::explicit_counter_loop)]
     async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
         let mut current_index = self.current_index.fetch_add(num as u32, Ordering::SeqCst);
         let mut indexes = Vec::with_capacity(num);
@@ -179,44 +205,88 @@ impl Storage for PostgresStorage {
         &self,
         announcement: OracleAnnouncement,
         indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
     ) -> Result<String, Error> {
-        let is_enum = match announcement.oracle_event.event_descriptor {
-            EventDescriptor::EnumEvent(_) => true,
-            EventDescriptor::DigitDecompositionEvent(_) => false,
-        };
-        let new_event = NewEvent {
-            event_id: announcement.oracle_event.event_id.clone(),
-            announcement_signature: announcement.announcement_signature.encode(),
-            oracle_event: announcement.oracle_event.encode(),
-            name: &announcement.oracle_event.event_id,
-            is_enum,
-        };
+        let mut event_ids = self
+            .save_announcements(vec![(announcement, indexes)], attestation_scheme)
+            .await?;
+        event_ids.pop().ok_or(Error::Internal)
+    }
 
+    async fn save_announcements(
+        &self,
+        announcements: Vec<(OracleAnnouncement, Vec<u32>)>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<Vec<String>, Error> {
         let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
         conn.transaction::<_, anyhow::Error, _>(|conn| {
-            let event_id: String = diesel::insert_into(schema::events::table)
-                .values(&new_event)
-                .returning(schema::events::event_id)
-                .get_result(conn)?;
+            let mut event_ids = Vec::with_capacity(announcements.len());
+            for (announcement, indexes) in announcements {
+                let is_enum = match announcement.oracle_event.event_descriptor {
+                    EventDescriptor::EnumEvent(_) => true,
+                    EventDescriptor::DigitDecompositionEvent(_) => false,
+                };
+                let new_event = NewEvent {
+                    event_id: announcement.oracle_event.event_id.clone(),
+                    announcement_signature: announcement.announcement_signature.encode(),
+                    oracle_event: announcement.oracle_event.encode(),
+                    name: &announcement.oracle_event.event_id,
+                    is_enum,
+                    oracle_public_key: announcement.oracle_public_key.serialize().to_vec(),
+                    attestation_scheme: attestation_scheme.to_string(),
+                };
 
-            let new_event_nonces = indexes
-                .into_iter()
-                .zip(announcement.oracle_event.oracle_nonces)
-                .map(|(index, nonce)| NewEventNonce {
-                    id: index as i32,
-                    event_id: event_id.clone(),
-                    index: index as i32,
-                    nonce: nonce.serialize().to_vec(),
-                })
-                .collect::<Vec<_>>();
+                if Event::get_by_event_id(conn, new_event.event_id.clone())?.is_some() {
+                    return Err(Error::EventAlreadyExists.into());
+                }
 
-            diesel::insert_into(schema::event_nonces::table)
-                .values(&new_event_nonces)
-                .execute(conn)?;
+                let event_id: String = diesel::insert_into(schema::events::table)
+                    .values(&new_event)
+                    .returning(schema::events::event_id)
+                    .get_result(conn)?;
+
+                let new_event_nonces = indexes
+                    .into_iter()
+                    .zip(announcement.oracle_event.oracle_nonces)
+                    .map(|(index, nonce)| NewEventNonce {
+                        id: index as i32,
+                        event_id: event_id.clone(),
+                        index: index as i32,
+                        nonce: nonce.serialize().to_vec(),
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(schema::event_nonces::table)
+                    .values(&new_event_nonces)
+                    .execute(conn)?;
+
+                event_ids.push(event_id);
+            }
 
-            Ok(event_id)
+            Ok(event_ids)
         })
-        .map_err(|_| Error::StorageFailure)
+        .map_err(|e| e.downcast::<Error>().unwrap_or(Error::StorageFailure))
+    }
+
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if Event::get_by_event_id(conn, event_id.clone())?.is_none() {
+                return Ok(None);
+            }
+            let intent = SignIntent::insert_or_get(conn, event_id, outcomes)?;
+            Ok(Some(intent.outcomes))
+        })
+        .map_err(|e| {
+            log::error!("Failed to save sign intent: {}", e);
+            Error::StorageFailure
+        })?
+        .ok_or(Error::NotFound)
     }
 
     async fn save_signatures(
@@ -227,10 +297,12 @@ impl Storage for PostgresStorage {
         let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
 
         conn.transaction(|conn| {
-            let event =
-                Event::get_by_event_id(conn, event_id.clone())?.ok_or(anyhow!("Not Found"))?;
+            let event = Event::get_by_event_id(conn, event_id.clone())?.ok_or(Error::NotFound)?;
 
             let mut event_nonces = EventNonce::get_by_event_id(conn, event_id.clone())?;
+            if event_nonces.iter().any(|nonce| nonce.outcome.is_some()) {
+                return Err(Error::EventAlreadySigned.into());
+            }
             if event_nonces.len() != signatures.len() {
                 return Err(anyhow!("Invalid number of signatures"));
             }
@@ -249,22 +321,107 @@ impl Storage for PostgresStorage {
                 })
                 .collect::<anyhow::Result<Vec<_>>>()?;
 
+            let state = match event.state() {
+                EventState::Cancelled => EventState::Cancelled,
+                _ => EventState::Signed,
+            };
+            Event::set_state(conn, event_id.clone(), state)?;
+
             Ok(OracleEventData {
                 event_id,
                 announcement: OracleAnnouncement {
                     announcement_signature: event.announcement_signature(),
-                    oracle_public_key: self.oracle_public_key,
+                    oracle_public_key: event.oracle_public_key().unwrap_or(self.oracle_public_key),
                     oracle_event: event.oracle_event(),
                 },
                 indexes,
                 signatures,
+                state,
+                attestation_scheme: event.attestation_scheme(),
                 announcement_event_id: event.announcement_event_id().map(|id| id.to_hex()),
                 attestation_event_id: event.attestation_event_id().map(|id| id.to_hex()),
             })
         })
+        .map_err(|e: anyhow::Error| e.downcast::<Error>().unwrap_or(Error::StorageFailure))
+    }
+
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+
+        let updated = Event::set_state(&mut conn, event_id.clone(), state).map_err(|e| {
+            log::error!("Failed to set event state: {}", e);
+            Error::StorageFailure
+        })?;
+        if !updated {
+            return Err(Error::NotFound);
+        }
+
+        self.get_event(event_id).await?.ok_or(Error::Internal)
+    }
+
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let events = Event::list(conn)?;
+
+            let mut oracle_events = Vec::with_capacity(events.len());
+            for event in events {
+                let mut event_nonces = EventNonce::get_by_event_id(conn, event.event_id.clone())?;
+                event_nonces.sort_by_key(|nonce| nonce.index);
+
+                let indexes = event_nonces
+                    .iter()
+                    .map(|nonce| nonce.index as u32)
+                    .collect::<Vec<_>>();
+
+                let signatures = event_nonces
+                    .into_iter()
+                    .flat_map(|nonce| nonce.outcome_and_sig())
+                    .collect();
+
+                let announcement_event_id =
+                    event.announcement_event_id().map(|ann| ann.to_string());
+                let attestation_event_id = event.attestation_event_id().map(|att| att.to_string());
+
+                let data = OracleEventData {
+                    event_id: event.oracle_event().event_id,
+                    announcement: OracleAnnouncement {
+                        announcement_signature: event.announcement_signature(),
+                        oracle_public_key: event
+                            .oracle_public_key()
+                            .unwrap_or(self.oracle_public_key),
+                        oracle_event: event.oracle_event(),
+                    },
+                    indexes,
+                    signatures,
+                    state: event.state(),
+                    attestation_scheme: event.attestation_scheme(),
+                    announcement_event_id,
+                    attestation_event_id,
+                };
+                oracle_events.push(data);
+            }
+
+            Ok(oracle_events)
+        })
         .map_err(|_| Error::StorageFailure)
     }
 
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        let intents = SignIntent::list(&mut conn).map_err(|_| Error::StorageFailure)?;
+
+        Ok(intents
+            .into_iter()
+            .map(|intent| (intent.event_id, intent.outcomes))
+            .collect())
+    }
+
     async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
         let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
 
@@ -290,15 +447,209 @@ impl Storage for PostgresStorage {
                 event_id,
                 announcement: OracleAnnouncement {
                     announcement_signature: event.announcement_signature(),
-                    oracle_public_key: self.oracle_public_key,
+                    oracle_public_key: event.oracle_public_key().unwrap_or(self.oracle_public_key),
                     oracle_event: event.oracle_event(),
                 },
                 indexes,
                 signatures,
+                state: event.state(),
+                attestation_scheme: event.attestation_scheme(),
                 announcement_event_id: event.announcement_event_id().map(|id| id.to_hex()),
                 attestation_event_id: event.attestation_event_id().map(|id| id.to_hex()),
             }))
         })
         .map_err(|_| Error::StorageFailure)
     }
+
+    async fn get_nonce_counter(&self) -> Result<u32, Error> {
+        Ok(self.current_index.load(Ordering::SeqCst))
+    }
+
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+        // only held in memory, on startup the counter continues after the
+        // highest saved nonce, which covers every restored event
+        let previous = self.current_index.fetch_max(next, Ordering::SeqCst);
+        if previous > next {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    async fn restore_event(&self, data: OracleEventData) -> Result<(), Error> {
+        if !data.signatures.is_empty() && data.signatures.len() != data.indexes.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let parse_event_id = |id: &Option<String>| {
+            id.as_ref()
+                .map(|id| EventId::from_hex(id).map(|id| id.as_bytes().to_vec()))
+                .transpose()
+                .map_err(|_| Error::InvalidArgument)
+        };
+        let announcement_event_id = parse_event_id(&data.announcement_event_id)?;
+        let attestation_event_id = parse_event_id(&data.attestation_event_id)?;
+
+        let announcement = &data.announcement;
+        let new_event = NewEvent {
+            event_id: data.event_id.clone(),
+            announcement_signature: announcement.announcement_signature.encode(),
+            oracle_event: announcement.oracle_event.encode(),
+            name: &data.event_id,
+            is_enum: matches!(
+                announcement.oracle_event.event_descriptor,
+                EventDescriptor::EnumEvent(_)
+            ),
+            oracle_public_key: announcement.oracle_public_key.serialize().to_vec(),
+            attestation_scheme: data.attestation_scheme.to_string(),
+        };
+
+        let mut conn = self.db_pool.get().map_err(|_| Error::StorageFailure)?;
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if Event::get_by_event_id(conn, data.event_id.clone())?.is_some() {
+                return Err(Error::EventAlreadyExists.into());
+            }
+
+            diesel::insert_into(schema::events::table)
+                .values(&new_event)
+                .execute(conn)?;
+            diesel::update(schema::events::table.find(&data.event_id))
+                .set((
+                    schema::events::state.eq(data.state.to_string()),
+                    schema::events::announcement_event_id.eq(announcement_event_id),
+                    schema::events::attestation_event_id.eq(attestation_event_id),
+                ))
+                .execute(conn)?;
+
+            let new_event_nonces = data
+                .indexes
+                .iter()
+                .zip(announcement.oracle_event.oracle_nonces.iter())
+                .map(|(index, nonce)| NewEventNonce {
+                    id: *index as i32,
+                    event_id: data.event_id.clone(),
+                    index: *index as i32,
+                    nonce: nonce.serialize().to_vec(),
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(schema::event_nonces::table)
+                .values(&new_event_nonces)
+                .execute(conn)?;
+
+            for (index, (outcome, sig)) in data.indexes.iter().zip(data.signatures.iter()) {
+                diesel::update(schema::event_nonces::table.find(*index as i32))
+                    .set((
+                        schema::event_nonces::outcome.eq(Some(outcome)),
+                        schema::event_nonces::signature.eq(Some(sig.encode())),
+                    ))
+                    .execute(conn)?;
+            }
+            if !data.signatures.is_empty() {
+                let outcomes = data.signatures.iter().map(|(o, _)| o.clone()).collect();
+                SignIntent::insert_or_get(conn, data.event_id.clone(), outcomes)?;
+            }
+
+            Ok(())
+        })
+        .map_err(|e| {
+            log::error!("Failed to restore event: {}", e);
+            e.downcast::<Error>().unwrap_or(Error::StorageFailure)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::secp256k1::{Keypair, Secp256k1, SecretKey};
+    use diesel::sql_types::Text;
+    use diesel_migrations::MigrationHarness;
+    use kormir::test_utils::{run_storage_conformance_tests, StorageFactory};
+    use std::sync::Mutex;
+
+    /// Creates every storage in its own schema of the database, the schemas
+    /// are dropped when it is dropped
+    struct PostgresStorageFactory {
+        database_url: String,
+        schemas: Mutex<Vec<String>>,
+    }
+
+    impl PostgresStorageFactory {
+        fn open(&self, schema: &str) -> PostgresStorage {
+            let separator = if self.database_url.contains('?') {
+                '&'
+            } else {
+                '?'
+            };
+            let url = format!(
+                "{}{separator}options=-csearch_path%3D{schema}",
+                self.database_url
+            );
+            let db_pool = Pool::builder()
+                .max_size(2)
+                .build(ConnectionManager::<PgConnection>::new(url))
+                .unwrap();
+            db_pool
+                .get()
+                .unwrap()
+                .run_pending_migrations(MIGRATIONS)
+                .unwrap();
+            // events are saved with their own key, this one is never used
+            let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+            let pubkey = Keypair::from_secret_key(&Secp256k1::new(), &secret_key)
+                .x_only_public_key()
+                .0;
+            PostgresStorage::new(db_pool, pubkey).unwrap()
+        }
+    }
+
+    impl Drop for PostgresStorageFactory {
+        fn drop(&mut self) {
+            let Ok(mut conn) = PgConnection::establish(&self.database_url) else {
+                return;
+            };
+            for schema in self.schemas.get_mut().unwrap().drain(..) {
+                let _ =
+                    diesel::sql_query(format!("DROP SCHEMA {schema} CASCADE")).execute(&mut conn);
+            }
+        }
+    }
+
+    impl StorageFactory for PostgresStorageFactory {
+        type Storage = PostgresStorage;
+
+        async fn create(&self) -> PostgresStorage {
+            let schema = format!("kormir_test_{}", thread_rng().gen::<u32>());
+            let mut conn = PgConnection::establish(&self.database_url).unwrap();
+            diesel::sql_query(format!("CREATE SCHEMA {schema}"))
+                .execute(&mut conn)
+                .unwrap();
+            self.schemas.lock().unwrap().push(schema.clone());
+            self.open(&schema)
+        }
+
+        async fn reopen(&self, storage: PostgresStorage) -> PostgresStorage {
+            let mut conn = storage.db_pool.get().unwrap();
+            let schema = diesel::select(diesel::dsl::sql::<Text>("current_schema()"))
+                .get_result::<String>(&mut conn)
+                .unwrap();
+            drop((conn, storage));
+            self.open(&schema)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_postgres_storage_conformance() {
+        // only runs against a postgres database given with DATABASE_URL
+        let Ok(database_url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        if database_url.starts_with("sqlite:") {
+            return;
+        }
+        let factory = PostgresStorageFactory {
+            database_url,
+            schemas: Mutex::new(vec![]),
+        };
+        run_storage_conformance_tests(&factory).await;
+    }
 }
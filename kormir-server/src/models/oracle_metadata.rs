@@ -45,6 +45,24 @@ impl OracleMetadata {
             .optional()?)
     }
 
+    /// Moves the oracle to a new key
+    pub fn set_pubkey(conn: &mut PgConnection, pubkey: XOnlyPublicKey) -> anyhow::Result<()> {
+        diesel::update(oracle_metadata::table)
+            .filter(oracle_metadata::singleton_constant.eq(true))
+            .set(oracle_metadata::pubkey.eq(pubkey.serialize().to_vec()))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Renames the oracle
+    pub fn set_name(conn: &mut PgConnection, name: &str) -> anyhow::Result<()> {
+        diesel::update(oracle_metadata::table)
+            .filter(oracle_metadata::singleton_constant.eq(true))
+            .set(oracle_metadata::name.eq(name))
+            .execute(conn)?;
+        Ok(())
+    }
+
     pub fn upsert(conn: &mut PgConnection, pubkey: XOnlyPublicKey) -> anyhow::Result<()> {
         let pubkey = pubkey.serialize().to_vec();
         let name = "Kormir";
@@ -24,6 +24,19 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         event_id -> Text,
+        state -> Text,
+        oracle_public_key -> Nullable<Bytea>,
+        attestation_scheme -> Text,
+    }
+}
+
+diesel::table! {
+    key_handovers (old_pubkey) {
+        old_pubkey -> Bytea,
+        new_pubkey -> Bytea,
+        effective_at -> Int8,
+        signature -> Bytea,
+        created_at -> Timestamp,
     }
 }
 
@@ -37,6 +50,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sign_intents (event_id) {
+        event_id -> Text,
+        outcomes -> Array<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::joinable!(event_nonces -> events (event_id));
+diesel::joinable!(sign_intents -> events (event_id));
 
-diesel::allow_tables_to_appear_in_same_query!(event_nonces, events, oracle_metadata,);
+diesel::allow_tables_to_appear_in_same_query!(
+    event_nonces,
+    events,
+    key_handovers,
+    oracle_metadata,
+    sign_intents,
+);
@@ -0,0 +1,54 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::schema::sign_intents;
+
+#[derive(
+    Queryable,
+    Insertable,
+    Identifiable,
+    AsChangeset,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    PartialEq,
+)]
+#[diesel(primary_key(event_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SignIntent {
+    pub event_id: String,
+    pub outcomes: Vec<String>,
+    created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = sign_intents)]
+pub struct NewSignIntent {
+    pub event_id: String,
+    pub outcomes: Vec<String>,
+}
+
+impl SignIntent {
+    /// Saves the intent unless one already exists, returning the committed intent.
+    pub fn insert_or_get(
+        conn: &mut PgConnection,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> anyhow::Result<Self> {
+        let new = NewSignIntent {
+            event_id: event_id.clone(),
+            outcomes,
+        };
+        diesel::insert_into(sign_intents::table)
+            .values(&new)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(sign_intents::table.find(event_id).first::<Self>(conn)?)
+    }
+
+    pub fn list(conn: &mut PgConnection) -> anyhow::Result<Vec<Self>> {
+        Ok(sign_intents::table.load::<Self>(conn)?)
+    }
+}
@@ -1,14 +1,25 @@
+use crate::signer::ServerSigner;
 use crate::State;
+use axum::body::Bytes;
 use axum::extract::Path;
 use axum::extract::Query;
-use axum::http::StatusCode;
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::{Extension, Json};
 use bitcoin::key::XOnlyPublicKey;
-use dlc_messages::ser_impls::write_as_tlv;
+use kormir::backup::OracleBackup;
+use kormir::batch::NewEvent;
+use kormir::error::Error;
+use kormir::format::{format_events, EventFormat};
 use kormir::lightning::util::ser::Writeable;
-use kormir::storage::{OracleEventData, Storage};
-use kormir::{OracleAnnouncement, OracleAttestation, Signature};
+use kormir::metadata::SignedOracleMetadata;
+use kormir::numeric::{Decimal, Rounding};
+use kormir::offline::ProposedOutcome;
+use kormir::rotation::KeyHandover;
+use kormir::signer::Signer;
+use kormir::storage::{EventState, EventStatus, Storage};
+use kormir::{OracleAnnouncement, OracleAttestation};
 use nostr::{EventId, JsonUtil};
+use nostr_sdk::RelaySendOptions;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -24,30 +35,68 @@ pub async fn get_pubkey(
     Ok(Json(state.oracle.public_key()))
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyHistory {
+    pub public_key: XOnlyPublicKey,
+    /// Handovers from the oracle's previous keys, oldest first
+    pub handovers: Vec<KeyHandover>,
+}
+
+pub async fn get_key_history(
+    Extension(state): Extension<State>,
+) -> Result<Json<KeyHistory>, (StatusCode, String)> {
+    let handovers = state.oracle.storage.key_handovers().await.map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to get key history".to_string(),
+        )
+    })?;
+
+    Ok(Json(KeyHistory {
+        public_key: state.oracle.public_key(),
+        handovers,
+    }))
+}
+
+pub async fn get_metadata(
+    Extension(state): Extension<State>,
+) -> Result<Json<SignedOracleMetadata>, (StatusCode, String)> {
+    Ok(Json(state.metadata))
+}
+
 pub async fn list_events(
     Query(params): Query<HashMap<String, String>>,
     Extension(state): Extension<State>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
-    let events = state.oracle.storage.list_events().await.map_err(|_| {
+    let mut events = state.oracle.storage.list_events().await.map_err(|_| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to list events".to_string(),
         )
     })?;
 
-    if let Some(format) = params.get("format") {
-        if format == "json" {
-            Ok(list_events_json(&events))
-        } else if format == "hex" {
-            Ok(list_events_hex(&events))
-        } else if format == "tlv" {
-            Ok(list_events_tlv(&events))
-        } else {
-            Err((StatusCode::BAD_REQUEST, "Invalid format".into()))
-        }
-    } else {
-        Ok(list_events_json(&events))
+    let now = now();
+    if let Some(event_state) = params.get("state") {
+        let event_state = event_state
+            .parse::<EventState>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid state".to_string()))?;
+        events.retain(|e| e.state == event_state);
+    }
+    if let Some(status) = params.get("status") {
+        let status = status
+            .parse::<EventStatus>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid status".to_string()))?;
+        events.retain(|e| e.status(now) == status);
     }
+
+    let format = match params.get("format") {
+        Some(format) => format
+            .parse::<EventFormat>()
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid format".to_string()))?,
+        None => EventFormat::Json,
+    };
+
+    Ok(Json(format_events(&events, format, now)))
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -70,32 +119,14 @@ async fn create_enum_event_impl(state: &State, body: CreateEnumEvent) -> anyhow:
 
     log::info!("Created enum event: {hex}");
 
-    let relays = state
-        .client
-        .relays()
-        .await
-        .keys()
-        .map(|x| x.to_string())
-        .collect::<Vec<_>>();
-
-    let event =
-        kormir::nostr_events::create_announcement_event(&state.oracle.nostr_keys(), &ann, &relays)?;
+    let event = announcement_nostr_event(state, &ann).await?;
 
     log::debug!("Broadcasting nostr event: {}", event.as_json());
 
-    state
-        .oracle
-        .storage
-        .add_announcement_event_id(body.event_id, event.id)
-        .await?;
-
-    log::debug!(
-        "Added announcement event id to storage: {}",
-        event.id.to_hex()
-    );
-
     state.client.send_event(event).await?;
 
+    state.oracle.mark_announced(body.event_id).await?;
+
     Ok(hex)
 }
 
@@ -121,6 +152,9 @@ pub async fn create_enum_event(
         Ok(hex) => Ok(Json(hex)),
         Err(e) => {
             eprintln!("Error creating enum event: {:?}", e);
+            if let Some(Error::EventAlreadyExists) = e.downcast_ref::<Error>() {
+                return Err((StatusCode::CONFLICT, "Event already exists".to_string()));
+            }
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Error creating enum event".to_string(),
@@ -133,46 +167,28 @@ pub async fn create_enum_event(
 pub struct SignEnumEvent {
     pub event_id: String,
     pub outcome: String,
+    /// Sign before maturity when the signing policy requires an override
+    #[serde(default)]
+    pub override_maturity: bool,
 }
 
 async fn sign_enum_event_impl(state: &State, body: SignEnumEvent) -> anyhow::Result<String> {
-    let att = state
-        .oracle
-        .sign_enum_event(body.event_id.clone(), body.outcome)
-        .await?;
+    let att = if body.override_maturity {
+        state
+            .oracle
+            .sign_enum_event_early(body.event_id.clone(), body.outcome)
+            .await?
+    } else {
+        state
+            .oracle
+            .sign_enum_event(body.event_id.clone(), body.outcome)
+            .await?
+    };
     let hex = hex::encode(att.encode());
 
     log::info!("Signed enum event: {hex}");
 
-    let data = state
-        .oracle
-        .storage
-        .get_event(body.event_id.clone())
-        .await?;
-    let event_id = data
-        .and_then(|d| {
-            d.announcement_event_id
-                .and_then(|s| EventId::from_hex(s).ok())
-        })
-        .ok_or_else(|| anyhow::anyhow!("Failed to get announcement event id"))?;
-
-    let event =
-        kormir::nostr_events::create_attestation_event(&state.oracle.nostr_keys(), &att, event_id)?;
-
-    log::debug!("Broadcasting nostr event: {}", event.as_json());
-
-    state
-        .oracle
-        .storage
-        .add_attestation_event_id(body.event_id, event.id)
-        .await?;
-
-    log::debug!(
-        "Added announcement event id to storage: {}",
-        event.id.to_hex()
-    );
-
-    state.client.send_event(event).await?;
+    publish_attestation(state, body.event_id, &att).await?;
 
     Ok(hex)
 }
@@ -185,10 +201,7 @@ pub async fn sign_enum_event(
         Ok(hex) => Ok(Json(hex)),
         Err(e) => {
             eprintln!("Error signing enum event: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Error signing enum event".to_string(),
-            ))
+            Err(sign_error_response(e, "Error signing enum event"))
         }
     }
 }
@@ -196,6 +209,7 @@ pub async fn sign_enum_event(
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateNumericEvent {
     pub event_id: String,
+    pub base: Option<u16>,
     pub num_digits: Option<u16>,
     pub is_signed: Option<bool>,
     pub precision: Option<i32>,
@@ -211,6 +225,7 @@ async fn create_numeric_event_impl(
         .oracle
         .create_numeric_event(
             body.event_id.clone(),
+            body.base.unwrap_or(2),
             body.num_digits.unwrap_or(18),
             body.is_signed.unwrap_or(false),
             body.precision.unwrap_or(0),
@@ -222,32 +237,14 @@ async fn create_numeric_event_impl(
 
     log::info!("Created numeric event: {hex}");
 
-    let relays = state
-        .client
-        .relays()
-        .await
-        .keys()
-        .map(|x| x.to_string())
-        .collect::<Vec<_>>();
-
-    let event =
-        kormir::nostr_events::create_announcement_event(&state.oracle.nostr_keys(), &ann, &relays)?;
+    let event = announcement_nostr_event(state, &ann).await?;
 
     log::debug!("Broadcasting nostr event: {}", event.as_json());
 
-    state
-        .oracle
-        .storage
-        .add_announcement_event_id(body.event_id, event.id)
-        .await?;
-
-    log::debug!(
-        "Added announcement event id to storage: {}",
-        event.id.to_hex()
-    );
-
     state.client.send_event(event).await?;
 
+    state.oracle.mark_announced(body.event_id).await?;
+
     Ok(hex)
 }
 
@@ -262,6 +259,13 @@ pub async fn create_numeric_event(
         ));
     }
 
+    if body.base.is_some_and(|base| base < 2) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Base must be at least 2".to_string(),
+        ));
+    }
+
     if body.event_maturity_epoch < now() {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -273,6 +277,9 @@ pub async fn create_numeric_event(
         Ok(hex) => Ok(Json(hex)),
         Err(e) => {
             eprintln!("Error creating numeric event: {:?}", e);
+            if let Some(Error::EventAlreadyExists) = e.downcast_ref::<Error>() {
+                return Err((StatusCode::CONFLICT, "Event already exists".to_string()));
+            }
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Error creating numeric event".to_string(),
@@ -314,28 +321,15 @@ pub async fn get_oracle_attestation_impl(
     state: &State,
     event_id: String,
 ) -> anyhow::Result<OracleAttestation> {
-    let Some(event) = state.oracle.storage.get_event(event_id.clone()).await? else {
+    let Some(event) = state.oracle.storage.get_event(event_id).await? else {
         return Err(anyhow::anyhow!(
             "Announcement by event id is not found in storage."
         ));
     };
 
-    if event.signatures.is_empty() {
-        return Err(anyhow::anyhow!("Attestation not signed."));
-    }
-
-    let (outcomes, signatures): (Vec<String>, Vec<Signature>) = event
-        .signatures
-        .iter()
-        .map(|(outcome, signature)| (outcome.clone(), signature))
-        .unzip();
-
-    Ok(OracleAttestation {
-        event_id,
-        oracle_public_key: state.oracle.public_key(),
-        signatures,
-        outcomes,
-    })
+    event
+        .attestation()
+        .ok_or_else(|| anyhow::anyhow!("Attestation not signed."))
 }
 
 pub async fn get_oracle_attestation(
@@ -357,138 +351,746 @@ pub async fn get_oracle_attestation(
 #[derive(Debug, Clone, Deserialize)]
 pub struct SignNumericEvent {
     pub event_id: String,
-    pub outcome: i64,
+    /// The raw outcome, already scaled by the announced precision
+    pub outcome: Option<i64>,
+    /// A decimal value like `"1234.56"` to sign instead of `outcome`
+    pub value: Option<String>,
+    /// How `value` is rounded to the announced precision
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// Sign the nearest representable outcome for a `value` out of range
+    #[serde(default)]
+    pub clamp: bool,
+    /// Sign before maturity when the signing policy requires an override
+    #[serde(default)]
+    pub override_maturity: bool,
 }
 
 async fn sign_numeric_event_impl(
     state: &State,
     body: crate::routes::SignNumericEvent,
 ) -> anyhow::Result<String> {
-    let att = state
-        .oracle
-        .sign_numeric_event(body.event_id.clone(), body.outcome)
-        .await?;
+    let outcome = match (body.outcome, &body.value) {
+        (Some(outcome), None) => outcome,
+        (None, Some(value)) => {
+            let value = value.parse::<Decimal>()?;
+            state
+                .oracle
+                .decimal_outcome(&body.event_id, value, body.rounding, body.clamp)
+                .await?
+        }
+        _ => return Err(Error::InvalidArgument.into()),
+    };
+    let att = if body.override_maturity {
+        state
+            .oracle
+            .sign_numeric_event_early(body.event_id.clone(), outcome)
+            .await?
+    } else {
+        state
+            .oracle
+            .sign_numeric_event(body.event_id.clone(), outcome)
+            .await?
+    };
     let hex = hex::encode(att.encode());
 
     log::info!("Signed numeric event: {hex}");
 
+    publish_attestation(state, body.event_id, &att).await?;
+
+    Ok(hex)
+}
+
+pub async fn sign_numeric_event(
+    Extension(state): Extension<State>,
+    Json(body): Json<crate::routes::SignNumericEvent>,
+) -> Result<Json<String>, (StatusCode, String)> {
+    match crate::routes::sign_numeric_event_impl(&state, body).await {
+        Ok(hex) => Ok(Json(hex)),
+        Err(e) => {
+            eprintln!("Error signing numeric event: {:?}", e);
+            Err(sign_error_response(e, "Error signing numeric event"))
+        }
+    }
+}
+
+/// Maps errors from signing an event to a response, `fallback` is used for
+/// unexpected errors.
+fn sign_error_response(e: anyhow::Error, fallback: &str) -> (StatusCode, String) {
+    let Some(e) = e.downcast_ref::<Error>() else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, fallback.to_string());
+    };
+    let status = match e {
+        Error::NotFound => StatusCode::NOT_FOUND,
+        Error::InvalidOutcome | Error::InvalidArgument => StatusCode::BAD_REQUEST,
+        Error::EventAlreadySigned | Error::ConflictingSignIntent | Error::EventCancelled => {
+            StatusCode::CONFLICT
+        }
+        Error::EventNotMatured => StatusCode::FORBIDDEN,
+        Error::MaturityOverrideRequired => StatusCode::PRECONDITION_REQUIRED,
+        _ => return (StatusCode::INTERNAL_SERVER_ERROR, fallback.to_string()),
+    };
+    (status, e.to_string())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CancelEvent {
+    pub event_id: String,
+}
+
+async fn cancel_event_impl(state: &State, body: CancelEvent) -> anyhow::Result<Option<String>> {
+    // the notice refers to the announcement, it must have been published
     let data = state
         .oracle
         .storage
         .get_event(body.event_id.clone())
-        .await?;
-    let event_id = data
-        .and_then(|d| {
-            d.announcement_event_id
-                .and_then(|s| EventId::from_hex(s).ok())
-        })
+        .await?
+        .ok_or(Error::NotFound)?;
+    let announcement_event_id = data
+        .announcement_event_id
+        .and_then(|s| EventId::from_hex(s).ok())
         .ok_or_else(|| anyhow::anyhow!("Failed to get announcement event id"))?;
 
-    let event =
-        kormir::nostr_events::create_attestation_event(&state.oracle.nostr_keys(), &att, event_id)?;
-
-    log::debug!("Broadcasting nostr event: {}", event.as_json());
+    let att = state.oracle.cancel_event(body.event_id.clone()).await?;
 
-    state
-        .oracle
-        .storage
-        .add_attestation_event_id(body.event_id, event.id)
-        .await?;
+    if let Some(att) = &att {
+        log::info!("Attested cancelled outcome for event: {}", body.event_id);
+        if data.attestation_event_id.is_none() {
+            publish_attestation(state, body.event_id.clone(), att).await?;
+        }
+    }
 
-    log::debug!(
-        "Added announcement event id to storage: {}",
-        event.id.to_hex()
+    let event = kormir::nostr_events::create_cancellation_event(
+        state.oracle.nostr_public_key(),
+        &body.event_id,
+        announcement_event_id,
     );
+    let event = state.oracle.sign_nostr_event(event).await?;
+
+    log::debug!("Broadcasting nostr event: {}", event.as_json());
 
     state.client.send_event(event).await?;
 
-    Ok(hex)
+    Ok(att.map(|att| hex::encode(att.encode())))
 }
 
-pub async fn sign_numeric_event(
+pub async fn cancel_event(
     Extension(state): Extension<State>,
-    Json(body): Json<crate::routes::SignNumericEvent>,
-) -> Result<Json<String>, (StatusCode, String)> {
-    match crate::routes::sign_numeric_event_impl(&state, body).await {
+    Json(body): Json<CancelEvent>,
+) -> Result<Json<Option<String>>, (StatusCode, String)> {
+    match cancel_event_impl(&state, body).await {
         Ok(hex) => Ok(Json(hex)),
         Err(e) => {
-            eprintln!("Error signing numeric event: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Error signing numeric event".to_string(),
-            ))
+            eprintln!("Error cancelling event: {:?}", e);
+            match e.downcast_ref::<Error>() {
+                Some(Error::NotFound) => {
+                    Err((StatusCode::NOT_FOUND, "Event not found".to_string()))
+                }
+                Some(Error::EventAlreadySigned) => {
+                    Err((StatusCode::CONFLICT, "Event was already signed".to_string()))
+                }
+                Some(Error::ConflictingSignIntent) => Err((
+                    StatusCode::CONFLICT,
+                    "Event is being signed with another outcome".to_string(),
+                )),
+                _ => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error cancelling event".to_string(),
+                )),
+            }
         }
     }
 }
 
-fn now() -> u32 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32
+/// An event to create with `/create-batch`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CreateBatchEvent {
+    Enum(CreateEnumEvent),
+    Numeric(CreateNumericEvent),
 }
 
-fn list_events_json(events: &Vec<OracleEventData>) -> Json<Value> {
-    Json(serde_json::to_value(events).unwrap())
+impl CreateBatchEvent {
+    fn event_id(&self) -> &str {
+        match self {
+            CreateBatchEvent::Enum(body) => &body.event_id,
+            CreateBatchEvent::Numeric(body) => &body.event_id,
+        }
+    }
+
+    /// Checks the request like the single event endpoints do
+    fn into_new_event(self, now: u32) -> Result<NewEvent, String> {
+        let event_maturity_epoch = match &self {
+            CreateBatchEvent::Enum(body) => body.event_maturity_epoch,
+            CreateBatchEvent::Numeric(body) => body.event_maturity_epoch,
+        };
+        if event_maturity_epoch < now {
+            return Err("Event maturity epoch must be in the future".to_string());
+        }
+
+        let new_event = match self {
+            CreateBatchEvent::Enum(body) => {
+                if body.outcomes.is_empty() {
+                    return Err("Must have at least one outcome".to_string());
+                }
+                NewEvent::Enum {
+                    event_id: body.event_id,
+                    outcomes: body.outcomes,
+                    event_maturity_epoch: body.event_maturity_epoch,
+                }
+            }
+            CreateBatchEvent::Numeric(body) => {
+                if body.num_digits == Some(0) {
+                    return Err("Number of digits must be greater than 0".to_string());
+                }
+                if body.base.is_some_and(|base| base < 2) {
+                    return Err("Base must be at least 2".to_string());
+                }
+                NewEvent::Numeric {
+                    event_id: body.event_id,
+                    base: body.base.unwrap_or(2),
+                    num_digits: body.num_digits.unwrap_or(18),
+                    is_signed: body.is_signed.unwrap_or(false),
+                    precision: body.precision.unwrap_or(0),
+                    unit: body.unit,
+                    event_maturity_epoch: body.event_maturity_epoch,
+                }
+            }
+        };
+        Ok(new_event)
+    }
 }
 
+/// The outcome of one event of a batch, with the hex encoded announcement or
+/// attestation on success. Events that were saved but could not be published
+/// have both.
 #[derive(Debug, Clone, Serialize)]
-struct HexEvent {
+pub struct BatchResult {
     pub event_id: String,
-    pub event_maturity_epoch: u32,
-    pub announcement: String,
-    pub attestation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
-fn list_events_hex(events: &[OracleEventData]) -> Json<Value> {
-    let hex_events = events
+impl BatchResult {
+    fn new(event_id: String, result: Result<String, String>) -> Self {
+        match result {
+            Ok(hex) => Self {
+                event_id,
+                hex: Some(hex),
+                error: None,
+            },
+            Err(error) => Self {
+                event_id,
+                hex: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    fn publish_failed(&mut self, error: impl std::fmt::Display) {
+        self.error = Some(format!("Failed to publish: {error}"));
+    }
+}
+
+async fn create_batch_impl(
+    state: &State,
+    body: Vec<CreateBatchEvent>,
+) -> anyhow::Result<Vec<BatchResult>> {
+    let now = now();
+    let event_ids = body
         .iter()
-        .map(|e| {
-            let attestation = assemble_attestation(e);
-            HexEvent {
-                event_id: e.announcement.oracle_event.event_id.clone(),
-                event_maturity_epoch: e.announcement.oracle_event.event_maturity_epoch,
-                announcement: hex::encode(e.announcement.encode()),
-                attestation: attestation.map(|a| hex::encode(a.encode())),
-            }
-        })
+        .map(|event| event.event_id().to_string())
+        .collect::<Vec<_>>();
+    let checked = body
+        .into_iter()
+        .map(|event| event.into_new_event(now))
+        .collect::<Vec<_>>();
+    let new_events = checked
+        .iter()
+        .filter_map(|event| event.as_ref().ok().cloned())
         .collect::<Vec<_>>();
-    Json(serde_json::to_value(hex_events).unwrap())
+    let mut created = state
+        .oracle
+        .create_events_batch(new_events)
+        .await?
+        .into_iter();
+
+    let mut results = Vec::with_capacity(checked.len());
+    let mut events = Vec::new();
+    for (event_id, checked) in event_ids.into_iter().zip(checked) {
+        let result = match checked {
+            Ok(_) => match created.next().ok_or(Error::Internal)? {
+                Ok(ann) => {
+                    // the event is saved, a failure from here on is only reported
+                    let mut result = BatchResult::new(event_id, Ok(hex::encode(ann.encode())));
+                    match announcement_nostr_event(state, &ann).await {
+                        Ok(event) => events.push((results.len(), event)),
+                        Err(e) => result.publish_failed(e),
+                    }
+                    result
+                }
+                Err(e) => BatchResult::new(event_id, Err(e.to_string())),
+            },
+            Err(e) => BatchResult::new(event_id, Err(e)),
+        };
+        results.push(result);
+    }
+
+    log::info!("Created {} events in a batch", events.len());
+
+    let (published, events): (Vec<usize>, Vec<nostr::Event>) = events.into_iter().unzip();
+    if let Err(e) = publish_batch(state, events).await {
+        log::error!("Failed to publish batch: {e}");
+        for i in published {
+            results[i].publish_failed(&e);
+        }
+        return Ok(results);
+    }
+
+    for i in published {
+        let result = &mut results[i];
+        if let Err(e) = state.oracle.mark_announced(result.event_id.clone()).await {
+            result.error = Some(format!("Failed to mark announced: {e}"));
+        }
+    }
+
+    Ok(results)
+}
+
+pub async fn create_batch(
+    Extension(state): Extension<State>,
+    Json(body): Json<Vec<CreateBatchEvent>>,
+) -> Result<Json<Vec<BatchResult>>, (StatusCode, String)> {
+    match create_batch_impl(&state, body).await {
+        Ok(results) => Ok(Json(results)),
+        Err(e) => {
+            eprintln!("Error creating events: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error creating events".to_string(),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignBatchEvent {
+    pub event_id: String,
+    pub outcome: ProposedOutcome,
 }
 
-fn list_events_tlv(events: &[OracleEventData]) -> Json<Value> {
-    let tlv_events = events
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignBatch {
+    pub events: Vec<SignBatchEvent>,
+    /// Sign before maturity when the signing policy requires an override
+    #[serde(default)]
+    pub override_maturity: bool,
+}
+
+async fn sign_batch_impl(state: &State, body: SignBatch) -> anyhow::Result<Vec<BatchResult>> {
+    let outcomes = body
+        .events
+        .into_iter()
+        .map(|event| (event.event_id, event.outcome))
+        .collect::<Vec<_>>();
+    let event_ids = outcomes
         .iter()
-        .map(|e| {
-            let attestation = assemble_attestation(e);
-            HexEvent {
-                event_id: e.announcement.oracle_event.event_id.clone(),
-                event_maturity_epoch: e.announcement.oracle_event.event_maturity_epoch,
-                announcement: {
-                    let mut bytes = Vec::new();
-                    write_as_tlv(&e.announcement, &mut bytes).unwrap();
-                    hex::encode(bytes)
-                },
-                attestation: attestation.map(|a| {
-                    let mut bytes = Vec::new();
-                    write_as_tlv(&a, &mut bytes).unwrap();
-                    hex::encode(bytes)
-                }),
+        .map(|(event_id, _)| event_id.clone())
+        .collect::<Vec<_>>();
+    let attestations = state
+        .oracle
+        .sign_events_batch(outcomes, body.override_maturity)
+        .await;
+
+    let mut results = Vec::with_capacity(attestations.len());
+    let mut events = Vec::new();
+    for (event_id, att) in event_ids.into_iter().zip(attestations) {
+        let result = match att {
+            Ok(att) => {
+                log::info!("Signed event: {event_id}");
+                match attestation_nostr_event(state, event_id.clone(), &att).await {
+                    Ok(event) => {
+                        events.push((results.len(), event));
+                        Ok(hex::encode(att.encode()))
+                    }
+                    Err(e) => Err(e.to_string()),
+                }
             }
+            Err(e) => Err(e.to_string()),
+        };
+        results.push(BatchResult::new(event_id, result));
+    }
+
+    // the attestations are saved, a failed publish is only reported
+    let (published, events): (Vec<usize>, Vec<nostr::Event>) = events.into_iter().unzip();
+    if let Err(e) = publish_batch(state, events).await {
+        log::error!("Failed to publish batch: {e}");
+        for i in published {
+            results[i].publish_failed(&e);
+        }
+    }
+
+    Ok(results)
+}
+
+pub async fn sign_batch(
+    Extension(state): Extension<State>,
+    Json(body): Json<SignBatch>,
+) -> Result<Json<Vec<BatchResult>>, (StatusCode, String)> {
+    match sign_batch_impl(&state, body).await {
+        Ok(results) => Ok(Json(results)),
+        Err(e) => {
+            eprintln!("Error signing events: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error signing events".to_string(),
+            ))
+        }
+    }
+}
+
+/// Broadcasts the nostr events of a batch at once
+async fn publish_batch(state: &State, events: Vec<nostr::Event>) -> anyhow::Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    log::debug!("Broadcasting {} nostr events", events.len());
+
+    state
+        .client
+        .batch_event(events, RelaySendOptions::new())
+        .await?;
+
+    Ok(())
+}
+
+/// Exports the oracle's keys and events, encrypted with
+/// `KORMIR_BACKUP_PASSPHRASE`. Only available with a local signer.
+async fn export_backup_impl(state: &State) -> anyhow::Result<Vec<u8>> {
+    let passphrase = state
+        .backup_passphrase
+        .clone()
+        .ok_or(Error::InvalidArgument)?;
+    let ServerSigner::Local(signer) = state.oracle.signer() else {
+        anyhow::bail!("Backups are not supported with a remote signer");
+    };
+    let retired_signers = state
+        .oracle
+        .retired_signers()
+        .iter()
+        .map(|s| match s {
+            ServerSigner::Local(s) => Ok(s.clone()),
+            ServerSigner::Remote(_) => Err(anyhow::anyhow!(
+                "Backups are not supported with a remote signer"
+            )),
         })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let backup = OracleBackup::create(&state.oracle.storage, signer, &retired_signers).await?;
+    // scrypt takes a while, keep it off the runtime
+    let bytes =
+        tokio::task::spawn_blocking(move || backup.encrypt(passphrase.expose_secret())).await??;
+    Ok(bytes)
+}
+
+pub async fn export_backup(
+    Extension(state): Extension<State>,
+    headers: HeaderMap,
+) -> Result<Vec<u8>, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    if state.backup_passphrase.is_none() {
+        return Err(backups_disabled());
+    }
+
+    match export_backup_impl(&state).await {
+        Ok(bytes) => {
+            log::info!("Exported oracle backup");
+            Ok(bytes)
+        }
+        Err(e) => {
+            eprintln!("Error exporting backup: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error exporting backup".to_string(),
+            ))
+        }
+    }
+}
+
+/// Restores the events of a backup of the oracle's current or a retired key,
+/// encrypted with `KORMIR_BACKUP_PASSPHRASE`.
+async fn import_backup_impl(state: &State, body: Bytes) -> anyhow::Result<usize> {
+    let passphrase = state
+        .backup_passphrase
+        .clone()
+        .ok_or(Error::InvalidArgument)?;
+    let backup = tokio::task::spawn_blocking(move || {
+        OracleBackup::decrypt(&body, passphrase.expose_secret())
+    })
+    .await??;
+
+    // the oracle must be able to sign the restored events
+    state.oracle.signer_for(&backup.public_key())?;
+    backup.restore(&state.oracle.storage).await?;
+    Ok(backup.events.len())
+}
+
+pub async fn import_backup(
+    Extension(state): Extension<State>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<usize>, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+    if state.backup_passphrase.is_none() {
+        return Err(backups_disabled());
+    }
+
+    match import_backup_impl(&state, body).await {
+        Ok(count) => {
+            log::info!("Restored {count} events from backup");
+            Ok(Json(count))
+        }
+        Err(e) => {
+            eprintln!("Error importing backup: {:?}", e);
+            match e.downcast_ref::<Error>() {
+                Some(Error::InvalidArgument) => Err((
+                    StatusCode::BAD_REQUEST,
+                    "Invalid backup, passphrase or oracle key".to_string(),
+                )),
+                Some(Error::EventAlreadyExists) => Err((
+                    StatusCode::CONFLICT,
+                    "Backup conflicts with existing events".to_string(),
+                )),
+                _ => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error importing backup".to_string(),
+                )),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HandOverKey {
+    /// Unix timestamp from which events are announced with the new key
+    pub effective_at: u32,
+}
+
+/// Has the retired key in the database sign a handover to the current key
+/// and makes the current key the oracle's key.
+async fn hand_over_key_impl(state: &State, effective_at: u32) -> anyhow::Result<KeyHandover> {
+    let pubkey = state.oracle.public_key();
+    let Some(old_pubkey) = state.oracle.storage.oracle_public_key().await? else {
+        return Err(Error::InvalidArgument.into());
+    };
+    if old_pubkey == pubkey {
+        return Err(Error::InvalidArgument.into());
+    }
+
+    let old_signer = state.oracle.signer_for(&old_pubkey)?;
+    let handover = old_signer.sign_key_handover(pubkey, effective_at).await?;
+    handover
+        .verify(&bitcoin::secp256k1::Secp256k1::verification_only())
+        .map_err(|_| Error::SignerFailure)?;
+    state.oracle.storage.save_key_handover(&handover).await?;
+    state.oracle.storage.set_oracle_public_key(pubkey).await?;
+    log::info!(
+        "Handed oracle over from {} to {}",
+        hex::encode(old_pubkey.serialize()),
+        hex::encode(pubkey.serialize()),
+    );
+
+    if let Err(e) = publish_key_handover(state, old_signer, &handover).await {
+        log::error!("Failed to publish key handover: {e}");
+    }
+
+    Ok(handover)
+}
+
+pub async fn hand_over_key(
+    Extension(state): Extension<State>,
+    headers: HeaderMap,
+    Json(body): Json<HandOverKey>,
+) -> Result<Json<KeyHandover>, (StatusCode, String)> {
+    check_admin_token(&state, &headers)?;
+
+    match hand_over_key_impl(&state, body.effective_at).await {
+        Ok(handover) => Ok(Json(handover)),
+        Err(e) => {
+            eprintln!("Error handing over key: {:?}", e);
+            match e.downcast_ref::<Error>() {
+                Some(Error::InvalidArgument) => Err((
+                    StatusCode::CONFLICT,
+                    "No key handover is pending".to_string(),
+                )),
+                _ => Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error handing over key".to_string(),
+                )),
+            }
+        }
+    }
+}
+
+/// Admin routes need `Authorization: Bearer <KORMIR_ADMIN_TOKEN>`, and are
+/// disabled when no token is set.
+fn check_admin_token(state: &State, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let Some(admin_token) = &state.admin_token else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Admin routes are disabled, set KORMIR_ADMIN_TOKEN".to_string(),
+        ));
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // compare in constant time, only the length leaks
+    let expected = admin_token.expose_secret().as_bytes();
+    let matches = token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if !matches {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+    Ok(())
+}
+
+fn backups_disabled() -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        "Backups are disabled, set KORMIR_BACKUP_PASSPHRASE".to_string(),
+    )
+}
+
+/// Publishes the attestation as a nostr event that refers to the event's
+/// announcement, and saves the nostr event id.
+pub async fn publish_attestation(
+    state: &State,
+    event_id: String,
+    att: &OracleAttestation,
+) -> anyhow::Result<()> {
+    let event = attestation_nostr_event(state, event_id, att).await?;
+
+    log::debug!("Broadcasting nostr event: {}", event.as_json());
+
+    state.client.send_event(event).await?;
+
+    Ok(())
+}
+
+/// Creates and signs the nostr event of the announcement, and saves its id.
+async fn announcement_nostr_event(
+    state: &State,
+    ann: &OracleAnnouncement,
+) -> anyhow::Result<nostr::Event> {
+    let relays = state
+        .client
+        .relays()
+        .await
+        .keys()
+        .map(|x| x.to_string())
         .collect::<Vec<_>>();
-    Json(serde_json::to_value(tlv_events).unwrap())
+
+    let event = kormir::nostr_events::create_announcement_event(
+        state.oracle.nostr_public_key(),
+        ann,
+        &relays,
+    );
+    let event = state.oracle.sign_nostr_event(event).await?;
+
+    state
+        .oracle
+        .storage
+        .add_announcement_event_id(ann.oracle_event.event_id.clone(), event.id)
+        .await?;
+
+    log::debug!(
+        "Added announcement event id to storage: {}",
+        event.id.to_hex()
+    );
+
+    Ok(event)
 }
 
-fn assemble_attestation(e: &OracleEventData) -> Option<OracleAttestation> {
-    if e.signatures.is_empty() {
-        None
-    } else {
-        Some(OracleAttestation {
-            event_id: e.announcement.oracle_event.event_id.clone(),
-            oracle_public_key: e.announcement.oracle_public_key,
-            signatures: e.signatures.iter().map(|x| x.1).collect(),
-            outcomes: e.signatures.iter().map(|x| x.0.clone()).collect(),
+/// Creates and signs the nostr event of the attestation, referring to the
+/// event's announcement, and saves its id.
+async fn attestation_nostr_event(
+    state: &State,
+    event_id: String,
+    att: &OracleAttestation,
+) -> anyhow::Result<nostr::Event> {
+    let data = state.oracle.storage.get_event(event_id.clone()).await?;
+    let announcement_event_id = data
+        .and_then(|d| {
+            d.announcement_event_id
+                .and_then(|s| EventId::from_hex(s).ok())
         })
-    }
+        .ok_or_else(|| anyhow::anyhow!("Failed to get announcement event id"))?;
+
+    let event = kormir::nostr_events::create_attestation_event(
+        state.oracle.nostr_public_key(),
+        att,
+        announcement_event_id,
+    );
+    let event = state.oracle.sign_nostr_event(event).await?;
+
+    state
+        .oracle
+        .storage
+        .add_attestation_event_id(event_id, event.id)
+        .await?;
+
+    log::debug!(
+        "Added attestation event id to storage: {}",
+        event.id.to_hex()
+    );
+
+    Ok(event)
+}
+
+/// Publishes the oracle's signed metadata as its nostr profile.
+pub async fn publish_metadata(state: &State) -> anyhow::Result<()> {
+    let event = kormir::nostr_events::create_metadata_event(
+        state.oracle.nostr_public_key(),
+        &state.metadata,
+    );
+    let event = state.oracle.sign_nostr_event(event).await?;
+
+    log::debug!("Broadcasting nostr event: {}", event.as_json());
+
+    state.client.send_event(event).await?;
+
+    Ok(())
+}
+
+/// Publishes the handover signed by the old key, so its followers find the new one.
+pub async fn publish_key_handover(
+    state: &State,
+    old_signer: &impl Signer,
+    handover: &KeyHandover,
+) -> anyhow::Result<()> {
+    let pubkey = nostr::PublicKey::from_slice(&handover.old_public_key.serialize())?;
+    let event = kormir::nostr_events::create_key_handover_event(pubkey, handover);
+    let event = old_signer.sign_nostr_event(event).await?;
+
+    log::debug!("Broadcasting key handover event: {}", event.as_json());
+
+    state.client.send_event(event).await?;
+
+    Ok(())
+}
+
+pub(crate) fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
 }
@@ -0,0 +1,90 @@
+use bitcoin::key::XOnlyPublicKey;
+use kormir::error::Error;
+use kormir::metadata::OracleMetadata;
+use kormir::remote_signer::RemoteSigner;
+use kormir::rotation::KeyHandover;
+use kormir::signer::{LocalSigner, Signer};
+use kormir::{AttestationScheme, NonceDerivation, OracleAnnouncement, OracleEvent, Signature};
+
+/// The signer selected by `KORMIR_SIGNER_SOCKET`, falls back to `KORMIR_KEY`
+#[derive(Debug, Clone)]
+pub enum ServerSigner {
+    Local(LocalSigner),
+    Remote(RemoteSigner),
+}
+
+impl Signer for ServerSigner {
+    fn public_key(&self) -> XOnlyPublicKey {
+        match self {
+            ServerSigner::Local(s) => s.public_key(),
+            ServerSigner::Remote(s) => s.public_key(),
+        }
+    }
+
+    async fn sign_announcement(
+        &self,
+        oracle_event: &OracleEvent,
+        scheme: AttestationScheme,
+    ) -> Result<Signature, Error> {
+        match self {
+            ServerSigner::Local(s) => s.sign_announcement(oracle_event, scheme).await,
+            ServerSigner::Remote(s) => s.sign_announcement(oracle_event, scheme).await,
+        }
+    }
+
+    async fn nonce_public_keys(
+        &self,
+        oracle_event: &OracleEvent,
+        indexes: &[u32],
+        derivation: NonceDerivation,
+    ) -> Result<Vec<XOnlyPublicKey>, Error> {
+        match self {
+            ServerSigner::Local(s) => s.nonce_public_keys(oracle_event, indexes, derivation).await,
+            ServerSigner::Remote(s) => s.nonce_public_keys(oracle_event, indexes, derivation).await,
+        }
+    }
+
+    async fn sign_outcomes(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+        outcomes: &[String],
+        scheme: AttestationScheme,
+    ) -> Result<Vec<Signature>, Error> {
+        match self {
+            ServerSigner::Local(s) => {
+                s.sign_outcomes(announcement, indexes, outcomes, scheme)
+                    .await
+            }
+            ServerSigner::Remote(s) => {
+                s.sign_outcomes(announcement, indexes, outcomes, scheme)
+                    .await
+            }
+        }
+    }
+
+    async fn sign_nostr_event(&self, event: nostr::UnsignedEvent) -> Result<nostr::Event, Error> {
+        match self {
+            ServerSigner::Local(s) => s.sign_nostr_event(event).await,
+            ServerSigner::Remote(s) => s.sign_nostr_event(event).await,
+        }
+    }
+
+    async fn sign_key_handover(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error> {
+        match self {
+            ServerSigner::Local(s) => s.sign_key_handover(new_public_key, effective_at).await,
+            ServerSigner::Remote(s) => s.sign_key_handover(new_public_key, effective_at).await,
+        }
+    }
+
+    async fn sign_metadata(&self, metadata: &OracleMetadata) -> Result<Signature, Error> {
+        match self {
+            ServerSigner::Local(s) => s.sign_metadata(metadata).await,
+            ServerSigner::Remote(s) => s.sign_metadata(metadata).await,
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::models::PostgresStorage;
+use bitcoin::key::XOnlyPublicKey;
+use kormir::error::Error;
+use kormir::rotation::KeyHandover;
+use kormir::sqlite::SqliteStorage;
+use kormir::storage::{EventState, OracleEventData, Storage};
+use kormir::AttestationScheme;
+use kormir::{OracleAnnouncement, Signature};
+use nostr::EventId;
+
+/// The storage backend selected by `DATABASE_URL`
+#[derive(Clone)]
+pub enum ServerStorage {
+    Postgres(PostgresStorage),
+    Sqlite(SqliteStorage),
+}
+
+impl ServerStorage {
+    pub async fn oracle_public_key(&self) -> Result<Option<XOnlyPublicKey>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.oracle_public_key().await,
+            ServerStorage::Sqlite(s) => s.oracle_public_key(),
+        }
+    }
+
+    pub async fn set_oracle_public_key(&self, pubkey: XOnlyPublicKey) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.set_oracle_public_key(pubkey).await,
+            ServerStorage::Sqlite(s) => s.set_oracle_public_key(pubkey),
+        }
+    }
+
+    pub async fn oracle_name(&self) -> Result<Option<String>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.oracle_name().await,
+            ServerStorage::Sqlite(s) => s.oracle_name(),
+        }
+    }
+
+    pub async fn set_oracle_name(&self, name: &str) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.set_oracle_name(name).await,
+            ServerStorage::Sqlite(s) => s.set_oracle_name(name),
+        }
+    }
+
+    pub async fn save_key_handover(&self, handover: &KeyHandover) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.save_key_handover(handover).await,
+            ServerStorage::Sqlite(s) => s.save_key_handover(handover),
+        }
+    }
+
+    pub async fn key_handovers(&self) -> Result<Vec<KeyHandover>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.key_handovers().await,
+            ServerStorage::Sqlite(s) => s.key_handovers(),
+        }
+    }
+
+    pub async fn add_announcement_event_id(
+        &self,
+        event_id: String,
+        nostr_event_id: EventId,
+    ) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => {
+                s.add_announcement_event_id(event_id, nostr_event_id).await
+            }
+            ServerStorage::Sqlite(s) => {
+                s.add_announcement_event_id(event_id, nostr_event_id.to_hex())
+                    .await
+            }
+        }
+    }
+
+    pub async fn add_attestation_event_id(
+        &self,
+        event_id: String,
+        nostr_event_id: EventId,
+    ) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => {
+                s.add_attestation_event_id(event_id, nostr_event_id).await
+            }
+            ServerStorage::Sqlite(s) => {
+                s.add_attestation_event_id(event_id, nostr_event_id.to_hex())
+                    .await
+            }
+        }
+    }
+}
+
+impl Storage for ServerStorage {
+    async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.get_next_nonce_indexes(num).await,
+            ServerStorage::Sqlite(s) => s.get_next_nonce_indexes(num).await,
+        }
+    }
+
+    async fn save_announcement(
+        &self,
+        announcement: OracleAnnouncement,
+        indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<String, Error> {
+        match self {
+            ServerStorage::Postgres(s) => {
+                s.save_announcement(announcement, indexes, attestation_scheme)
+                    .await
+            }
+            ServerStorage::Sqlite(s) => {
+                s.save_announcement(announcement, indexes, attestation_scheme)
+                    .await
+            }
+        }
+    }
+
+    async fn save_announcements(
+        &self,
+        announcements: Vec<(OracleAnnouncement, Vec<u32>)>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<Vec<String>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => {
+                s.save_announcements(announcements, attestation_scheme)
+                    .await
+            }
+            ServerStorage::Sqlite(s) => {
+                s.save_announcements(announcements, attestation_scheme)
+                    .await
+            }
+        }
+    }
+
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.save_sign_intent(event_id, outcomes).await,
+            ServerStorage::Sqlite(s) => s.save_sign_intent(event_id, outcomes).await,
+        }
+    }
+
+    async fn save_signatures(
+        &self,
+        event_id: String,
+        sigs: Vec<(String, Signature)>,
+    ) -> Result<OracleEventData, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.save_signatures(event_id, sigs).await,
+            ServerStorage::Sqlite(s) => s.save_signatures(event_id, sigs).await,
+        }
+    }
+
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.set_event_state(event_id, state).await,
+            ServerStorage::Sqlite(s) => s.set_event_state(event_id, state).await,
+        }
+    }
+
+    async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.get_event(event_id).await,
+            ServerStorage::Sqlite(s) => s.get_event(event_id).await,
+        }
+    }
+
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.list_events().await,
+            ServerStorage::Sqlite(s) => s.list_events().await,
+        }
+    }
+
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.list_sign_intents().await,
+            ServerStorage::Sqlite(s) => s.list_sign_intents().await,
+        }
+    }
+
+    async fn get_nonce_counter(&self) -> Result<u32, Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.get_nonce_counter().await,
+            ServerStorage::Sqlite(s) => s.get_nonce_counter().await,
+        }
+    }
+
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.set_nonce_counter(next).await,
+            ServerStorage::Sqlite(s) => s.set_nonce_counter(next).await,
+        }
+    }
+
+    async fn restore_event(&self, data: OracleEventData) -> Result<(), Error> {
+        match self {
+            ServerStorage::Postgres(s) => s.restore_event(data).await,
+            ServerStorage::Sqlite(s) => s.restore_event(data).await,
+        }
+    }
+}
@@ -9,15 +9,36 @@ pub enum JsError {
     /// Invalid argument given
     #[error("Invalid argument given")]
     InvalidArgument,
+    /// Attempted to announce an event with an id that is already used
+    #[error("An event with this id already exists")]
+    EventAlreadyExists,
     /// Attempted to sign an event that was already signed
     #[error("Attempted to sign an event that was already signed")]
     EventAlreadySigned,
+    /// Attempted to sign a different outcome than the one already committed to
+    #[error("A different outcome was already committed to for this event")]
+    ConflictingSignIntent,
+    /// Attempted to sign an event that was cancelled
+    #[error("Event was cancelled")]
+    EventCancelled,
+    /// The event can not move to the requested lifecycle state
+    #[error("Invalid event state transition")]
+    InvalidStateTransition,
+    /// The signing policy refuses to sign the event before its maturity
+    #[error("Event has not matured yet")]
+    EventNotMatured,
+    /// The signing policy requires an explicit override to sign before maturity
+    #[error("Signing before maturity requires an explicit override")]
+    MaturityOverrideRequired,
     /// Event data was not found
     #[error("Event data was not found")]
     NotFound,
     /// The storage failed to read/save the data
     #[error("Storage failed to read/save the data")]
     StorageFailure,
+    /// The signer failed or refused to sign
+    #[error("The signer failed or refused to sign")]
+    SignerFailure,
     /// User gave an invalid outcome
     #[error("User gave an invalid outcome")]
     InvalidOutcome,
@@ -33,9 +54,16 @@ impl From<Error> for JsError {
     fn from(value: Error) -> Self {
         match value {
             Error::InvalidArgument => Self::InvalidArgument,
+            Error::EventAlreadyExists => Self::EventAlreadyExists,
             Error::EventAlreadySigned => Self::EventAlreadySigned,
+            Error::ConflictingSignIntent => Self::ConflictingSignIntent,
+            Error::EventCancelled => Self::EventCancelled,
+            Error::InvalidStateTransition => Self::InvalidStateTransition,
+            Error::EventNotMatured => Self::EventNotMatured,
+            Error::MaturityOverrideRequired => Self::MaturityOverrideRequired,
             Error::NotFound => Self::NotFound,
             Error::StorageFailure => Self::StorageFailure,
+            Error::SignerFailure => Self::SignerFailure,
             Error::InvalidOutcome => Self::InvalidOutcome,
             Error::Internal => Self::Internal,
         }
@@ -46,9 +74,16 @@ impl From<JsError> for Error {
     fn from(value: JsError) -> Self {
         match value {
             JsError::InvalidArgument => Self::InvalidArgument,
+            JsError::EventAlreadyExists => Self::EventAlreadyExists,
             JsError::EventAlreadySigned => Self::EventAlreadySigned,
+            JsError::ConflictingSignIntent => Self::ConflictingSignIntent,
+            JsError::EventCancelled => Self::EventCancelled,
+            JsError::InvalidStateTransition => Self::InvalidStateTransition,
+            JsError::EventNotMatured => Self::EventNotMatured,
+            JsError::MaturityOverrideRequired => Self::MaturityOverrideRequired,
             JsError::NotFound => Self::NotFound,
             JsError::StorageFailure => Self::StorageFailure,
+            JsError::SignerFailure => Self::SignerFailure,
             JsError::InvalidOutcome => Self::InvalidOutcome,
             JsError::Internal => Self::Internal,
             JsError::Nostr => Self::Internal,
@@ -1,14 +1,22 @@
+use std::fmt::{Debug, Formatter};
 use std::str::FromStr;
 
 use gloo_utils::format::JsValueSerdeExt;
-use nostr::{EventId, JsonUtil, Keys};
+use nostr::{EventId, JsonUtil};
 use nostr_sdk::Client;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 
-use kormir::bitcoin::secp256k1::SecretKey;
-use kormir::storage::Storage;
-use kormir::{Oracle, OracleAnnouncement, OracleAttestation, Readable, Writeable};
+use kormir::backup::OracleBackup;
+use kormir::bitcoin::secp256k1::{Secp256k1, SecretKey, XOnlyPublicKey};
+use kormir::metadata::SignedOracleMetadata;
+use kormir::secret::{parse_nsec, Secret};
+use kormir::signer::{LocalSigner, Signer};
+use kormir::storage::{EventState, EventStatus, Storage};
+use kormir::{
+    EnumEventDescriptor, EventDescriptor, NonceDerivation, Oracle, OracleAnnouncement,
+    OracleAttestation, OracleEvent, Readable, Writeable,
+};
 
 use crate::error::JsError;
 use crate::models::{Announcement, Attestation, EventData};
@@ -19,7 +27,7 @@ mod models;
 mod storage;
 mod utils;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 #[wasm_bindgen]
 pub struct Kormir {
     oracle: Oracle<IndexedDb>,
@@ -28,22 +36,35 @@ pub struct Kormir {
     relays: Vec<String>,
 }
 
+impl Debug for Kormir {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Kormir")
+            .field("oracle", &self.oracle)
+            .field("relays", &self.relays)
+            .finish_non_exhaustive()
+    }
+}
+
 #[wasm_bindgen]
 impl Kormir {
     pub async fn new(relays: Vec<String>) -> Result<Kormir, JsError> {
         utils::set_panic_hook();
         let storage = IndexedDb::new().await?;
 
-        let nsec: Option<String> = storage.get_from_indexed_db(NSEC_KEY).await?;
+        let nsec: Option<Secret<String>> = storage
+            .get_from_indexed_db::<_, String>(NSEC_KEY)
+            .await?
+            .map(Secret::new);
         let nsec: SecretKey = match nsec {
-            Some(str) => SecretKey::from_str(&str)?,
+            Some(str) => SecretKey::from_str(str.expose_secret())?,
             None => {
-                let mut entropy: [u8; 32] = [0; 32];
-                getrandom::getrandom(&mut entropy).unwrap();
+                let mut entropy = Secret::new([0u8; 32]);
+                getrandom::getrandom(entropy.expose_secret_mut()).unwrap();
 
-                let nsec = SecretKey::from_slice(&entropy)?;
+                let nsec = SecretKey::from_slice(entropy.expose_secret())?;
+                let hex = Secret::new(hex::encode(nsec.secret_bytes()));
                 storage
-                    .save_to_indexed_db(NSEC_KEY, hex::encode(nsec.secret_bytes()))
+                    .save_to_indexed_db(NSEC_KEY, hex.expose_secret())
                     .await?;
                 nsec
             }
@@ -64,15 +85,67 @@ impl Kormir {
     }
 
     pub async fn restore(str: String) -> Result<(), JsError> {
-        let nsec = Keys::parse(&str)?;
+        let nsec = parse_nsec(&Secret::new(str))?;
         IndexedDb::clear().await?;
         let storage = IndexedDb::new().await?;
 
+        let hex = Secret::new(hex::encode(nsec.secret_bytes()));
         storage
-            .save_to_indexed_db(
-                NSEC_KEY,
-                hex::encode(nsec.secret_key().expect("just imported").secret_bytes()),
-            )
+            .save_to_indexed_db(NSEC_KEY, hex.expose_secret())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Exports the oracle's key and events, encrypted with `passphrase`, as hex
+    pub async fn export_backup(&self, passphrase: String) -> Result<String, JsError> {
+        let passphrase = Secret::new(passphrase);
+        let backup = self
+            .oracle
+            .export_backup(passphrase.expose_secret())
+            .await?;
+        Ok(hex::encode(backup))
+    }
+
+    /// Replaces the stored oracle with an encrypted backup, like `restore`
+    pub async fn import_backup(backup: String, passphrase: String) -> Result<(), JsError> {
+        let passphrase = Secret::new(passphrase);
+        let backup = hex::decode(backup)?;
+        let backup = OracleBackup::decrypt(&backup, passphrase.expose_secret())?;
+
+        // only the signing key is persisted, the nonces must derive from it
+        // and there is nowhere to keep retired keys
+        if !backup.retired_signers().is_empty() {
+            return Err(JsError::InvalidArgument);
+        }
+        let signing_key = backup.signing_key();
+        let event = OracleEvent {
+            oracle_nonces: vec![],
+            event_id: String::new(),
+            event_maturity_epoch: 0,
+            event_descriptor: EventDescriptor::EnumEvent(EnumEventDescriptor { outcomes: vec![] }),
+        };
+        let nonce = |signer: LocalSigner| {
+            let event = &event;
+            async move {
+                signer
+                    .nonce_public_keys(event, &[0], NonceDerivation::Indexed)
+                    .await
+            }
+        };
+        if nonce(backup.signer()).await?
+            != nonce(LocalSigner::from_signing_key(signing_key)?).await?
+        {
+            return Err(JsError::InvalidArgument);
+        }
+
+        IndexedDb::clear().await?;
+        let storage = IndexedDb::new().await?;
+        backup.restore(&storage).await?;
+
+        let hex = Secret::new(hex::encode(signing_key.secret_bytes()));
+        storage
+            .save_to_indexed_db(NSEC_KEY, hex.expose_secret())
             .await?;
 
         Ok(())
@@ -98,15 +171,16 @@ impl Kormir {
         log::info!("Created enum event: {hex}");
 
         let event = kormir::nostr_events::create_announcement_event(
-            &self.oracle.nostr_keys(),
+            self.oracle.nostr_public_key(),
             &ann,
             &self.relays,
-        )?;
+        );
+        let event = self.oracle.sign_nostr_event(event).await?;
 
         log::debug!("Created nostr event: {}", event.as_json());
 
         self.storage
-            .add_announcement_event_id(event_id, event.id.to_hex())
+            .add_announcement_event_id(event_id.clone(), event.id.to_hex())
             .await?;
 
         log::debug!(
@@ -118,6 +192,8 @@ impl Kormir {
 
         log::trace!("Sent event to nostr");
 
+        self.oracle.mark_announced(event_id).await?;
+
         Ok(hex)
     }
 
@@ -139,10 +215,11 @@ impl Kormir {
         let nostr_event_id = EventId::from_hex(event.announcement_event_id.unwrap()).unwrap();
 
         let event = kormir::nostr_events::create_attestation_event(
-            &self.oracle.nostr_keys(),
+            self.oracle.nostr_public_key(),
             &attestation,
             nostr_event_id,
-        )?;
+        );
+        let event = self.oracle.sign_nostr_event(event).await?;
 
         self.storage
             .add_attestation_event_id(event_id, event.id.to_hex())
@@ -153,6 +230,8 @@ impl Kormir {
         Ok(hex::encode(attestation.encode()))
     }
 
+    /// Announces a numeric event, `base` of its digits defaults to 2
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_numeric_event(
         &self,
         event_id: String,
@@ -161,11 +240,13 @@ impl Kormir {
         precision: i32,
         unit: String,
         event_maturity_epoch: u32,
+        base: Option<u16>,
     ) -> Result<String, JsError> {
         let ann = self
             .oracle
             .create_numeric_event(
                 event_id.clone(),
+                base.unwrap_or(2),
                 num_digits,
                 is_signed,
                 precision,
@@ -179,15 +260,16 @@ impl Kormir {
         log::info!("Created numeric event: {hex}");
 
         let event = kormir::nostr_events::create_announcement_event(
-            &self.oracle.nostr_keys(),
+            self.oracle.nostr_public_key(),
             &ann,
             &self.relays,
-        )?;
+        );
+        let event = self.oracle.sign_nostr_event(event).await?;
 
         log::debug!("Created nostr event: {}", event.as_json());
 
         self.storage
-            .add_announcement_event_id(event_id, event.id.to_hex())
+            .add_announcement_event_id(event_id.clone(), event.id.to_hex())
             .await?;
 
         log::debug!(
@@ -199,6 +281,8 @@ impl Kormir {
 
         log::trace!("Sent event to nostr");
 
+        self.oracle.mark_announced(event_id).await?;
+
         Ok(hex)
     }
 
@@ -220,10 +304,11 @@ impl Kormir {
         let nostr_event_id = EventId::from_hex(event.announcement_event_id.unwrap()).unwrap();
 
         let event = kormir::nostr_events::create_attestation_event(
-            &self.oracle.nostr_keys(),
+            self.oracle.nostr_public_key(),
             &attestation,
             nostr_event_id,
-        )?;
+        );
+        let event = self.oracle.sign_nostr_event(event).await?;
 
         self.storage
             .add_attestation_event_id(event_id, event.id.to_hex())
@@ -234,9 +319,75 @@ impl Kormir {
         Ok(hex::encode(attestation.encode()))
     }
 
+    /// Cancels the event and publishes a notice about it, returns the
+    /// attestation of the reserved "cancelled" outcome if the event has one.
+    pub async fn cancel_event(&self, event_id: String) -> Result<Option<String>, JsError> {
+        // the notice refers to the announcement, it must have been published
+        let event = self
+            .storage
+            .get_event(event_id.clone())
+            .await?
+            .ok_or(JsError::NotFound)?;
+        let nostr_event_id = event
+            .announcement_event_id
+            .ok_or(JsError::NotFound)
+            .and_then(|id| EventId::from_hex(id).map_err(|_| JsError::StorageFailure))?;
+
+        let attestation = self.oracle.cancel_event(event_id.clone()).await?;
+
+        if let Some(attestation) = &attestation {
+            if event.attestation_event_id.is_none() {
+                let event = kormir::nostr_events::create_attestation_event(
+                    self.oracle.nostr_public_key(),
+                    attestation,
+                    nostr_event_id,
+                );
+                let event = self.oracle.sign_nostr_event(event).await?;
+
+                self.storage
+                    .add_attestation_event_id(event_id.clone(), event.id.to_hex())
+                    .await?;
+
+                self.client.send_event(event).await?;
+            }
+        }
+
+        let notice = kormir::nostr_events::create_cancellation_event(
+            self.oracle.nostr_public_key(),
+            &event_id,
+            nostr_event_id,
+        );
+        let notice = self.oracle.sign_nostr_event(notice).await?;
+        self.client.send_event(notice).await?;
+
+        Ok(attestation.map(|a| hex::encode(a.encode())))
+    }
+
     pub async fn list_events(&self) -> Result<JsValue /* Vec<EventData> */, JsError> {
+        self.filter_events(None, None).await
+    }
+
+    /// Lists the events in the given lifecycle state (created, announced,
+    /// signed, cancelled) and/or status (pending, matured, overdue, signed,
+    /// cancelled).
+    // `is_none_or` is too new for the toolchain the wasm package is built with
+    #[allow(unknown_lints, clippy::unnecessary_map_or)]
+    pub async fn filter_events(
+        &self,
+        state: Option<String>,
+        status: Option<String>,
+    ) -> Result<JsValue /* Vec<EventData> */, JsError> {
+        let state = state.map(|s| s.parse::<EventState>()).transpose()?;
+        let status = status.map(|s| s.parse::<EventStatus>()).transpose()?;
+        let now = chrono::Utc::now().timestamp() as u32;
+
         let data = self.storage.list_events().await?;
-        let events = data.into_iter().map(EventData::from).collect::<Vec<_>>();
+        let events = data
+            .into_iter()
+            .filter(|event| state.map_or(true, |state| event.state == state))
+            .filter(|event| status.map_or(true, |status| event.status(now) == status))
+            .map(|event| EventData::try_from((event.event_id.clone(), event)))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(JsValue::from_serde(&events)?)
     }
@@ -254,4 +405,41 @@ impl Kormir {
         let attestation = OracleAttestation::read(&mut cursor)?;
         Ok(attestation.into())
     }
+
+    /// Verifies the attestation against its announcement, throws an `Error`
+    /// saying why it is invalid otherwise
+    pub async fn verify_attestation(
+        announcement_hex: String,
+        attestation_hex: String,
+    ) -> Result<(), wasm_bindgen::JsError> {
+        let decode = || -> Result<_, JsError> {
+            let bytes = hex::decode(announcement_hex)?;
+            let mut cursor = kormir::lightning::io::Cursor::new(&bytes);
+            let announcement = OracleAnnouncement::read(&mut cursor)?;
+
+            let bytes = hex::decode(attestation_hex)?;
+            let mut cursor = kormir::lightning::io::Cursor::new(&bytes);
+            let attestation = OracleAttestation::read(&mut cursor)?;
+            Ok((announcement, attestation))
+        };
+        let (announcement, attestation) = decode()?;
+
+        let secp = Secp256k1::verification_only();
+        kormir::verify::verify_attestation(&secp, &announcement, &attestation).map_err(|e| {
+            log::warn!("Attestation verification failed: {e}");
+            wasm_bindgen::JsError::new(&format!("Invalid attestation: {e}"))
+        })
+    }
+
+    /// Verifies an oracle's signed metadata document against its public key
+    pub async fn verify_metadata(json: String, public_key: String) -> Result<(), JsError> {
+        let metadata: SignedOracleMetadata =
+            serde_json::from_str(&json).map_err(|_| JsError::InvalidArgument)?;
+        let public_key =
+            XOnlyPublicKey::from_str(&public_key).map_err(|_| JsError::InvalidArgument)?;
+
+        let secp = Secp256k1::verification_only();
+        metadata.verify(&secp, &public_key)?;
+        Ok(())
+    }
 }
@@ -1,4 +1,6 @@
+use crate::error::JsError;
 use gloo_utils::format::JsValueSerdeExt;
+use kormir::bitcoin::secp256k1::Secp256k1;
 use kormir::storage::OracleEventData;
 use kormir::{EventDescriptor, OracleAnnouncement, OracleAttestation, Writeable};
 use serde::{Deserialize, Serialize};
@@ -14,6 +16,7 @@ pub struct Announcement {
     pub event_maturity_epoch: u32,
     outcomes: Vec<String>,
     event_id: String,
+    attestation_scheme: Option<String>,
 }
 
 #[wasm_bindgen]
@@ -47,10 +50,20 @@ impl Announcement {
     pub fn event_id(&self) -> String {
         self.event_id.clone()
     }
+
+    /// The scheme the announcement is signed with, none if its signature is invalid
+    #[wasm_bindgen(getter)]
+    pub fn attestation_scheme(&self) -> Option<String> {
+        self.attestation_scheme.clone()
+    }
 }
 
 impl From<OracleAnnouncement> for Announcement {
     fn from(value: OracleAnnouncement) -> Self {
+        let secp = Secp256k1::verification_only();
+        let attestation_scheme = kormir::verify::announcement_scheme(&secp, &value)
+            .ok()
+            .map(|scheme| scheme.to_string());
         let outcomes = match value.oracle_event.event_descriptor {
             EventDescriptor::EnumEvent(e) => e.outcomes,
             EventDescriptor::DigitDecompositionEvent(_) => {
@@ -70,6 +83,7 @@ impl From<OracleAnnouncement> for Announcement {
             event_maturity_epoch: value.oracle_event.event_maturity_epoch,
             outcomes,
             event_id: value.oracle_event.event_id,
+            attestation_scheme,
         }
     }
 }
@@ -131,6 +145,9 @@ pub struct EventData {
     announcement_event_id: Option<String>,
     attestation_event_id: Option<String>,
     observed_outcome: Option<String>,
+    state: String,
+    status: String,
+    attestation_scheme: String,
 }
 
 #[wasm_bindgen]
@@ -174,10 +191,30 @@ impl EventData {
     pub fn attestation_event_id(&self) -> Option<String> {
         self.attestation_event_id.clone()
     }
+
+    /// The lifecycle state: created, announced, signed or cancelled
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> String {
+        self.state.clone()
+    }
+
+    /// The state refined with the maturity: pending, matured, overdue, signed or cancelled
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        self.status.clone()
+    }
+
+    /// How the announcement and outcomes are signed: legacy or tagged
+    #[wasm_bindgen(getter)]
+    pub fn attestation_scheme(&self) -> String {
+        self.attestation_scheme.clone()
+    }
 }
 
-impl From<(String, OracleEventData)> for EventData {
-    fn from((id, value): (String, OracleEventData)) -> Self {
+impl TryFrom<(String, OracleEventData)> for EventData {
+    type Error = JsError;
+
+    fn try_from((id, value): (String, OracleEventData)) -> Result<Self, Self::Error> {
         let outcomes = match &value.announcement.oracle_event.event_descriptor {
             EventDescriptor::EnumEvent(e) => e.outcomes.clone(),
             EventDescriptor::DigitDecompositionEvent(_) => {
@@ -200,25 +237,30 @@ impl From<(String, OracleEventData)> for EventData {
                     EventDescriptor::EnumEvent(_) => {
                         value.signatures.iter().map(|x| x.0.clone()).next().unwrap()
                     }
-                    EventDescriptor::DigitDecompositionEvent(_) => {
-                        let mut outcome_str = value
-                            .signatures
-                            .iter()
-                            .map(|x| x.0.clone())
-                            .collect::<Vec<_>>()
-                            .join("");
-                        if outcome_str.starts_with('+') {
-                            outcome_str.remove(0);
+                    EventDescriptor::DigitDecompositionEvent(desc) => {
+                        let mut digits = value.signatures.iter().map(|x| x.0.as_str()).peekable();
+                        let sign = digits.next_if(|d| *d == "+" || *d == "-");
+                        // wider than the signed i64, so its minimum fits too
+                        let outcome = digits
+                            .try_fold(0i128, |acc, digit| {
+                                let digit = digit.parse::<i128>().ok()?;
+                                acc.checked_mul(desc.base as i128)?.checked_add(digit)
+                            })
+                            .ok_or(JsError::StorageFailure)?;
+                        if sign == Some("-") {
+                            (-outcome).to_string()
+                        } else {
+                            outcome.to_string()
                         }
-                        let outcome = i64::from_str_radix(&outcome_str, 2).unwrap();
-                        outcome.to_string()
                     }
                 };
                 (Some(attestation), Some(outcome))
             }
         };
 
-        EventData {
+        let status = value.status(chrono::Utc::now().timestamp() as u32);
+
+        Ok(EventData {
             event_id: id,
             announcement: hex::encode(value.announcement.encode()),
             attestation,
@@ -228,6 +270,9 @@ impl From<(String, OracleEventData)> for EventData {
             announcement_event_id: value.announcement_event_id,
             attestation_event_id: value.attestation_event_id,
             observed_outcome,
-        }
+            state: value.state.to_string(),
+            status: status.to_string(),
+            attestation_scheme: value.attestation_scheme.to_string(),
+        })
     }
 }
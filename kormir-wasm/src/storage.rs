@@ -1,8 +1,8 @@
 use crate::error::JsError;
 use gloo_utils::format::JsValueSerdeExt;
 use kormir::error::Error;
-use kormir::storage::{OracleEventData, Storage};
-use kormir::{OracleAnnouncement, Signature};
+use kormir::storage::{EventState, OracleEventData, Storage};
+use kormir::{AttestationScheme, OracleAnnouncement, Signature};
 use rexie::{ObjectStore, Rexie, TransactionMode};
 use serde::Serialize;
 use std::sync::atomic::{AtomicU32, Ordering};
@@ -14,11 +14,29 @@ const OBJECT_STORE_NAME: &str = "oracle";
 pub const NSEC_KEY: &str = "nsec";
 const NONCE_INDEX_KEY: &str = "nonce_index";
 const ORACLE_DATA_PREFIX: &str = "oracle_data/";
+const SIGN_INTENT_PREFIX: &str = "sign_intent/";
 
 fn get_oracle_data_key(event_id: String) -> String {
     format!("{ORACLE_DATA_PREFIX}{event_id}")
 }
 
+fn get_sign_intent_key(event_id: String) -> String {
+    format!("{SIGN_INTENT_PREFIX}{event_id}")
+}
+
+/// Events saved before the lifecycle state was stored default to
+/// [`EventState::Created`], infer their actual state from the other fields.
+fn with_legacy_state(mut event: OracleEventData) -> OracleEventData {
+    if event.state == EventState::Created {
+        if !event.signatures.is_empty() {
+            event.state = EventState::Signed;
+        } else if event.announcement_event_id.is_some() {
+            event.state = EventState::Announced;
+        }
+    }
+    event
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexedDb {
     current_index: Arc<AtomicU32>,
@@ -122,7 +140,61 @@ impl IndexedDb {
         Ok(())
     }
 
-    pub async fn list_events(&self) -> Result<Vec<(String, OracleEventData)>, JsError> {
+    /// Saves the sign intent for the event unless one already exists, in a
+    /// single transaction, and returns the committed outcomes.
+    async fn insert_or_get_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, JsError> {
+        let tx = self
+            .rexie
+            .transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadWrite)?;
+        let store = tx.store(OBJECT_STORE_NAME)?;
+
+        let event_key = JsValue::from_serde(&get_oracle_data_key(event_id.clone()))?;
+        let event: Option<OracleEventData> = store.get(&event_key).await?.into_serde()?;
+        if event.is_none() {
+            return Err(JsError::NotFound);
+        }
+
+        let key = JsValue::from_serde(&get_sign_intent_key(event_id))?;
+        let existing: Option<Vec<String>> = store.get(&key).await?.into_serde()?;
+        let committed = match existing {
+            Some(existing) => existing,
+            None => {
+                store
+                    .put(&JsValue::from_serde(&outcomes)?, Some(&key))
+                    .await?;
+                outcomes
+            }
+        };
+        tx.done().await?;
+
+        Ok(committed)
+    }
+
+    async fn set_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, JsError> {
+        let tx = self
+            .rexie
+            .transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadWrite)?;
+        let store = tx.store(OBJECT_STORE_NAME)?;
+        let key = JsValue::from_serde(&get_oracle_data_key(event_id))?;
+        let event: Option<OracleEventData> = store.get(&key).await?.into_serde()?;
+        let Some(mut event) = event else {
+            return Err(JsError::NotFound);
+        };
+        event.state = state;
+        store.put(&JsValue::from_serde(&event)?, Some(&key)).await?;
+        tx.done().await?;
+        Ok(event)
+    }
+
+    async fn list_oracle_data(&self) -> Result<Vec<OracleEventData>, JsError> {
         let tx = self
             .rexie
             .transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadOnly)?;
@@ -135,17 +207,63 @@ impl IndexedDb {
             let key: String = key.into_serde()?;
             if key.starts_with(ORACLE_DATA_PREFIX) {
                 let data: OracleEventData = value.into_serde()?;
-                let id = key
-                    .strip_prefix(ORACLE_DATA_PREFIX)
-                    .expect("just checked")
-                    .to_string();
-                vec.push((id, data))
+                vec.push(with_legacy_state(data))
+            }
+        }
+
+        Ok(vec)
+    }
+
+    async fn list_sign_intent_data(&self) -> Result<Vec<(String, Vec<String>)>, JsError> {
+        let tx = self
+            .rexie
+            .transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadOnly)?;
+        let store = tx.store(OBJECT_STORE_NAME)?;
+        let all = store.get_all(None, None, None, None).await?;
+        tx.done().await?;
+
+        let mut vec = Vec::new();
+        for (key, value) in all {
+            let key: String = key.into_serde()?;
+            if let Some(event_id) = key.strip_prefix(SIGN_INTENT_PREFIX) {
+                vec.push((event_id.to_string(), value.into_serde()?));
             }
         }
 
         Ok(vec)
     }
 
+    /// Saves an event from a backup along with the sign intent of its
+    /// signatures, in a single transaction.
+    async fn insert_restored_event(&self, event: OracleEventData) -> Result<(), JsError> {
+        let tx = self
+            .rexie
+            .transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadWrite)?;
+        let store = tx.store(OBJECT_STORE_NAME)?;
+
+        let key = JsValue::from_serde(&get_oracle_data_key(event.event_id.clone()))?;
+        let existing: Option<OracleEventData> = store.get(&key).await?.into_serde()?;
+        if existing.is_some() {
+            return Err(JsError::EventAlreadyExists);
+        }
+
+        if !event.signatures.is_empty() {
+            let outcomes = event
+                .signatures
+                .iter()
+                .map(|(o, _)| o.clone())
+                .collect::<Vec<_>>();
+            let intent_key = JsValue::from_serde(&get_sign_intent_key(event.event_id.clone()))?;
+            store
+                .put(&JsValue::from_serde(&outcomes)?, Some(&intent_key))
+                .await?;
+        }
+        store.put(&JsValue::from_serde(&event)?, Some(&key)).await?;
+        tx.done().await?;
+
+        Ok(())
+    }
+
     pub async fn clear() -> Result<(), JsError> {
         let rexie = Self::build_indexed_db().await?;
         let tx = rexie.transaction(&[OBJECT_STORE_NAME], TransactionMode::ReadWrite)?;
@@ -159,6 +277,7 @@ impl IndexedDb {
 }
 
 impl Storage for IndexedDb {
+    #[allow(clippy::explicit_counter_loop)]
     async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
         let mut current_index = self.current_index.fetch_add(num as u32, Ordering::SeqCst);
         let mut indexes = Vec::with_capacity(num);
@@ -175,12 +294,23 @@ impl Storage for IndexedDb {
         &self,
         announcement: OracleAnnouncement,
         indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
     ) -> Result<String, Error> {
+        if self
+            .get_event(announcement.oracle_event.event_id.clone())
+            .await?
+            .is_some()
+        {
+            return Err(Error::EventAlreadyExists);
+        }
+
         let event = OracleEventData {
             event_id: announcement.oracle_event.event_id.clone(),
             announcement: announcement.clone(),
             indexes,
             signatures: Default::default(),
+            state: EventState::Created,
+            attestation_scheme,
             announcement_event_id: None,
             attestation_event_id: None,
         };
@@ -191,6 +321,14 @@ impl Storage for IndexedDb {
         Ok(announcement.oracle_event.event_id.clone())
     }
 
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        Ok(self.insert_or_get_sign_intent(event_id, outcomes).await?)
+    }
+
     async fn save_signatures(
         &self,
         event_id: String,
@@ -205,16 +343,83 @@ impl Storage for IndexedDb {
         }
 
         event.signatures = sigs;
+        if event.state != EventState::Cancelled {
+            event.state = EventState::Signed;
+        }
         self.save_to_indexed_db(get_oracle_data_key(event_id), &event)
             .await?;
 
         Ok(event)
     }
 
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error> {
+        Ok(self.set_state(event_id, state).await?)
+    }
+
     async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
         let event: Option<OracleEventData> = self
             .get_from_indexed_db(get_oracle_data_key(event_id))
             .await?;
-        Ok(event)
+        Ok(event.map(with_legacy_state))
+    }
+
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+        Ok(self.list_oracle_data().await?)
+    }
+
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        Ok(self.list_sign_intent_data().await?)
+    }
+
+    async fn get_nonce_counter(&self) -> Result<u32, Error> {
+        Ok(self.current_index.load(Ordering::SeqCst))
+    }
+
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+        let previous = self.current_index.fetch_max(next, Ordering::SeqCst);
+        if previous > next {
+            return Err(Error::InvalidArgument);
+        }
+        self.save_to_indexed_db(NONCE_INDEX_KEY, next).await?;
+        Ok(())
+    }
+
+    async fn restore_event(&self, data: OracleEventData) -> Result<(), Error> {
+        Ok(self.insert_restored_event(data).await?)
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use super::*;
+    use kormir::test_utils::{run_storage_conformance_tests, StorageFactory};
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// The browser has a single kormir database, every storage clears it first
+    struct IndexedDbFactory;
+
+    impl StorageFactory for IndexedDbFactory {
+        type Storage = IndexedDb;
+
+        async fn create(&self) -> IndexedDb {
+            IndexedDb::clear().await.unwrap();
+            IndexedDb::new().await.unwrap()
+        }
+
+        async fn reopen(&self, storage: IndexedDb) -> IndexedDb {
+            storage.rexie.close();
+            IndexedDb::new().await.unwrap()
+        }
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_indexed_db_conformance() {
+        run_storage_conformance_tests(&IndexedDbFactory).await;
     }
 }
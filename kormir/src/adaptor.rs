@@ -0,0 +1,191 @@
+use crate::error::Error;
+use crate::signer::outcome_message;
+use crate::verify::announcement_scheme;
+use crate::AttestationScheme;
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey, Signing, Verification};
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
+
+/// Computes the anticipation point `R + H(R||P||m)·P` for an outcome, this is
+/// the point the oracle's attestation scalar will be the discrete log of if it
+/// attests to `outcome` with `nonce` under the announcement's `scheme`.
+pub fn compute_outcome_point<C: Verification>(
+    secp: &Secp256k1<C>,
+    oracle_public_key: &XOnlyPublicKey,
+    nonce: &XOnlyPublicKey,
+    outcome: &str,
+    scheme: AttestationScheme,
+) -> Result<PublicKey, Error> {
+    let msg = outcome_message(outcome, scheme);
+    dlc::secp_utils::schnorrsig_compute_sig_point(secp, oracle_public_key, nonce, &msg)
+        .map_err(|_| Error::Internal)
+}
+
+/// Computes the anticipation point for every outcome of an enum event, the
+/// announcement's signature tells which scheme the outcomes are signed with.
+pub fn enum_outcome_points<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+) -> Result<Vec<(String, PublicKey)>, Error> {
+    let oracle_event = &announcement.oracle_event;
+    let EventDescriptor::EnumEvent(desc) = &oracle_event.event_descriptor else {
+        return Err(Error::InvalidArgument);
+    };
+    let scheme = announcement_scheme(secp, announcement).map_err(|_| Error::InvalidArgument)?;
+    let nonce = oracle_event
+        .oracle_nonces
+        .first()
+        .ok_or(Error::InvalidArgument)?;
+
+    desc.outcomes
+        .iter()
+        .map(|outcome| {
+            let point = compute_outcome_point(
+                secp,
+                &announcement.oracle_public_key,
+                nonce,
+                outcome,
+                scheme,
+            )?;
+            Ok((outcome.clone(), point))
+        })
+        .collect()
+}
+
+/// Computes the anticipation points of a digit decomposition event, one list
+/// per nonce. For signed events the first list is for the `+` and `-` outcomes,
+/// every other list has a point for each digit value of the event's base.
+pub fn digit_outcome_points<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+) -> Result<Vec<Vec<(String, PublicKey)>>, Error> {
+    let oracle_event = &announcement.oracle_event;
+    let EventDescriptor::DigitDecompositionEvent(desc) = &oracle_event.event_descriptor else {
+        return Err(Error::InvalidArgument);
+    };
+    oracle_event
+        .validate()
+        .map_err(|_| Error::InvalidArgument)?;
+    let scheme = announcement_scheme(secp, announcement).map_err(|_| Error::InvalidArgument)?;
+
+    let digits = (0..desc.base).map(|d| d.to_string()).collect::<Vec<_>>();
+    let signs = vec!["+".to_string(), "-".to_string()];
+
+    oracle_event
+        .oracle_nonces
+        .iter()
+        .enumerate()
+        .map(|(index, nonce)| {
+            let outcomes = if desc.is_signed && index == 0 {
+                &signs
+            } else {
+                &digits
+            };
+            outcomes
+                .iter()
+                .map(|outcome| {
+                    let point = compute_outcome_point(
+                        secp,
+                        &announcement.oracle_public_key,
+                        nonce,
+                        outcome,
+                        scheme,
+                    )?;
+                    Ok((outcome.clone(), point))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the point `s·G` for the scalar of an attestation signature, which
+/// equals the anticipation point of the attested outcome.
+pub fn signature_point<C: Signing>(
+    secp: &Secp256k1<C>,
+    signature: &Signature,
+) -> Result<PublicKey, Error> {
+    let scalar = SecretKey::from_slice(&signature[32..]).map_err(|_| Error::InvalidArgument)?;
+    Ok(scalar.public_key(secp))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    fn create_oracle() -> Oracle<MemoryStorage> {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_enum_outcome_points() {
+        let secp = Secp256k1::new();
+
+        for scheme in [AttestationScheme::Legacy, AttestationScheme::Tagged] {
+            let oracle = create_oracle().with_attestation_scheme(scheme);
+            let outcomes = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+            let ann = oracle
+                .create_enum_event("test".to_string(), outcomes.clone(), 100)
+                .await
+                .unwrap();
+            let points = enum_outcome_points(&secp, &ann).unwrap();
+            assert_eq!(
+                points.iter().map(|(o, _)| o.clone()).collect::<Vec<_>>(),
+                outcomes
+            );
+
+            let attestation = oracle
+                .sign_enum_event("test".to_string(), "b".to_string())
+                .await
+                .unwrap();
+            let point = signature_point(&secp, &attestation.signatures[0]).unwrap();
+            assert_eq!(point, points[1].1);
+            assert_ne!(point, points[0].1);
+            assert_ne!(point, points[2].1);
+
+            assert!(digit_outcome_points(&secp, &ann).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_digit_outcome_points() {
+        let secp = Secp256k1::new();
+
+        for scheme in [AttestationScheme::Legacy, AttestationScheme::Tagged] {
+            let oracle = create_oracle().with_attestation_scheme(scheme);
+            let ann = oracle
+                .create_numeric_event("test".to_string(), 10, 3, true, 0, "m/s".into(), 100)
+                .await
+                .unwrap();
+            let points = digit_outcome_points(&secp, &ann).unwrap();
+            assert_eq!(points.len(), 4);
+            assert_eq!(points[0].len(), 2);
+            assert!(points[1..].iter().all(|p| p.len() == 10));
+
+            let attestation = oracle
+                .sign_numeric_event("test".to_string(), -472)
+                .await
+                .unwrap();
+            for ((sig, outcome), points) in attestation
+                .signatures
+                .iter()
+                .zip(attestation.outcomes.iter())
+                .zip(points.iter())
+            {
+                let point = signature_point(&secp, sig).unwrap();
+                let expected = points.iter().find(|(o, _)| o == outcome).unwrap().1;
+                assert_eq!(point, expected);
+            }
+
+            assert!(enum_outcome_points(&secp, &ann).is_err());
+        }
+    }
+}
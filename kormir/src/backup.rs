@@ -0,0 +1,556 @@
+//! Encrypted backups of an oracle's keys and storage.
+//!
+//! An [`OracleBackup`] holds the signing key, the nonce master, the keys of
+//! retired signers, the nonce counter, every event with its signatures, state
+//! and nostr event ids, and the sign intents of events that were not signed
+//! yet. It can be taken from
+//! and restored into any [`Storage`].
+//!
+//! Encrypted backups start with a header of the magic bytes, the format
+//! version, the scrypt cost, a random salt and a random nonce. The rest is the
+//! JSON encoded backup, encrypted with XChaCha20-Poly1305 under the scrypt key
+//! of the passphrase, with the header as associated data.
+
+use crate::error::Error;
+use crate::secret::Secret;
+use crate::signer::{LocalSigner, Signer};
+use crate::storage::{OracleEventData, Storage};
+use bitcoin::bip32::{ChainCode, Xpriv};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::rand::{thread_rng, Rng};
+use bitcoin::secp256k1::SecretKey;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use zeroize::Zeroize;
+
+const MAGIC: &[u8; 8] = b"KORMIRBK";
+/// The version of the encrypted backup format
+pub const BACKUP_VERSION: u8 = 1;
+/// scrypt cost of new backups, as log2 of N
+const LOG_N: u8 = 15;
+/// Highest scrypt cost accepted on import, so a backup can not make us use
+/// more than 64 MiB of memory
+const MAX_LOG_N: u8 = LOG_N + 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN + NONCE_LEN;
+
+/// Everything needed to restore an oracle
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OracleBackup {
+    signing_key: SecretKey,
+    nonce_xpriv: Xpriv,
+    /// The nonce index the storage would have handed out next
+    pub next_nonce_index: u32,
+    pub events: Vec<OracleEventData>,
+    /// Committed outcomes by event id, so a restored oracle can not sign an
+    /// event it was interrupted signing with other outcomes
+    #[serde(default)]
+    pub sign_intents: BTreeMap<String, Vec<String>>,
+    /// Keys the oracle handed over from, that still sign their events
+    #[serde(default)]
+    retired_keys: Vec<RetiredKeys>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RetiredKeys {
+    signing_key: SecretKey,
+    nonce_xpriv: Xpriv,
+}
+
+impl Drop for RetiredKeys {
+    fn drop(&mut self) {
+        self.signing_key.non_secure_erase();
+        self.nonce_xpriv.private_key.non_secure_erase();
+        self.nonce_xpriv.chain_code = ChainCode::from([0; 32]);
+    }
+}
+
+impl Debug for OracleBackup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OracleBackup")
+            .field("public_key", &format_args!("{}", self.public_key()))
+            .field("next_nonce_index", &self.next_nonce_index)
+            .field("events", &self.events.len())
+            .field("retired_keys", &self.retired_keys.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for OracleBackup {
+    fn drop(&mut self) {
+        self.signing_key.non_secure_erase();
+        self.nonce_xpriv.private_key.non_secure_erase();
+        self.nonce_xpriv.chain_code = ChainCode::from([0; 32]);
+    }
+}
+
+impl OracleBackup {
+    /// Takes a backup of the signer's keys, the keys of the retired signers
+    /// and everything in the storage
+    pub async fn create<S: Storage>(
+        storage: &S,
+        signer: &LocalSigner,
+        retired_signers: &[LocalSigner],
+    ) -> Result<Self, Error> {
+        // read the counter first, events created meanwhile use higher indexes
+        let next_nonce_index = storage.get_nonce_counter().await?;
+        let events = storage.list_events().await?;
+        // intents of events created after they were listed are not needed
+        let sign_intents = storage
+            .list_sign_intents()
+            .await?
+            .into_iter()
+            .filter(|(event_id, _)| events.iter().any(|e| &e.event_id == event_id))
+            .collect();
+        Ok(Self {
+            signing_key: signer.signing_key(),
+            nonce_xpriv: signer.nonce_xpriv(),
+            next_nonce_index,
+            events,
+            sign_intents,
+            retired_keys: retired_signers
+                .iter()
+                .map(|s| RetiredKeys {
+                    signing_key: s.signing_key(),
+                    nonce_xpriv: s.nonce_xpriv(),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.signer().public_key()
+    }
+
+    /// The backed up signing key, for oracles that only persist it
+    pub fn signing_key(&self) -> SecretKey {
+        self.signing_key
+    }
+
+    /// The signer with the backed up keys
+    pub fn signer(&self) -> LocalSigner {
+        LocalSigner::new(self.signing_key, self.nonce_xpriv)
+    }
+
+    /// The signers of the backed up retired keys
+    pub fn retired_signers(&self) -> Vec<LocalSigner> {
+        self.retired_keys
+            .iter()
+            .map(|k| LocalSigner::new(k.signing_key, k.nonce_xpriv))
+            .collect()
+    }
+
+    /// Restores the nonce counter, the events and their sign intents into
+    /// `storage`. Events that are already in the storage with the same
+    /// announcement get the backup's signatures if they were not signed yet.
+    ///
+    /// Fails with [`Error::InvalidArgument`] if the storage's nonce counter
+    /// is already past the backup's, restoring would make it reuse nonces,
+    /// and with [`Error::ConflictingSignIntent`] if the storage committed to
+    /// other outcomes for an event.
+    pub async fn restore<S: Storage>(&self, storage: &S) -> Result<(), Error> {
+        // check everything first, a rejected backup leaves the storage as it was
+        let used = self.events.iter().flat_map(|e| e.indexes.iter());
+        if used.max().is_some_and(|max| *max >= self.next_nonce_index) {
+            return Err(Error::InvalidArgument);
+        }
+        if storage.get_nonce_counter().await? > self.next_nonce_index {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut new_events = vec![];
+        let mut signed_events = vec![];
+        for event in &self.events {
+            match storage.get_event(event.event_id.clone()).await? {
+                Some(existing) if existing.announcement == event.announcement => {
+                    if event.signatures.is_empty() || existing.signatures == event.signatures {
+                        continue;
+                    }
+                    if !existing.signatures.is_empty() {
+                        return Err(Error::EventAlreadyExists);
+                    }
+                    // the event was signed after the storage's copy was taken
+                    signed_events.push(event);
+                }
+                Some(_) => return Err(Error::EventAlreadyExists),
+                None => new_events.push(event),
+            }
+        }
+
+        let mut sign_intents = signed_events
+            .iter()
+            .map(|e| {
+                let outcomes = e.signatures.iter().map(|(o, _)| o.clone()).collect();
+                (e.event_id.clone(), outcomes)
+            })
+            .collect::<Vec<(String, Vec<String>)>>();
+        sign_intents.extend(self.sign_intents.clone());
+        let committed = storage
+            .list_sign_intents()
+            .await?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        for (event_id, outcomes) in &sign_intents {
+            if committed.get(event_id).is_some_and(|c| c != outcomes) {
+                return Err(Error::ConflictingSignIntent);
+            }
+        }
+
+        storage.set_nonce_counter(self.next_nonce_index).await?;
+        for event in new_events {
+            storage.restore_event(event.clone()).await?;
+        }
+        for (event_id, outcomes) in sign_intents {
+            let committed = storage.save_sign_intent(event_id, outcomes.clone()).await?;
+            if committed != outcomes {
+                return Err(Error::ConflictingSignIntent);
+            }
+        }
+        for event in signed_events {
+            storage
+                .save_signatures(event.event_id.clone(), event.signatures.clone())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts the backup with a key derived from `passphrase`
+    pub fn encrypt(&self, passphrase: &str) -> Result<Vec<u8>, Error> {
+        self.encrypt_with_cost(passphrase, LOG_N)
+    }
+
+    fn encrypt_with_cost(&self, passphrase: &str, log_n: u8) -> Result<Vec<u8>, Error> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        thread_rng().fill(&mut salt);
+        thread_rng().fill(&mut nonce);
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(BACKUP_VERSION);
+        header.push(log_n);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&nonce);
+
+        let cipher = backup_cipher(passphrase, &salt, log_n)?;
+        let mut json = serde_json::to_vec(self).map_err(|_| Error::Internal)?;
+        let payload = Payload {
+            msg: &json,
+            aad: &header,
+        };
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), payload);
+        json.zeroize();
+
+        header.extend(ciphertext.map_err(|_| Error::Internal)?);
+        Ok(header)
+    }
+
+    /// Decrypts a backup made with [`OracleBackup::encrypt`]. Fails with
+    /// [`Error::InvalidArgument`] for a wrong passphrase, a damaged backup or
+    /// an unsupported version.
+    pub fn decrypt(bytes: &[u8], passphrase: &str) -> Result<Self, Error> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+            return Err(Error::InvalidArgument);
+        }
+        let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+        let version = header[MAGIC.len()];
+        let log_n = header[MAGIC.len() + 1];
+        if version != BACKUP_VERSION || log_n > MAX_LOG_N {
+            return Err(Error::InvalidArgument);
+        }
+        let salt = &header[MAGIC.len() + 2..][..SALT_LEN];
+        let nonce = &header[MAGIC.len() + 2 + SALT_LEN..];
+
+        let cipher = backup_cipher(passphrase, salt, log_n)?;
+        let payload = Payload {
+            msg: ciphertext,
+            aad: header,
+        };
+        let json = Secret::new(
+            cipher
+                .decrypt(XNonce::from_slice(nonce), payload)
+                .map_err(|_| Error::InvalidArgument)?,
+        );
+
+        serde_json::from_slice(json.expose_secret()).map_err(|_| Error::InvalidArgument)
+    }
+}
+
+/// The cipher keyed with the scrypt hash of the passphrase
+fn backup_cipher(passphrase: &str, salt: &[u8], log_n: u8) -> Result<XChaCha20Poly1305, Error> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32).map_err(|_| Error::InvalidArgument)?;
+    let mut key = Secret::new([0u8; 32]);
+    scrypt::scrypt(
+        passphrase.as_bytes(),
+        salt,
+        &params,
+        key.expose_secret_mut(),
+    )
+    .map_err(|_| Error::Internal)?;
+    XChaCha20Poly1305::new_from_slice(key.expose_secret()).map_err(|_| Error::Internal)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::{EventState, MemoryStorage};
+    use crate::Oracle;
+    use bitcoin::Network;
+
+    /// Low scrypt cost to keep the tests fast
+    const TEST_LOG_N: u8 = 4;
+
+    async fn create_oracle() -> Oracle<MemoryStorage> {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let oracle = Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap();
+
+        oracle
+            .create_enum_event("signed".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        oracle
+            .sign_enum_event("signed".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        oracle
+            .create_numeric_event("pending".to_string(), 10, 3, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        oracle.mark_announced("pending".to_string()).await.unwrap();
+
+        oracle
+    }
+
+    #[tokio::test]
+    async fn test_backup_round_trip() {
+        let oracle = create_oracle().await;
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+        assert_eq!(backup.public_key(), oracle.public_key());
+        assert_eq!(backup.next_nonce_index, 5);
+
+        let bytes = backup.encrypt_with_cost("passphrase", TEST_LOG_N).unwrap();
+        let restored = OracleBackup::decrypt(&bytes, "passphrase").unwrap();
+        assert_eq!(restored.next_nonce_index, 5);
+
+        let storage = MemoryStorage::default();
+        restored.restore(&storage).await.unwrap();
+        let oracle = Oracle::from_signer(storage, restored.signer());
+
+        let signed = oracle
+            .storage
+            .get_event("signed".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(signed.state, EventState::Signed);
+        assert_eq!(signed.signatures.len(), 1);
+        let res = oracle
+            .sign_enum_event("signed".to_string(), "b".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::EventAlreadySigned)));
+
+        // the restored oracle continues where the old one stopped
+        assert_eq!(oracle.storage.get_nonce_counter().await.unwrap(), 5);
+        let attestation = oracle
+            .sign_numeric_event("pending".to_string(), 42)
+            .await
+            .unwrap();
+        let pending = oracle
+            .storage
+            .get_event("pending".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        crate::verify::verify_attestation(&oracle.secp, &pending.announcement, &attestation)
+            .unwrap();
+
+        // restoring again is a no-op
+        restored.restore(&oracle.storage).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backup_keeps_retired_signers() {
+        let old = create_oracle().await;
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let oracle =
+            Oracle::from_signer(old.storage.clone(), LocalSigner::from_xpriv(xpriv).unwrap())
+                .with_retired_signer(old.signer().clone());
+
+        let backup =
+            OracleBackup::create(&oracle.storage, oracle.signer(), oracle.retired_signers())
+                .await
+                .unwrap();
+        let bytes = backup.encrypt_with_cost("passphrase", TEST_LOG_N).unwrap();
+        let restored = OracleBackup::decrypt(&bytes, "passphrase").unwrap();
+        assert_eq!(restored.public_key(), oracle.public_key());
+        let retired = restored.retired_signers();
+        assert_eq!(retired.len(), 1);
+        assert_eq!(retired[0].public_key(), old.public_key());
+
+        // the events of the retired key can still be signed after a restore
+        let oracle = Oracle::import_backup(MemoryStorage::default(), &bytes, "passphrase")
+            .await
+            .unwrap();
+        assert_eq!(oracle.retired_signers().len(), 1);
+        oracle
+            .sign_numeric_event("pending".to_string(), 42)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backup_keeps_sign_intents() {
+        let oracle = create_oracle().await;
+        // interrupted after committing to an outcome
+        oracle
+            .storage
+            .save_sign_intent("pending".to_string(), vec!["+".into(); 4])
+            .await
+            .unwrap();
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+        assert_eq!(backup.sign_intents.len(), 2);
+
+        let bytes = backup.encrypt_with_cost("passphrase", TEST_LOG_N).unwrap();
+        let restored = OracleBackup::decrypt(&bytes, "passphrase").unwrap();
+        let storage = MemoryStorage::default();
+        restored.restore(&storage).await.unwrap();
+        let oracle = Oracle::from_signer(storage, restored.signer());
+        let res = oracle.sign_numeric_event("pending".to_string(), 42).await;
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+
+        // a storage that committed to other outcomes is not overwritten
+        let storage = MemoryStorage::default();
+        storage.set_nonce_counter(5).await.unwrap();
+        let pending = backup
+            .events
+            .iter()
+            .find(|e| e.event_id == "pending")
+            .unwrap();
+        storage.restore_event(pending.clone()).await.unwrap();
+        storage
+            .save_sign_intent("pending".to_string(), vec!["-".into(); 4])
+            .await
+            .unwrap();
+        let res = backup.restore(&storage).await;
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+        // nor is anything else written
+        let signed = storage.get_event("signed".to_string()).await.unwrap();
+        assert!(signed.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_backup_merges_signatures() {
+        let oracle = create_oracle().await;
+        let unsigned = MemoryStorage::default();
+        OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap()
+            .restore(&unsigned)
+            .await
+            .unwrap();
+
+        // the pending event was signed after the copy was restored
+        let attestation = oracle
+            .sign_numeric_event("pending".to_string(), 42)
+            .await
+            .unwrap();
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+        backup.restore(&unsigned).await.unwrap();
+        let pending = unsigned
+            .get_event("pending".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.state, EventState::Signed);
+        assert_eq!(pending.attestation(), Some(attestation));
+
+        // signatures that differ from the storage's are a conflict
+        let mut other = backup.clone();
+        let pending = other
+            .events
+            .iter_mut()
+            .find(|e| e.event_id == "pending")
+            .unwrap();
+        pending.signatures.swap(0, 1);
+        let res = other.restore(&unsigned).await;
+        assert!(matches!(res, Err(Error::EventAlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_backup_rejects_bad_input() {
+        let oracle = create_oracle().await;
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+        let bytes = backup.encrypt_with_cost("passphrase", TEST_LOG_N).unwrap();
+
+        let res = OracleBackup::decrypt(&bytes, "wrong");
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+
+        let mut tampered = bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(OracleBackup::decrypt(&tampered, "passphrase").is_err());
+
+        let mut future = bytes.clone();
+        future[MAGIC.len()] = BACKUP_VERSION + 1;
+        assert!(OracleBackup::decrypt(&future, "passphrase").is_err());
+
+        let mut costly = bytes.clone();
+        costly[MAGIC.len() + 1] = MAX_LOG_N + 1;
+        let res = OracleBackup::decrypt(&costly, "passphrase");
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+
+        assert!(OracleBackup::decrypt(&bytes[..HEADER_LEN], "passphrase").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_never_moves_nonce_counter_backwards() {
+        let oracle = create_oracle().await;
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+
+        let storage = MemoryStorage::default();
+        storage.get_next_nonce_indexes(10).await.unwrap();
+        let res = backup.restore(&storage).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+        assert_eq!(storage.get_nonce_counter().await.unwrap(), 10);
+        assert!(storage.list_events().await.unwrap().is_empty());
+        assert!(matches!(
+            storage.set_nonce_counter(9).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        // a backup whose events use indexes past its counter is damaged
+        let mut damaged = backup.clone();
+        damaged.next_nonce_index = 2;
+        let res = damaged.restore(&MemoryStorage::default()).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+    }
+
+    #[tokio::test]
+    async fn test_backup_debug_redacts_keys() {
+        let oracle = create_oracle().await;
+        let backup = OracleBackup::create(&oracle.storage, oracle.signer(), &[])
+            .await
+            .unwrap();
+        let debug = format!("{backup:?}");
+        assert!(!debug.contains(&hex::encode(backup.signing_key.secret_bytes())));
+        assert!(!debug.contains(&backup.nonce_xpriv.to_string()));
+    }
+}
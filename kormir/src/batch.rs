@@ -0,0 +1,310 @@
+//! Creating and signing many events at once.
+//!
+//! [`Oracle::create_events_batch`] checks every event before reserving their
+//! nonces in a single call to the storage, then saves all of the valid events
+//! with [`Storage::save_announcements`]. Each event of a batch gets its own
+//! result, an invalid event does not prevent the others from being created.
+
+use crate::error::Error;
+use crate::offline::ProposedOutcome;
+use crate::signer::Signer;
+use crate::storage::Storage;
+use crate::{verify, Oracle, OracleAnnouncement, OracleAttestation, OracleEvent};
+use dlc_messages::oracle_msgs::{
+    DigitDecompositionEventDescriptor, EnumEventDescriptor, EventDescriptor,
+};
+use std::collections::HashSet;
+
+/// An event to create in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewEvent {
+    Enum {
+        event_id: String,
+        outcomes: Vec<String>,
+        event_maturity_epoch: u32,
+    },
+    Numeric {
+        event_id: String,
+        base: u16,
+        num_digits: u16,
+        is_signed: bool,
+        precision: i32,
+        unit: String,
+        event_maturity_epoch: u32,
+    },
+}
+
+impl NewEvent {
+    pub fn event_id(&self) -> &str {
+        match self {
+            NewEvent::Enum { event_id, .. } | NewEvent::Numeric { event_id, .. } => event_id,
+        }
+    }
+
+    /// The event to announce, its nonces are not derived yet
+    fn into_oracle_event(self) -> OracleEvent {
+        match self {
+            NewEvent::Enum {
+                event_id,
+                outcomes,
+                event_maturity_epoch,
+            } => OracleEvent {
+                oracle_nonces: vec![],
+                event_id,
+                event_maturity_epoch,
+                event_descriptor: EventDescriptor::EnumEvent(EnumEventDescriptor { outcomes }),
+            },
+            NewEvent::Numeric {
+                event_id,
+                base,
+                num_digits,
+                is_signed,
+                precision,
+                unit,
+                event_maturity_epoch,
+            } => OracleEvent {
+                oracle_nonces: vec![],
+                event_id,
+                event_maturity_epoch,
+                event_descriptor: EventDescriptor::DigitDecompositionEvent(
+                    DigitDecompositionEventDescriptor {
+                        base,
+                        is_signed,
+                        unit,
+                        precision,
+                        nb_digits: num_digits,
+                    },
+                ),
+            },
+        }
+    }
+}
+
+impl<S: Storage, K: Signer> Oracle<S, K> {
+    /// Creates the events and returns the announcement of each one, in order.
+    /// The events that can be created are saved together, atomically when the
+    /// storage supports it. Fails as a whole only if the storage does.
+    pub async fn create_events_batch(
+        &self,
+        events: Vec<NewEvent>,
+    ) -> Result<Vec<Result<OracleAnnouncement, Error>>, Error> {
+        let events = events
+            .into_iter()
+            .map(NewEvent::into_oracle_event)
+            .collect::<Vec<_>>();
+        // the number of nonces of each event that can be created
+        let mut results = Vec::with_capacity(events.len());
+        let mut seen = HashSet::new();
+        for event in &events {
+            let result = match verify::event_nonce_count(&event.event_descriptor) {
+                None => Err(Error::InvalidArgument),
+                Some(_) if !seen.insert(event.event_id.clone()) => Err(Error::EventAlreadyExists),
+                Some(num) => match self.storage.get_event(event.event_id.clone()).await? {
+                    Some(_) => Err(Error::EventAlreadyExists),
+                    None => Ok(num),
+                },
+            };
+            results.push(result);
+        }
+
+        let num_nonces = results.iter().flatten().sum();
+        let mut indexes = self
+            .storage
+            .get_next_nonce_indexes(num_nonces)
+            .await?
+            .into_iter();
+
+        let mut announcements = Vec::with_capacity(events.len());
+        for (event, result) in events.into_iter().zip(results) {
+            let num = match result {
+                Ok(num) => num,
+                Err(e) => {
+                    announcements.push(Err(e));
+                    continue;
+                }
+            };
+            let event_indexes = indexes.by_ref().take(num).collect::<Vec<_>>();
+            let ann = match event_indexes.len() == num {
+                true => self.announce_new_event(event, &event_indexes).await,
+                false => Err(Error::Internal),
+            };
+            announcements.push(ann.map(|ann| (ann, event_indexes)));
+        }
+
+        let to_save = announcements
+            .iter()
+            .filter_map(|ann| ann.as_ref().ok().cloned())
+            .collect::<Vec<_>>();
+        if !to_save.is_empty() {
+            self.storage
+                .save_announcements(to_save, self.attestation_scheme)
+                .await?;
+        }
+
+        Ok(announcements
+            .into_iter()
+            .map(|ann| ann.map(|(ann, _)| ann))
+            .collect())
+    }
+
+    /// Derives the nonces of a new event and signs its announcement
+    async fn announce_new_event(
+        &self,
+        mut oracle_event: OracleEvent,
+        indexes: &[u32],
+    ) -> Result<OracleAnnouncement, Error> {
+        oracle_event.oracle_nonces = self
+            .signer
+            .nonce_public_keys(&oracle_event, indexes, self.nonce_derivation)
+            .await?;
+        if oracle_event.oracle_nonces.len() != indexes.len() {
+            return Err(Error::SignerFailure);
+        }
+
+        self.sign_announcement(oracle_event).await
+    }
+
+    /// Signs an outcome for each of the events and returns the attestations,
+    /// in order. Each event is signed on its own, a failure only affects its
+    /// own result.
+    pub async fn sign_events_batch(
+        &self,
+        outcomes: Vec<(String, ProposedOutcome)>,
+        override_maturity: bool,
+    ) -> Vec<Result<OracleAttestation, Error>> {
+        let mut attestations = Vec::with_capacity(outcomes.len());
+        for (event_id, outcome) in outcomes {
+            let attestation = match outcome {
+                ProposedOutcome::Enum(outcome) => {
+                    self.sign_enum_event_inner(event_id, outcome, override_maturity)
+                        .await
+                }
+                ProposedOutcome::Numeric(outcome) => {
+                    self.sign_numeric_event_inner(event_id, outcome, override_maturity)
+                        .await
+                }
+            };
+            attestations.push(attestation);
+        }
+        attestations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::verify::verify_attestation;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::Network;
+
+    fn create_oracle() -> Oracle<MemoryStorage> {
+        let xpriv = Xpriv::new_master(Network::Regtest, &[0; 32]).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    fn enum_event(event_id: &str) -> NewEvent {
+        NewEvent::Enum {
+            event_id: event_id.to_string(),
+            outcomes: vec!["a".to_string(), "b".to_string()],
+            event_maturity_epoch: 100,
+        }
+    }
+
+    fn numeric_event(event_id: &str, num_digits: u16) -> NewEvent {
+        NewEvent::Numeric {
+            event_id: event_id.to_string(),
+            base: 2,
+            num_digits,
+            is_signed: true,
+            precision: 0,
+            unit: "m".to_string(),
+            event_maturity_epoch: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_events_batch() {
+        let oracle = create_oracle();
+        oracle
+            .create_enum_event("existing".to_string(), vec!["a".to_string()], 100)
+            .await
+            .unwrap();
+
+        let results = oracle
+            .create_events_batch(vec![
+                enum_event("enum"),
+                numeric_event("numeric", 8),
+                enum_event("existing"),
+                numeric_event("invalid", 0),
+                enum_event("enum"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(matches!(results[2], Err(Error::EventAlreadyExists)));
+        assert!(matches!(results[3], Err(Error::InvalidArgument)));
+        assert!(matches!(results[4], Err(Error::EventAlreadyExists)));
+
+        let secp = Secp256k1::new();
+        let enum_ann = results[0].as_ref().unwrap();
+        let numeric_ann = results[1].as_ref().unwrap();
+        assert!(enum_ann.validate(&secp).is_ok());
+        assert!(numeric_ann.validate(&secp).is_ok());
+        assert_eq!(numeric_ann.oracle_event.oracle_nonces.len(), 9);
+
+        // the nonces were reserved once, one after the other
+        let enum_data = oracle.storage.get_event("enum".into()).await.unwrap();
+        let numeric_data = oracle.storage.get_event("numeric".into()).await.unwrap();
+        assert_eq!(enum_data.unwrap().indexes, vec![1]);
+        assert_eq!(numeric_data.unwrap().indexes, (2..11).collect::<Vec<_>>());
+        assert_eq!(oracle.storage.list_events().await.unwrap().len(), 3);
+
+        // reserving nothing for a batch without any valid event
+        let results = oracle
+            .create_events_batch(vec![enum_event("enum")])
+            .await
+            .unwrap();
+        assert!(matches!(results[0], Err(Error::EventAlreadyExists)));
+        assert_eq!(oracle.storage.get_nonce_counter().await.unwrap(), 11);
+    }
+
+    #[tokio::test]
+    async fn test_sign_events_batch() {
+        let oracle = create_oracle();
+        oracle
+            .create_events_batch(vec![enum_event("enum"), numeric_event("numeric", 8)])
+            .await
+            .unwrap();
+
+        let results = oracle
+            .sign_events_batch(
+                vec![
+                    ("enum".to_string(), ProposedOutcome::Enum("b".to_string())),
+                    ("numeric".to_string(), ProposedOutcome::Numeric(-42)),
+                    ("missing".to_string(), ProposedOutcome::Numeric(1)),
+                    ("enum".to_string(), ProposedOutcome::Enum("a".to_string())),
+                ],
+                false,
+            )
+            .await;
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[2], Err(Error::NotFound)));
+        assert!(matches!(results[3], Err(Error::EventAlreadySigned)));
+
+        let secp = Secp256k1::new();
+        for (event_id, result) in ["enum", "numeric"].into_iter().zip(&results) {
+            let data = oracle
+                .storage
+                .get_event(event_id.to_string())
+                .await
+                .unwrap()
+                .unwrap();
+            let attestation = result.as_ref().unwrap();
+            assert!(verify_attestation(&secp, &data.announcement, attestation).is_ok());
+            assert_eq!(data.signatures.len(), attestation.signatures.len());
+        }
+        assert_eq!(results[0].as_ref().unwrap().outcomes, vec!["b"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Deriving an oracle's keys from an HD seed.
+//!
+//! [`OracleBuilder`] lets several oracles share one seed by giving each its
+//! own account or derivation paths. Without any options it derives the same
+//! keys as [`Oracle::from_xpriv`].
+
+use crate::error::Error;
+use crate::signer::{nonce_master, LocalSigner};
+use crate::storage::Storage;
+use crate::Oracle;
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::Network;
+
+/// Builds an [`Oracle`] whose keys are derived from an xpriv.
+///
+/// The signing key is derived at `m/86'/<coin>'/<account>'/0/0`, where the
+/// coin type is `0` for [`Network::Bitcoin`] and `1` otherwise, unless a
+/// signing path is set. The nonce master is derived from the signing key
+/// unless a nonce path is set, and each event's nonces are derived under it.
+#[derive(Debug, Clone)]
+pub struct OracleBuilder<S: Storage> {
+    storage: S,
+    xpriv: Xpriv,
+    network: Network,
+    account: u32,
+    signing_path: Option<DerivationPath>,
+    nonce_path: Option<DerivationPath>,
+}
+
+impl<S: Storage> OracleBuilder<S> {
+    pub fn new(storage: S, xpriv: Xpriv) -> Self {
+        Self {
+            storage,
+            xpriv,
+            network: Network::Bitcoin,
+            account: 0,
+            signing_path: None,
+            nonce_path: None,
+        }
+    }
+
+    /// Sets the network of the default signing path and of the nonce master.
+    /// Defaults to [`Network::Bitcoin`], regardless of the xpriv's network.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Sets the account of the default signing path, one per feed sharing a seed
+    pub fn with_account(mut self, account: u32) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Derives the signing key at `path` instead, ignoring the account
+    pub fn with_signing_path(mut self, path: DerivationPath) -> Self {
+        self.signing_path = Some(path);
+        self
+    }
+
+    /// Derives the nonce master at `path` of the xpriv instead of from the
+    /// signing key
+    pub fn with_nonce_path(mut self, path: DerivationPath) -> Self {
+        self.nonce_path = Some(path);
+        self
+    }
+
+    /// The path the signing key is derived at
+    pub fn signing_path(&self) -> Result<DerivationPath, Error> {
+        if let Some(path) = &self.signing_path {
+            return Ok(path.clone());
+        }
+
+        let coin_type = match self.network {
+            Network::Bitcoin => 0,
+            _ => 1,
+        };
+        let hardened = |i| ChildNumber::from_hardened_idx(i).map_err(|_| Error::InvalidArgument);
+        Ok(DerivationPath::from(vec![
+            hardened(86)?,
+            hardened(coin_type)?,
+            hardened(self.account)?,
+            ChildNumber::from_normal_idx(0).map_err(|_| Error::Internal)?,
+            ChildNumber::from_normal_idx(0).map_err(|_| Error::Internal)?,
+        ]))
+    }
+
+    /// Derives the signer without creating an oracle, e.g. for a remote signer
+    pub fn build_signer(&self) -> Result<LocalSigner, Error> {
+        let secp = Secp256k1::new();
+
+        let signing_key = self
+            .xpriv
+            .derive_priv(&secp, &self.signing_path()?)
+            .map_err(|_| Error::InvalidArgument)?
+            .private_key;
+
+        let nonce_xpriv = match &self.nonce_path {
+            Some(path) => {
+                let mut xpriv = self
+                    .xpriv
+                    .derive_priv(&secp, path)
+                    .map_err(|_| Error::InvalidArgument)?;
+                xpriv.network = self.network.into();
+                xpriv
+            }
+            None => nonce_master(&signing_key, self.network)?,
+        };
+
+        Ok(LocalSigner::new(signing_key, nonce_xpriv))
+    }
+
+    pub fn build(self) -> Result<Oracle<S>, Error> {
+        let signer = self.build_signer()?;
+        Ok(Oracle::from_signer(self.storage, signer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signer::Signer;
+    use crate::storage::MemoryStorage;
+    use crate::{EnumEventDescriptor, EventDescriptor, NonceDerivation, OracleEvent};
+    use std::str::FromStr;
+
+    fn xpriv() -> Xpriv {
+        // BIP 32 test vector 1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        Xpriv::new_master(Network::Bitcoin, &seed).unwrap()
+    }
+
+    async fn keys(builder: OracleBuilder<MemoryStorage>) -> (String, String) {
+        let signer = builder.build_signer().unwrap();
+        let event = OracleEvent {
+            oracle_nonces: vec![],
+            event_id: String::new(),
+            event_maturity_epoch: 0,
+            event_descriptor: EventDescriptor::EnumEvent(EnumEventDescriptor { outcomes: vec![] }),
+        };
+        let nonce = signer
+            .nonce_public_keys(&event, &[0], NonceDerivation::Indexed)
+            .await
+            .unwrap()[0];
+        (
+            hex::encode(signer.public_key().serialize()),
+            hex::encode(nonce.serialize()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_builder_vectors() {
+        let builder = || OracleBuilder::new(MemoryStorage::default(), xpriv());
+
+        assert_eq!(
+            keys(builder()).await,
+            (
+                "8724f200544f593846c3a868faa13cfc47fb29842e010758c0b95e6f79896434".to_string(),
+                "be9720df4204856c4144810150702da7625dde0a912af8e9ff2e37593237f2c0".to_string(),
+            )
+        );
+        assert_eq!(
+            keys(builder().with_account(1)).await,
+            (
+                "be44b12d1af4df56b5332bbfe906f56810c58745fe404f48ede8f7d3a24c8659".to_string(),
+                "6ef19c39d5fe376fc2ead0613e78bff00f9ae7696aa3c5e2fa624dd0e67abd1c".to_string(),
+            )
+        );
+        assert_eq!(
+            keys(builder().with_network(Network::Testnet)).await,
+            (
+                "83f00c97a27a6015d53acd68686aeaef0f26756cd3b24fef767420566e3de154".to_string(),
+                "66f2331097ebeadc47f72c0cfec6d123bdc5aba8577d4b378b4cbe7a9c378116".to_string(),
+            )
+        );
+        let paths = builder()
+            .with_signing_path(DerivationPath::from_str("m/0'/1").unwrap())
+            .with_nonce_path(DerivationPath::from_str("m/0'/2'").unwrap());
+        assert_eq!(
+            keys(paths).await,
+            (
+                "501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c".to_string(),
+                "cc714f542f9b33c3df5f701664cf7bcbc6d4ef99a2a5f949aaabc0309299a809".to_string(),
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_defaults() {
+        let builder = OracleBuilder::new(MemoryStorage::default(), xpriv());
+        assert_eq!(
+            builder.signing_path().unwrap(),
+            DerivationPath::from_str(crate::SIGNING_KEY_PATH).unwrap()
+        );
+
+        // existing oracles keep their keys
+        let signer = LocalSigner::from_xpriv(xpriv()).unwrap();
+        let oracle = builder.build().unwrap();
+        assert_eq!(oracle.public_key(), signer.public_key());
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        let nonces = signer
+            .nonce_public_keys(&ann.oracle_event, &[0], NonceDerivation::Indexed)
+            .await
+            .unwrap();
+        assert_eq!(ann.oracle_event.oracle_nonces, nonces);
+    }
+
+    #[test]
+    fn test_builder_invalid_account() {
+        let builder = OracleBuilder::new(MemoryStorage::default(), xpriv()).with_account(1 << 31);
+        assert!(matches!(builder.build(), Err(Error::InvalidArgument)));
+    }
+}
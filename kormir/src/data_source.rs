@@ -0,0 +1,83 @@
+use crate::error::Error;
+use crate::storage::OracleEventData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// The observed outcome of an event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Outcome {
+    /// Outcome of an enum event
+    Enum(String),
+    /// Outcome of a digit decomposition event
+    Numeric(i64),
+}
+
+/// A source of truth the oracle can resolve event outcomes from.
+pub trait DataSource {
+    /// Resolve the outcome of the given event, returns `None` if the
+    /// outcome is not known yet.
+    async fn resolve(&self, event: &OracleEventData) -> Result<Option<Outcome>, Error>;
+}
+
+/// Resolves outcomes from a JSON file mapping event ids to outcomes, e.g.
+/// `{"btc-usd-close": 6543210, "match-42": "home"}`. The file is read again
+/// on every resolution so it can be updated while the oracle is running.
+#[derive(Debug, Clone)]
+pub struct FileDataSource {
+    path: PathBuf,
+}
+
+impl FileDataSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl DataSource for FileDataSource {
+    async fn resolve(&self, event: &OracleEventData) -> Result<Option<Outcome>, Error> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(_) => return Err(Error::StorageFailure),
+        };
+        let mut outcomes: HashMap<String, Outcome> =
+            serde_json::from_str(&contents).map_err(|_| Error::InvalidArgument)?;
+
+        Ok(outcomes.remove(&event.event_id))
+    }
+}
+
+/// In-memory data source, outcomes and failures are set by hand. Useful for
+/// testing the attestation flow offline.
+#[derive(Debug, Clone, Default)]
+pub struct MockDataSource {
+    results: Arc<RwLock<HashMap<String, Result<Outcome, Error>>>>,
+}
+
+impl MockDataSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the event to the given outcome
+    pub fn set_outcome(&self, event_id: impl Into<String>, outcome: Outcome) {
+        let mut results = self.results.write().unwrap();
+        results.insert(event_id.into(), Ok(outcome));
+    }
+
+    /// Fail to resolve the event with the given error
+    pub fn set_error(&self, event_id: impl Into<String>, error: Error) {
+        let mut results = self.results.write().unwrap();
+        results.insert(event_id.into(), Err(error));
+    }
+}
+
+impl DataSource for MockDataSource {
+    async fn resolve(&self, event: &OracleEventData) -> Result<Option<Outcome>, Error> {
+        let results = self.results.read().unwrap();
+        results.get(&event.event_id).cloned().transpose()
+    }
+}
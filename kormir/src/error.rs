@@ -5,12 +5,26 @@ use std::fmt::{Display, Formatter};
 pub enum Error {
     /// Invalid argument given
     InvalidArgument,
+    /// Attempted to announce an event with an id that is already used
+    EventAlreadyExists,
     /// Attempted to sign an event that was already signed
     EventAlreadySigned,
+    /// Attempted to sign a different outcome than the one already committed to
+    ConflictingSignIntent,
+    /// Attempted to sign an event that was cancelled
+    EventCancelled,
+    /// The event can not move to the requested lifecycle state
+    InvalidStateTransition,
+    /// The signing policy refuses to sign the event before its maturity
+    EventNotMatured,
+    /// The signing policy requires an explicit override to sign before maturity
+    MaturityOverrideRequired,
     /// Event data was not found
     NotFound,
     /// The storage failed to read/save the data
     StorageFailure,
+    /// The signer failed or refused to sign, or returned an invalid signature
+    SignerFailure,
     /// User gave an invalid outcome
     InvalidOutcome,
     /// An error that should never happen, if it does it's a bug
@@ -21,9 +35,23 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::InvalidArgument => write!(f, "Invalid argument given"),
+            Error::EventAlreadyExists => write!(f, "Event already exists"),
             Error::EventAlreadySigned => write!(f, "Event already signed"),
+            Error::ConflictingSignIntent => {
+                write!(
+                    f,
+                    "A different outcome was already committed to for this event"
+                )
+            }
+            Error::EventCancelled => write!(f, "Event was cancelled"),
+            Error::InvalidStateTransition => write!(f, "Invalid event state transition"),
+            Error::EventNotMatured => write!(f, "Event has not matured yet"),
+            Error::MaturityOverrideRequired => {
+                write!(f, "Signing before maturity requires an explicit override")
+            }
             Error::NotFound => write!(f, "Event data not found"),
             Error::StorageFailure => write!(f, "Storage failure"),
+            Error::SignerFailure => write!(f, "Signer failure"),
             Error::InvalidOutcome => write!(f, "Invalid outcome"),
             Error::Internal => write!(f, "Internal error"),
         }
@@ -31,3 +59,64 @@ impl Display for Error {
 }
 
 impl std::error::Error for Error {}
+
+/// Reasons an announcement or attestation failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The announcement signature is invalid
+    InvalidAnnouncementSignature,
+    /// The event descriptor can not be attested to with the announced nonces
+    InvalidEventDescriptor,
+    /// The attestation is for a different event id
+    EventIdMismatch,
+    /// The attestation is signed by a different oracle
+    OraclePublicKeyMismatch,
+    /// The attestation has the wrong number of signatures
+    SignatureCountMismatch { expected: usize, actual: usize },
+    /// The attestation has the wrong number of outcomes
+    OutcomeCountMismatch { expected: usize, actual: usize },
+    /// The signature at the index does not use the announced nonce
+    NonceMismatch { index: usize },
+    /// The signature at the index does not sign its outcome
+    InvalidSignature { index: usize },
+    /// The outcome is not one of the enum event's outcomes
+    UnknownOutcome(String),
+    /// The sign outcome of a signed numeric event is not `+` or `-`
+    InvalidSign(String),
+    /// The outcome at the index is not a digit of the event's base
+    InvalidDigit { index: usize, digit: String },
+}
+
+impl Display for VerificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationError::InvalidAnnouncementSignature => {
+                write!(f, "Invalid announcement signature")
+            }
+            VerificationError::InvalidEventDescriptor => write!(f, "Invalid event descriptor"),
+            VerificationError::EventIdMismatch => write!(f, "Event id does not match"),
+            VerificationError::OraclePublicKeyMismatch => {
+                write!(f, "Oracle public key does not match")
+            }
+            VerificationError::SignatureCountMismatch { expected, actual } => {
+                write!(f, "Expected {expected} signatures, got {actual}")
+            }
+            VerificationError::OutcomeCountMismatch { expected, actual } => {
+                write!(f, "Expected {expected} outcomes, got {actual}")
+            }
+            VerificationError::NonceMismatch { index } => {
+                write!(f, "Signature {index} does not use the announced nonce")
+            }
+            VerificationError::InvalidSignature { index } => {
+                write!(f, "Signature {index} is invalid")
+            }
+            VerificationError::UnknownOutcome(outcome) => write!(f, "Unknown outcome: {outcome}"),
+            VerificationError::InvalidSign(sign) => write!(f, "Invalid sign outcome: {sign}"),
+            VerificationError::InvalidDigit { index, digit } => {
+                write!(f, "Invalid digit at {index}: {digit}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
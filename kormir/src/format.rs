@@ -0,0 +1,186 @@
+//! The formats events are listed in, shared by the server's `/list-events`
+//! and the CLI.
+
+use crate::error::Error;
+use crate::storage::{EventState, EventStatus, OracleEventData};
+use crate::{AttestationScheme, OracleAnnouncement, OracleAttestation, Readable, Writeable};
+use dlc_messages::ser_impls::{read_as_tlv, write_as_tlv};
+use lightning::io::Cursor;
+use lightning::ln::msgs::DecodeError;
+use lightning::ln::wire::Type;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// How announcements and attestations are encoded in a list of events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EventFormat {
+    /// The stored event data as JSON
+    #[default]
+    Json,
+    /// Hex of the announcement and attestation
+    Hex,
+    /// Hex of the TLV encoded announcement and attestation
+    Tlv,
+}
+
+impl Display for EventFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventFormat::Json => write!(f, "json"),
+            EventFormat::Hex => write!(f, "hex"),
+            EventFormat::Tlv => write!(f, "tlv"),
+        }
+    }
+}
+
+impl FromStr for EventFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EventFormat::Json),
+            "hex" => Ok(EventFormat::Hex),
+            "tlv" => Ok(EventFormat::Tlv),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+/// An event with its announcement and attestation encoded as hex
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodedEvent {
+    pub event_id: String,
+    pub event_maturity_epoch: u32,
+    pub announcement: String,
+    pub attestation: Option<String>,
+    pub state: EventState,
+    pub status: EventStatus,
+    pub attestation_scheme: AttestationScheme,
+}
+
+/// Encodes the announcement as hex, or as hex of its TLV encoding
+pub fn encode_announcement(announcement: &OracleAnnouncement, tlv: bool) -> String {
+    hex::encode(encode(announcement, tlv))
+}
+
+/// Encodes the attestation as hex, or as hex of its TLV encoding
+pub fn encode_attestation(attestation: &OracleAttestation, tlv: bool) -> String {
+    hex::encode(encode(attestation, tlv))
+}
+
+fn encode<T: Writeable + Type>(msg: &T, tlv: bool) -> Vec<u8> {
+    if tlv {
+        let mut bytes = Vec::new();
+        write_as_tlv(msg, &mut bytes).expect("writing to a vec");
+        bytes
+    } else {
+        msg.encode()
+    }
+}
+
+/// Decodes hex of an announcement, or of its TLV encoding
+pub fn decode_announcement(hex: &str) -> Result<OracleAnnouncement, Error> {
+    decode(hex)
+}
+
+/// Decodes hex of an attestation, or of its TLV encoding
+pub fn decode_attestation(hex: &str) -> Result<OracleAttestation, Error> {
+    decode(hex)
+}
+
+fn decode<T: Readable + Type>(hex: &str) -> Result<T, Error> {
+    let bytes = hex::decode(hex.trim()).map_err(|_| Error::InvalidArgument)?;
+    read_all(&bytes, |r| T::read(r))
+        .or_else(|| read_all(&bytes, |r| read_as_tlv(r)))
+        .ok_or(Error::InvalidArgument)
+}
+
+/// Reads the message, only if it takes up all of `bytes`
+fn read_all<T>(
+    bytes: &[u8],
+    read: impl FnOnce(&mut Cursor<&[u8]>) -> Result<T, DecodeError>,
+) -> Option<T> {
+    let mut cursor = Cursor::new(bytes);
+    let msg = read(&mut cursor).ok()?;
+    (cursor.position() as usize == bytes.len()).then_some(msg)
+}
+
+/// Lists the events in `format`, with their status at `now`
+pub fn format_events(events: &[OracleEventData], format: EventFormat, now: u32) -> Value {
+    let tlv = match format {
+        EventFormat::Json => return serde_json::to_value(events).unwrap_or_default(),
+        EventFormat::Hex => false,
+        EventFormat::Tlv => true,
+    };
+
+    let events = events
+        .iter()
+        .map(|e| EncodedEvent {
+            event_id: e.announcement.oracle_event.event_id.clone(),
+            event_maturity_epoch: e.announcement.oracle_event.event_maturity_epoch,
+            announcement: encode_announcement(&e.announcement, tlv),
+            attestation: e.attestation().map(|a| encode_attestation(&a, tlv)),
+            state: e.state,
+            status: e.status(now),
+            attestation_scheme: e.attestation_scheme,
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_value(events).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::storage::Storage;
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::Network;
+
+    #[tokio::test]
+    async fn test_format_events() {
+        let xpriv = Xpriv::new_master(Network::Regtest, &[1; 32]).unwrap();
+        let oracle = Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap();
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        let att = oracle
+            .sign_enum_event("test".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        let events = oracle.storage.list_events().await.unwrap();
+
+        let json = format_events(&events, EventFormat::Json, 0);
+        assert_eq!(json, serde_json::to_value(&events).unwrap());
+
+        let hex = format_events(&events, EventFormat::Hex, 0);
+        assert_eq!(hex[0]["event_id"], "test");
+        assert_eq!(hex[0]["announcement"], hex::encode(ann.encode()));
+        assert_eq!(hex[0]["attestation"], hex::encode(att.encode()));
+        assert_eq!(hex[0]["status"], "signed");
+
+        let tlv = format_events(&events, EventFormat::Tlv, 0);
+        let mut bytes = Vec::new();
+        write_as_tlv(&ann, &mut bytes).unwrap();
+        assert_eq!(tlv[0]["announcement"], hex::encode(bytes));
+        assert_ne!(tlv[0]["attestation"], hex[0]["attestation"]);
+
+        assert_eq!("tlv".parse::<EventFormat>().unwrap(), EventFormat::Tlv);
+        assert!("xml".parse::<EventFormat>().is_err());
+
+        // both encodings decode to the same messages
+        for format in [&hex, &tlv] {
+            let decoded = decode_announcement(format[0]["announcement"].as_str().unwrap());
+            assert_eq!(decoded.unwrap(), ann);
+            let decoded = decode_attestation(format[0]["attestation"].as_str().unwrap());
+            assert_eq!(decoded.unwrap(), att);
+        }
+        let trailing = format!("{}00", hex::encode(ann.encode()));
+        assert!(decode_announcement(&trailing).is_err());
+        assert!(decode_attestation(&hex::encode(ann.encode())).is_err());
+        assert!(decode_announcement("zz").is_err());
+    }
+}
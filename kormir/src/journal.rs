@@ -0,0 +1,229 @@
+//! A durable record of the attestation nonces a signer has used.
+//!
+//! Signing two different outcomes with the same nonce leaks the key. A
+//! [`Signer`](crate::signer::Signer) that can not trust its caller to never
+//! ask for that, like a remote signer, keeps a [`NonceJournal`] and only
+//! hands out signatures that are recorded in it.
+
+use crate::error::Error;
+use crate::AttestationScheme;
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use dlc_messages::oracle_msgs::OracleAnnouncement;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// A nonce that was signed with, and what it signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JournalEntry {
+    event_id: String,
+    nonce: XOnlyPublicKey,
+    outcome: String,
+    scheme: AttestationScheme,
+    signature: Signature,
+}
+
+/// Every nonce signed with, by nonce. Persistent journals are an append only
+/// file of json lines, each entry is synced to disk before its signature is
+/// handed out.
+#[derive(Debug, Default)]
+pub struct NonceJournal {
+    /// None for a journal that only lives in memory
+    file: Option<File>,
+    entries: HashMap<XOnlyPublicKey, JournalEntry>,
+}
+
+impl NonceJournal {
+    /// Opens the journal at `path`, creating it if it does not exist. An entry
+    /// cut short by a crash was never handed out and is dropped.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path.as_ref())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let complete = contents.rfind('\n').map_or(0, |i| i + 1);
+        if complete < contents.len() {
+            log::warn!("Dropping a partially written nonce journal entry");
+            file.set_len(complete as u64)?;
+        }
+
+        let mut entries = HashMap::new();
+        for line in contents[..complete].lines() {
+            let entry: JournalEntry = serde_json::from_str(line)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            entries.insert(entry.nonce, entry);
+        }
+
+        Ok(Self {
+            file: Some(file),
+            entries,
+        })
+    }
+
+    /// A journal that is forgotten when it is dropped, only for tests
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Signs the outcomes with `sign` unless one of the announcement's nonces
+    /// was used before for anything else, and records them before returning
+    /// the signatures. Asking for the same outcomes again returns the
+    /// signatures that were handed out the first time.
+    pub fn sign_outcomes(
+        &mut self,
+        announcement: &OracleAnnouncement,
+        outcomes: &[String],
+        scheme: AttestationScheme,
+        sign: impl FnOnce() -> Result<Vec<Signature>, Error>,
+    ) -> Result<Vec<Signature>, Error> {
+        let event = &announcement.oracle_event;
+        if outcomes.len() != event.oracle_nonces.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let mut signed = vec![];
+        for (nonce, outcome) in event.oracle_nonces.iter().zip(outcomes) {
+            if let Some(entry) = self.entries.get(nonce) {
+                if entry.event_id != event.event_id
+                    || &entry.outcome != outcome
+                    || entry.scheme != scheme
+                {
+                    log::error!(
+                        "Refusing to sign event {} again with a different outcome",
+                        event.event_id
+                    );
+                    return Err(Error::ConflictingSignIntent);
+                }
+                signed.push(entry.signature);
+            }
+        }
+        if signed.len() == outcomes.len() {
+            return Ok(signed);
+        }
+
+        let sigs = sign()?;
+        if sigs.len() != outcomes.len() {
+            return Err(Error::SignerFailure);
+        }
+
+        let new_entries = event
+            .oracle_nonces
+            .iter()
+            .zip(outcomes)
+            .zip(&sigs)
+            .filter(|((nonce, _), _)| !self.entries.contains_key(nonce))
+            .map(|((nonce, outcome), signature)| JournalEntry {
+                event_id: event.event_id.clone(),
+                nonce: *nonce,
+                outcome: outcome.clone(),
+                scheme,
+                signature: *signature,
+            })
+            .collect::<Vec<_>>();
+        if let Some(file) = self.file.as_mut() {
+            let mut lines = String::new();
+            for entry in &new_entries {
+                lines.push_str(&serde_json::to_string(entry).map_err(|_| Error::Internal)?);
+                lines.push('\n');
+            }
+            file.write_all(lines.as_bytes())
+                .and_then(|_| file.sync_data())
+                .map_err(|e| {
+                    log::error!("Could not write the nonce journal: {e}");
+                    Error::StorageFailure
+                })?;
+        }
+        for entry in new_entries {
+            self.entries.insert(entry.nonce, entry);
+        }
+
+        Ok(sigs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signer::LocalSigner;
+    use crate::storage::{MemoryStorage, Storage};
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "kormir-journal-{}",
+            hex::encode(thread_rng().gen::<[u8; 8]>())
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_nonce_journal() {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let signer = LocalSigner::from_xpriv(xpriv).unwrap();
+        let oracle = Oracle::from_signer(MemoryStorage::default(), signer.clone());
+        let ann = oracle
+            .create_numeric_event("numeric".to_string(), 10, 2, false, 0, "m".into(), 100)
+            .await
+            .unwrap();
+        let data = oracle.storage.get_event("numeric".into()).await.unwrap();
+        let indexes = data.unwrap().indexes;
+        let scheme = AttestationScheme::Legacy;
+        let outcomes = vec!["4".to_string(), "2".to_string()];
+        let other = vec!["4".to_string(), "3".to_string()];
+        let sign = |outcomes: &[String], scheme| {
+            let signer = signer.clone();
+            let (ann, indexes, outcomes) = (ann.clone(), indexes.clone(), outcomes.to_vec());
+            move || signer.sign_outcomes_sync(&ann, &indexes, &outcomes, scheme)
+        };
+
+        let path = temp_path();
+        let mut journal = NonceJournal::open(&path).unwrap();
+        let sigs = journal
+            .sign_outcomes(&ann, &outcomes, scheme, sign(&outcomes, scheme))
+            .unwrap();
+        // the same outcomes get the same signatures, without signing again
+        let again = journal.sign_outcomes(&ann, &outcomes, scheme, || Err(Error::Internal));
+        assert_eq!(again.unwrap(), sigs);
+
+        let res = journal.sign_outcomes(&ann, &other, scheme, sign(&other, scheme));
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+        let tagged = AttestationScheme::Tagged;
+        let res = journal.sign_outcomes(&ann, &outcomes, tagged, sign(&outcomes, tagged));
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+
+        // not even after a restart
+        drop(journal);
+        let mut journal = NonceJournal::open(&path).unwrap();
+        let res = journal.sign_outcomes(&ann, &other, scheme, sign(&other, scheme));
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+        let res = journal.sign_outcomes(&ann, &outcomes, scheme, || Err(Error::Internal));
+        assert_eq!(res.unwrap(), sigs);
+    }
+
+    #[test]
+    fn test_journal_drops_partial_entry() {
+        let path = temp_path();
+        std::fs::write(&path, "{\"event_id\":\"cut").unwrap();
+        let journal = NonceJournal::open(&path).unwrap();
+        assert!(journal.entries.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::write(&path, "not json\n").unwrap();
+        assert!(NonceJournal::open(&path).is_err());
+    }
+}
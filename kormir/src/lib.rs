@@ -1,19 +1,46 @@
 #![allow(async_fn_in_trait)]
 
+pub mod adaptor;
+pub mod backup;
+pub mod batch;
+pub mod builder;
+pub mod data_source;
 pub mod error;
+pub mod format;
+pub mod journal;
+pub mod metadata;
 #[cfg(feature = "nostr")]
 pub mod nostr_events;
+pub mod numeric;
+pub mod offline;
+pub mod policy;
+#[cfg(unix)]
+pub mod remote_signer;
+pub mod rotation;
+pub mod scheduler;
+pub mod secret;
+pub mod series;
+pub mod signer;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 pub mod storage;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+pub mod verify;
 
+pub use crate::builder::OracleBuilder;
 use crate::error::Error;
-use crate::storage::Storage;
-use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv};
-use bitcoin::hashes::{sha256, Hash};
+use crate::numeric::{Decimal, Rounding};
+use crate::policy::{Clock, SigningPolicy, SystemClock};
+use crate::rotation::KeyHandover;
+use crate::signer::{LocalSigner, Signer};
+use crate::storage::{EventState, OracleEventData, Storage};
+use bitcoin::bip32::{DerivationPath, Xpriv};
 use bitcoin::key::XOnlyPublicKey;
-use bitcoin::secp256k1::{All, Message, Secp256k1, SecretKey};
-use bitcoin::Network;
-use secp256k1_zkp::Keypair;
+use bitcoin::secp256k1::{All, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub use bitcoin;
 pub use bitcoin::secp256k1::schnorr::Signature;
@@ -29,67 +56,374 @@ pub use nostr;
 // first key for taproot address
 const SIGNING_KEY_PATH: &str = "m/86'/0'/0'/0/0";
 
+/// Reserved enum outcome that is attested to when the event is cancelled
+pub const CANCELLED_OUTCOME: &str = "cancelled";
+
+/// How the oracle derives the nonce keys for new events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NonceDerivation {
+    /// Nonce keys are derived from the nonce xpriv at the index handed out by
+    /// [`Storage::get_next_nonce_indexes`].
+    #[default]
+    Indexed,
+    /// Nonce keys are derived from the announced event and digit position,
+    /// independent of the storage's nonce counter. An event id announced again
+    /// with different contents gets different nonces.
+    EventDerived,
+}
+
+/// How announcements and outcomes are hashed before they are signed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationScheme {
+    /// Plain sha256 of the outcome, and of the serialized oracle event for
+    /// announcements.
+    #[default]
+    Legacy,
+    /// BIP 340 tagged hashes with a tag per message type, as in the newer DLC
+    /// specification drafts.
+    Tagged,
+}
+
+impl AttestationScheme {
+    /// The version advertised in the oracle's metadata
+    pub fn version(&self) -> u16 {
+        match self {
+            AttestationScheme::Legacy => 0,
+            AttestationScheme::Tagged => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for AttestationScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttestationScheme::Legacy => write!(f, "legacy"),
+            AttestationScheme::Tagged => write!(f, "tagged"),
+        }
+    }
+}
+
+impl FromStr for AttestationScheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(AttestationScheme::Legacy),
+            "tagged" => Ok(AttestationScheme::Tagged),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct Oracle<S: Storage> {
+pub struct Oracle<S: Storage, K: Signer = LocalSigner> {
     pub storage: S,
-    key_pair: Keypair,
-    nonce_xpriv: Xpriv,
+    signer: K,
+    /// Signers of the oracle's previous keys, still used to sign their events
+    retired_signers: Vec<K>,
+    nonce_derivation: NonceDerivation,
+    attestation_scheme: AttestationScheme,
+    signing_policy: SigningPolicy,
+    clock: Arc<dyn Clock>,
     secp: Secp256k1<All>,
 }
 
 impl<S: Storage> Oracle<S> {
     pub fn new(storage: S, signing_key: SecretKey, nonce_xpriv: Xpriv) -> Self {
-        let secp = Secp256k1::new();
+        Self::from_signer(storage, LocalSigner::new(signing_key, nonce_xpriv))
+    }
+
+    /// Derives the keys at the default paths, see [`OracleBuilder`] for others
+    pub fn from_xpriv(storage: S, xpriv: Xpriv) -> Result<Self, Error> {
+        OracleBuilder::new(storage, xpriv).build()
+    }
+
+    pub fn from_signing_key(storage: S, signing_key: SecretKey) -> Result<Self, Error> {
+        Ok(Self::from_signer(
+            storage,
+            LocalSigner::from_signing_key(signing_key)?,
+        ))
+    }
+
+    /// Returns the keys for the oracle, used for Nostr.
+    #[cfg(feature = "nostr")]
+    pub fn nostr_keys(&self) -> nostr::Keys {
+        self.signer.nostr_keys()
+    }
+
+    /// Exports the oracle's keys and storage, encrypted with `passphrase`.
+    /// See [`OracleBackup`](backup::OracleBackup).
+    pub async fn export_backup(&self, passphrase: &str) -> Result<Vec<u8>, Error> {
+        backup::OracleBackup::create(&self.storage, &self.signer, &self.retired_signers)
+            .await?
+            .encrypt(passphrase)
+    }
+
+    /// Restores an encrypted backup into `storage` and creates the oracle
+    /// with the backed up keys.
+    pub async fn import_backup(storage: S, backup: &[u8], passphrase: &str) -> Result<Self, Error> {
+        let backup = backup::OracleBackup::decrypt(backup, passphrase)?;
+        backup.restore(&storage).await?;
+        let oracle = Self::from_signer(storage, backup.signer());
+        Ok(backup
+            .retired_signers()
+            .into_iter()
+            .fold(oracle, |oracle, retired| {
+                oracle.with_retired_signer(retired)
+            }))
+    }
+}
+
+impl<S: Storage, K: Signer> Oracle<S, K> {
+    /// Creates an oracle that never touches the keys itself, every signature
+    /// is requested from the `signer`.
+    pub fn from_signer(storage: S, signer: K) -> Self {
         Self {
             storage,
-            key_pair: Keypair::from_secret_key(&secp, &signing_key),
-            nonce_xpriv,
-            secp,
+            signer,
+            retired_signers: Vec::new(),
+            nonce_derivation: NonceDerivation::default(),
+            attestation_scheme: AttestationScheme::default(),
+            signing_policy: SigningPolicy::default(),
+            clock: Arc::new(SystemClock),
+            secp: Secp256k1::new(),
         }
     }
 
-    pub fn from_xpriv(storage: S, xpriv: Xpriv) -> Result<Self, Error> {
-        let secp = Secp256k1::new();
+    pub fn signer(&self) -> &K {
+        &self.signer
+    }
 
-        let signing_key = derive_signing_key(&secp, xpriv)?;
-        Self::from_signing_key(storage, signing_key)
+    /// Adds the signer of a key the oracle has handed over from, so events
+    /// announced with it can still be signed.
+    pub fn with_retired_signer(mut self, signer: K) -> Self {
+        self.retired_signers.push(signer);
+        self
     }
 
-    pub fn from_signing_key(storage: S, signing_key: SecretKey) -> Result<Self, Error> {
-        let secp = Secp256k1::new();
+    /// The signers of the keys the oracle has handed over from
+    pub fn retired_signers(&self) -> &[K] {
+        &self.retired_signers
+    }
 
-        let xpriv_bytes = sha256::Hash::hash(&signing_key.secret_bytes()).to_byte_array();
-        let nonce_xpriv =
-            Xpriv::new_master(Network::Bitcoin, &xpriv_bytes).map_err(|_| Error::Internal)?;
+    /// Returns the signer for events announced with `public_key`.
+    pub fn signer_for(&self, public_key: &XOnlyPublicKey) -> Result<&K, Error> {
+        std::iter::once(&self.signer)
+            .chain(self.retired_signers.iter())
+            .find(|s| s.public_key() == *public_key)
+            .ok_or(Error::InvalidArgument)
+    }
 
-        Ok(Self {
-            storage,
-            key_pair: Keypair::from_secret_key(&secp, &signing_key),
-            nonce_xpriv,
-            secp,
-        })
+    /// Has the current key sign a statement handing the oracle over to
+    /// `new_public_key` from `effective_at`.
+    pub async fn hand_over_to(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error> {
+        let handover = self
+            .signer
+            .sign_key_handover(new_public_key, effective_at)
+            .await?;
+        handover
+            .verify(&self.secp)
+            .map_err(|_| Error::SignerFailure)?;
+        Ok(handover)
+    }
+
+    /// Sets how nonce keys are derived for newly created events. Events that
+    /// were announced with a different mode can still be signed.
+    pub fn with_nonce_derivation(mut self, nonce_derivation: NonceDerivation) -> Self {
+        self.nonce_derivation = nonce_derivation;
+        self
+    }
+
+    pub fn nonce_derivation(&self) -> NonceDerivation {
+        self.nonce_derivation
+    }
+
+    /// Sets how newly created events are signed. Each event keeps the scheme
+    /// it was announced with, so older events still verify.
+    pub fn with_attestation_scheme(mut self, attestation_scheme: AttestationScheme) -> Self {
+        self.attestation_scheme = attestation_scheme;
+        self
+    }
+
+    pub fn attestation_scheme(&self) -> AttestationScheme {
+        self.attestation_scheme
+    }
+
+    /// Sets when outcomes may be signed relative to the events' maturity.
+    pub fn with_signing_policy(mut self, signing_policy: SigningPolicy) -> Self {
+        self.signing_policy = signing_policy;
+        self
+    }
+
+    pub fn signing_policy(&self) -> SigningPolicy {
+        self.signing_policy
+    }
+
+    /// Sets the clock the signing policy is checked against and the
+    /// [`Scheduler`](scheduler::Scheduler) runs by.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Checks the signing policy allows signing the event now.
+    fn check_maturity(&self, data: &OracleEventData, override_maturity: bool) -> Result<(), Error> {
+        self.signing_policy.check(
+            data.announcement.oracle_event.event_maturity_epoch,
+            self.clock.now(),
+            override_maturity,
+        )
     }
 
     pub fn public_key(&self) -> XOnlyPublicKey {
-        self.key_pair.x_only_public_key().0
+        self.signer.public_key()
     }
 
-    /// Returns the keys for the oracle, used for Nostr.
+    /// Returns the oracle's public key as a nostr public key.
     #[cfg(feature = "nostr")]
-    pub fn nostr_keys(&self) -> nostr::Keys {
-        let sec = nostr::key::SecretKey::from_slice(&self.key_pair.secret_key().secret_bytes()[..])
-            .expect("just converting types");
-        nostr::Keys::new(sec)
+    pub fn nostr_public_key(&self) -> nostr::PublicKey {
+        nostr::PublicKey::from_slice(&self.public_key().serialize()).expect("just converting types")
+    }
+
+    /// Signs a nostr event with the oracle's key, see [`nostr_events`].
+    #[cfg(feature = "nostr")]
+    pub async fn sign_nostr_event(
+        &self,
+        event: nostr::UnsignedEvent,
+    ) -> Result<nostr::Event, Error> {
+        self.signer.sign_nostr_event(event).await
+    }
+
+    /// Reserves nonce indexes for a new event and fills in its public nonces,
+    /// derived with the oracle's configured [`NonceDerivation`]. Fails before
+    /// reserving anything if the event can not be attested to or its id is
+    /// already used.
+    async fn new_event_nonces(&self, oracle_event: &mut OracleEvent) -> Result<Vec<u32>, Error> {
+        let num = verify::event_nonce_count(&oracle_event.event_descriptor)
+            .ok_or(Error::InvalidArgument)?;
+        if self
+            .storage
+            .get_event(oracle_event.event_id.clone())
+            .await?
+            .is_some()
+        {
+            return Err(Error::EventAlreadyExists);
+        }
+
+        let indexes = self.storage.get_next_nonce_indexes(num).await?;
+        let nonces = self
+            .signer
+            .nonce_public_keys(oracle_event, &indexes, self.nonce_derivation)
+            .await?;
+        if nonces.len() != num {
+            return Err(Error::SignerFailure);
+        }
+        oracle_event.oracle_nonces = nonces;
+
+        Ok(indexes)
+    }
+
+    /// Signs the announcement of the event and saves it.
+    async fn announce(
+        &self,
+        oracle_event: OracleEvent,
+        indexes: Vec<u32>,
+    ) -> Result<OracleAnnouncement, Error> {
+        let ann = self.sign_announcement(oracle_event).await?;
+        let _ = self
+            .storage
+            .save_announcement(ann.clone(), indexes, self.attestation_scheme)
+            .await?;
+
+        Ok(ann)
+    }
+
+    /// Signs the announcement of the event, checking the signer's signature.
+    async fn sign_announcement(
+        &self,
+        oracle_event: OracleEvent,
+    ) -> Result<OracleAnnouncement, Error> {
+        oracle_event.validate().map_err(|_| Error::Internal)?;
+
+        let announcement_signature = self
+            .signer
+            .sign_announcement(&oracle_event, self.attestation_scheme)
+            .await?;
+
+        let ann = OracleAnnouncement {
+            oracle_event,
+            oracle_public_key: self.public_key(),
+            announcement_signature,
+        };
+        // the signer is not trusted to have signed the right thing
+        let msg = signer::announcement_message(&ann.oracle_event, self.attestation_scheme)?;
+        self.secp
+            .verify_schnorr(&ann.announcement_signature, &msg, &ann.oracle_public_key)
+            .map_err(|_| Error::SignerFailure)?;
+
+        Ok(ann)
     }
 
-    fn get_nonce_key(&self, index: u32) -> SecretKey {
-        self.nonce_xpriv
-            .derive_priv(
-                &self.secp,
-                &[ChildNumber::from_hardened_idx(index).unwrap()],
+    /// Has the signer sign the outcomes with the nonces of the announcement,
+    /// checking every signature before it is used.
+    async fn sign_outcomes(
+        &self,
+        data: &OracleEventData,
+        outcomes: &[String],
+    ) -> Result<Vec<Signature>, Error> {
+        let nonces = &data.announcement.oracle_event.oracle_nonces;
+        if data.indexes.len() != nonces.len() || outcomes.len() != nonces.len() {
+            return Err(Error::Internal);
+        }
+
+        let public_key = data.announcement.oracle_public_key;
+        let sigs = self
+            .signer_for(&public_key)?
+            .sign_outcomes(
+                &data.announcement,
+                &data.indexes,
+                outcomes,
+                data.attestation_scheme,
             )
-            .unwrap()
-            .private_key
+            .await?;
+        if sigs.len() != outcomes.len() {
+            return Err(Error::SignerFailure);
+        }
+
+        for ((sig, outcome), nonce) in sigs.iter().zip(outcomes).zip(nonces) {
+            // verify our nonce is the same as the one in the announcement
+            if sig.serialize()[..32] != nonce.serialize() {
+                return Err(Error::SignerFailure);
+            }
+            // verify our signature
+            let msg = signer::outcome_message(outcome, data.attestation_scheme);
+            if self.secp.verify_schnorr(sig, &msg, &public_key).is_err() {
+                return Err(Error::SignerFailure);
+            }
+        }
+
+        Ok(sigs)
+    }
+
+    /// Durably commits to the outcomes we are about to sign for an event.
+    /// Fails if a different set of outcomes was committed to before, so that a
+    /// crash or a racing signer can never get two outcomes signed with the same
+    /// nonces.
+    async fn commit_sign_intent(&self, event_id: &str, outcomes: Vec<String>) -> Result<(), Error> {
+        let committed = self
+            .storage
+            .save_sign_intent(event_id.to_string(), outcomes.clone())
+            .await?;
+        if committed != outcomes {
+            return Err(Error::ConflictingSignIntent);
+        }
+        Ok(())
     }
 
     pub async fn create_enum_event(
@@ -98,50 +432,56 @@ impl<S: Storage> Oracle<S> {
         outcomes: Vec<String>,
         event_maturity_epoch: u32,
     ) -> Result<OracleAnnouncement, Error> {
-        let indexes = self.storage.get_next_nonce_indexes(1).await?;
-        let oracle_nonces = indexes
-            .iter()
-            .map(|i| {
-                let nonce_key = self.get_nonce_key(*i);
-                nonce_key.x_only_public_key(&self.secp).0
-            })
-            .collect();
         let event_descriptor = EventDescriptor::EnumEvent(EnumEventDescriptor { outcomes });
-        let oracle_event = OracleEvent {
-            oracle_nonces,
+        let mut oracle_event = OracleEvent {
+            oracle_nonces: vec![],
             event_id,
             event_maturity_epoch,
             event_descriptor,
         };
-        oracle_event.validate().map_err(|_| Error::Internal)?;
+        let indexes = self.new_event_nonces(&mut oracle_event).await?;
 
-        // create signature
-        let mut data = Vec::new();
-        oracle_event.write(&mut data).map_err(|_| Error::Internal)?;
-        let hash = sha256::Hash::hash(&data);
-        let msg = Message::from_digest(hash.to_byte_array());
-        let announcement_signature = self.secp.sign_schnorr_no_aux_rand(&msg, &self.key_pair);
-
-        let ann = OracleAnnouncement {
-            oracle_event,
-            oracle_public_key: self.public_key(),
-            announcement_signature,
-        };
-        ann.validate(&self.secp).map_err(|_| Error::Internal)?;
+        self.announce(oracle_event, indexes).await
+    }
 
-        let _ = self.storage.save_announcement(ann.clone(), indexes).await?;
+    pub async fn sign_enum_event(
+        &self,
+        event_id: String,
+        outcome: String,
+    ) -> Result<OracleAttestation, Error> {
+        self.sign_enum_event_inner(event_id, outcome, false).await
+    }
 
-        Ok(ann)
+    /// Like [`Oracle::sign_enum_event`], but explicitly overrides a
+    /// [`SigningPolicy::RequireOverride`] policy to sign before maturity.
+    pub async fn sign_enum_event_early(
+        &self,
+        event_id: String,
+        outcome: String,
+    ) -> Result<OracleAttestation, Error> {
+        self.sign_enum_event_inner(event_id, outcome, true).await
     }
 
-    pub async fn sign_enum_event(
+    async fn sign_enum_event_inner(
         &self,
         event_id: String,
         outcome: String,
+        override_maturity: bool,
     ) -> Result<OracleAttestation, Error> {
         let Some(data) = self.storage.get_event(event_id.clone()).await? else {
             return Err(Error::NotFound);
         };
+        check_signable(&data)?;
+        self.check_maturity(&data, override_maturity)?;
+        self.attest_enum_outcome(data, outcome).await
+    }
+
+    async fn attest_enum_outcome(
+        &self,
+        data: OracleEventData,
+        outcome: String,
+    ) -> Result<OracleAttestation, Error> {
+        let event_id = data.event_id.clone();
         if !data.signatures.is_empty() {
             return Err(Error::EventAlreadySigned);
         }
@@ -155,33 +495,14 @@ impl<S: Storage> Oracle<S> {
         if !descriptor.outcomes.contains(&outcome) {
             return Err(Error::InvalidOutcome);
         }
+        // fail before committing if we no longer hold the event's key
+        self.signer_for(&data.announcement.oracle_public_key)?;
 
-        let nonce_index = data.indexes.first().expect("Already checked length");
-        let nonce_key = self.get_nonce_key(*nonce_index);
-
-        let hash = sha256::Hash::hash(outcome.as_bytes());
-        let msg = Message::from_digest(hash.to_byte_array());
-
-        let sig = dlc::secp_utils::schnorrsig_sign_with_nonce(
-            &self.secp,
-            &msg,
-            &self.key_pair,
-            &nonce_key.secret_bytes(),
-        );
-
-        // verify our nonce is the same as the one in the announcement
-        debug_assert!(
-            sig.encode()[..32] == data.announcement.oracle_event.oracle_nonces[0].serialize()
-        );
+        self.commit_sign_intent(&event_id, vec![outcome.clone()])
+            .await?;
 
-        // verify our signature
-        if self
-            .secp
-            .verify_schnorr(&sig, &msg, &self.key_pair.x_only_public_key().0)
-            .is_err()
-        {
-            return Err(Error::Internal);
-        };
+        let outcomes = vec![outcome.clone()];
+        let sig = self.sign_outcomes(&data, &outcomes).await?[0];
 
         let sigs = vec![(outcome.clone(), sig)];
 
@@ -191,7 +512,7 @@ impl<S: Storage> Oracle<S> {
 
         let attestation = OracleAttestation {
             event_id: data.announcement.oracle_event.event_id,
-            oracle_public_key: self.public_key(),
+            oracle_public_key: data.announcement.oracle_public_key,
             signatures: vec![sig],
             outcomes: vec![outcome],
         };
@@ -199,76 +520,117 @@ impl<S: Storage> Oracle<S> {
         Ok(attestation)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_numeric_event(
         &self,
         event_id: String,
+        base: u16,
         num_digits: u16,
         is_signed: bool,
         precision: i32,
         unit: String,
         event_maturity_epoch: u32,
     ) -> Result<OracleAnnouncement, Error> {
-        if num_digits == 0 {
-            return Err(Error::InvalidArgument);
-        }
-
-        let num_nonces = if is_signed {
-            num_digits as usize + 1
-        } else {
-            num_digits as usize
-        };
-
-        let indexes = self.storage.get_next_nonce_indexes(num_nonces).await?;
-        let oracle_nonces = indexes
-            .iter()
-            .map(|i| {
-                let nonce_key = self.get_nonce_key(*i);
-                nonce_key.x_only_public_key(&self.secp).0
-            })
-            .collect();
         let event_descriptor =
             EventDescriptor::DigitDecompositionEvent(DigitDecompositionEventDescriptor {
-                base: 2,
+                base,
                 is_signed,
                 unit,
                 precision,
                 nb_digits: num_digits,
             });
-        let oracle_event = OracleEvent {
-            oracle_nonces,
+        let mut oracle_event = OracleEvent {
+            oracle_nonces: vec![],
             event_id,
             event_maturity_epoch,
             event_descriptor,
         };
-        oracle_event.validate().map_err(|_| Error::Internal)?;
+        let indexes = self.new_event_nonces(&mut oracle_event).await?;
 
-        // create signature
-        let mut data = Vec::new();
-        oracle_event.write(&mut data).map_err(|_| Error::Internal)?;
-        let hash = sha256::Hash::hash(&data);
-        let msg = Message::from_digest(hash.to_byte_array());
-        let announcement_signature = self.secp.sign_schnorr_no_aux_rand(&msg, &self.key_pair);
+        self.announce(oracle_event, indexes).await
+    }
 
-        let ann = OracleAnnouncement {
-            oracle_event,
-            oracle_public_key: self.public_key(),
-            announcement_signature,
-        };
-        ann.validate(&self.secp).map_err(|_| Error::Internal)?;
+    pub async fn sign_numeric_event(
+        &self,
+        event_id: String,
+        outcome: i64,
+    ) -> Result<OracleAttestation, Error> {
+        self.sign_numeric_event_inner(event_id, outcome, false)
+            .await
+    }
 
-        let _ = self.storage.save_announcement(ann.clone(), indexes).await?;
+    /// Like [`Oracle::sign_numeric_event`], but explicitly overrides a
+    /// [`SigningPolicy::RequireOverride`] policy to sign before maturity.
+    pub async fn sign_numeric_event_early(
+        &self,
+        event_id: String,
+        outcome: i64,
+    ) -> Result<OracleAttestation, Error> {
+        self.sign_numeric_event_inner(event_id, outcome, true).await
+    }
 
-        Ok(ann)
+    /// Signs a decimal value for a numeric event, scaled to its announced
+    /// precision with `rounding`. Values outside of the announced range are
+    /// clamped to it when `clamp` is set, see [`numeric::numeric_outcome`].
+    pub async fn sign_numeric_event_decimal(
+        &self,
+        event_id: String,
+        value: Decimal,
+        rounding: Rounding,
+        clamp: bool,
+    ) -> Result<OracleAttestation, Error> {
+        let outcome = self
+            .decimal_outcome(&event_id, value, rounding, clamp)
+            .await?;
+        self.sign_numeric_event_inner(event_id, outcome, false)
+            .await
     }
 
-    pub async fn sign_numeric_event(
+    /// Like [`Oracle::sign_numeric_event_decimal`], but explicitly overrides a
+    /// [`SigningPolicy::RequireOverride`] policy to sign before maturity.
+    pub async fn sign_numeric_event_decimal_early(
+        &self,
+        event_id: String,
+        value: Decimal,
+        rounding: Rounding,
+        clamp: bool,
+    ) -> Result<OracleAttestation, Error> {
+        let outcome = self
+            .decimal_outcome(&event_id, value, rounding, clamp)
+            .await?;
+        self.sign_numeric_event_inner(event_id, outcome, true).await
+    }
+
+    /// The outcome of the numeric event for a decimal value
+    pub async fn decimal_outcome(
+        &self,
+        event_id: &str,
+        value: Decimal,
+        rounding: Rounding,
+        clamp: bool,
+    ) -> Result<i64, Error> {
+        let Some(data) = self.storage.get_event(event_id.to_string()).await? else {
+            return Err(Error::NotFound);
+        };
+        match &data.announcement.oracle_event.event_descriptor {
+            EventDescriptor::DigitDecompositionEvent(desc) => {
+                numeric::numeric_outcome(desc, value, rounding, clamp)
+            }
+            EventDescriptor::EnumEvent(_) => Err(Error::InvalidOutcome),
+        }
+    }
+
+    async fn sign_numeric_event_inner(
         &self,
         event_id: String,
         outcome: i64,
+        override_maturity: bool,
     ) -> Result<OracleAttestation, Error> {
         let Some(data) = self.storage.get_event(event_id.clone()).await? else {
             return Err(Error::NotFound);
         };
+        check_signable(&data)?;
+        self.check_maturity(&data, override_maturity)?;
         if !data.signatures.is_empty() {
             return Err(Error::EventAlreadySigned);
         }
@@ -276,22 +638,21 @@ impl<S: Storage> Oracle<S> {
             EventDescriptor::DigitDecompositionEvent(desc) => desc,
             _ => return Err(Error::Internal),
         };
-        if descriptor.base != 2 {
+        if descriptor.base < 2 {
             return Err(Error::Internal);
         }
-        let max_value = (descriptor.base as i64).pow(descriptor.nb_digits as u32) - 1;
-        let min_value = if descriptor.is_signed { -max_value } else { 0 };
+        let (min_value, max_value) = numeric::numeric_range(descriptor);
         if outcome < min_value || outcome > max_value {
             return Err(Error::InvalidOutcome);
         }
 
-        let digits = format!(
-            "{:0width$b}",
-            outcome.abs(),
-            width = descriptor.nb_digits as usize
+        let digits = decompose_digits(
+            outcome.unsigned_abs(),
+            descriptor.base,
+            descriptor.nb_digits,
         )
-        .chars()
-        .map(|char| char.to_string())
+        .into_iter()
+        .map(|digit| digit.to_string())
         .collect::<Vec<_>>();
 
         let outcomes = if descriptor.is_signed {
@@ -309,53 +670,120 @@ impl<S: Storage> Oracle<S> {
         if data.indexes.len() != outcomes.len() {
             return Err(Error::Internal);
         }
+        self.signer_for(&data.announcement.oracle_public_key)?;
 
-        let nonce_keys = data.indexes.iter().map(|i| self.get_nonce_key(*i));
+        self.commit_sign_intent(&event_id, outcomes.clone()).await?;
 
-        let mut sigs: Vec<(String, Signature)> = vec![];
-
-        let signatures = outcomes
+        let signatures = self.sign_outcomes(&data, &outcomes).await?;
+        let sigs = outcomes
             .iter()
-            .zip(nonce_keys)
-            .enumerate()
-            .map(|(idx, (outcome, nonce_key))| {
-                let hash = sha256::Hash::hash(outcome.as_bytes());
-                let msg = Message::from_digest(hash.to_byte_array());
-                let sig = dlc::secp_utils::schnorrsig_sign_with_nonce(
-                    &self.secp,
-                    &msg,
-                    &self.key_pair,
-                    &nonce_key.secret_bytes(),
-                );
-                // verify our nonce is the same as the one in the announcement
-                debug_assert!(
-                    sig.encode()[..32]
-                        == data.announcement.oracle_event.oracle_nonces[idx].serialize()
-                );
-                // verify our signature
-                if self
-                    .secp
-                    .verify_schnorr(&sig, &msg, &self.key_pair.x_only_public_key().0)
-                    .is_err()
-                {
-                    return Err(Error::Internal);
-                };
-                sigs.push((outcome.clone(), sig));
-                Ok(sig)
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+            .cloned()
+            .zip(signatures.iter().copied())
+            .collect::<Vec<_>>();
 
         self.storage.save_signatures(event_id, sigs).await?;
 
         let attestation = OracleAttestation {
             event_id: data.announcement.oracle_event.event_id,
-            oracle_public_key: self.public_key(),
+            oracle_public_key: data.announcement.oracle_public_key,
             signatures,
             outcomes,
         };
 
         Ok(attestation)
     }
+
+    /// Moves the event to the next lifecycle state, if the transition is allowed.
+    async fn transition(
+        &self,
+        data: &OracleEventData,
+        next: EventState,
+    ) -> Result<OracleEventData, Error> {
+        if !data.state.can_transition_to(next) {
+            return Err(Error::InvalidStateTransition);
+        }
+        self.storage
+            .set_event_state(data.event_id.clone(), next)
+            .await
+    }
+
+    /// Records that the announcement of the event was broadcast.
+    pub async fn mark_announced(&self, event_id: String) -> Result<OracleEventData, Error> {
+        let Some(data) = self.storage.get_event(event_id).await? else {
+            return Err(Error::NotFound);
+        };
+        if data.state == EventState::Announced {
+            return Ok(data);
+        }
+        self.transition(&data, EventState::Announced).await
+    }
+
+    /// Cancels an event that can not be resolved, no outcome can be signed for
+    /// it afterwards. If it is an enum event announced with the
+    /// [`CANCELLED_OUTCOME`] outcome, that outcome is attested to so that
+    /// counterparties can settle, and the attestation is returned.
+    ///
+    /// Cancelling an already cancelled event retries the attestation if needed.
+    pub async fn cancel_event(&self, event_id: String) -> Result<Option<OracleAttestation>, Error> {
+        let Some(mut data) = self.storage.get_event(event_id.clone()).await? else {
+            return Err(Error::NotFound);
+        };
+
+        if data.state != EventState::Cancelled {
+            if data.state == EventState::Signed || !data.signatures.is_empty() {
+                return Err(Error::EventAlreadySigned);
+            }
+            // blocks any signer that has not committed to an outcome yet
+            self.commit_sign_intent(&event_id, vec![CANCELLED_OUTCOME.to_string()])
+                .await?;
+            data = self.transition(&data, EventState::Cancelled).await?;
+        }
+
+        let has_cancelled_outcome = match &data.announcement.oracle_event.event_descriptor {
+            EventDescriptor::EnumEvent(desc) => {
+                desc.outcomes.iter().any(|o| o == CANCELLED_OUTCOME)
+            }
+            EventDescriptor::DigitDecompositionEvent(_) => false,
+        };
+        if !has_cancelled_outcome {
+            return Ok(None);
+        }
+
+        if !data.signatures.is_empty() {
+            return Ok(Some(OracleAttestation {
+                event_id: data.event_id,
+                oracle_public_key: data.announcement.oracle_public_key,
+                signatures: data.signatures.iter().map(|(_, sig)| *sig).collect(),
+                outcomes: data.signatures.into_iter().map(|(o, _)| o).collect(),
+            }));
+        }
+
+        let attestation = self
+            .attest_enum_outcome(data, CANCELLED_OUTCOME.to_string())
+            .await?;
+        Ok(Some(attestation))
+    }
+}
+
+/// Checks the event is in a state where an outcome can be signed
+fn check_signable(data: &OracleEventData) -> Result<(), Error> {
+    match data.state {
+        EventState::Cancelled => Err(Error::EventCancelled),
+        EventState::Signed => Err(Error::EventAlreadySigned),
+        EventState::Created | EventState::Announced => Ok(()),
+    }
+}
+
+/// Decomposes `value` into `nb_digits` digits of the given `base`,
+/// most significant digit first.
+fn decompose_digits(mut value: u64, base: u16, nb_digits: u16) -> Vec<u64> {
+    let base = base as u64;
+    let mut digits = vec![0; nb_digits as usize];
+    for digit in digits.iter_mut().rev() {
+        *digit = value % base;
+        value /= base;
+    }
+    digits
 }
 
 pub fn derive_signing_key(secp: &Secp256k1<All>, xpriv: Xpriv) -> Result<SecretKey, Error> {
@@ -372,8 +800,11 @@ pub fn derive_signing_key(secp: &Secp256k1<All>, xpriv: Xpriv) -> Result<SecretK
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::policy::ManualClock;
     use crate::storage::MemoryStorage;
+    use crate::storage::{EventStatus, OVERDUE_AFTER};
     use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
 
     fn create_oracle() -> Oracle<MemoryStorage> {
         let mut seed: [u8; 64] = [0; 64];
@@ -452,6 +883,7 @@ mod test {
         let ann = oracle
             .create_numeric_event(
                 event_id.clone(),
+                2,
                 num_digits,
                 false,
                 0,
@@ -487,6 +919,7 @@ mod test {
         let ann = oracle
             .create_numeric_event(
                 event_id.clone(),
+                2,
                 num_digits,
                 false,
                 0,
@@ -539,6 +972,7 @@ mod test {
         let ann = oracle
             .create_numeric_event(
                 event_id.clone(),
+                2,
                 num_digits,
                 true,
                 0,
@@ -574,6 +1008,7 @@ mod test {
         let ann = oracle
             .create_numeric_event(
                 event_id.clone(),
+                2,
                 num_digits,
                 true,
                 0,
@@ -625,6 +1060,7 @@ mod test {
         let ann = oracle
             .create_numeric_event(
                 event_id.clone(),
+                2,
                 num_digits,
                 true,
                 0,
@@ -664,4 +1100,706 @@ mod test {
 
         println!("{}", hex::encode(attestation.encode()));
     }
+
+    #[tokio::test]
+    async fn test_sign_base_10_numeric_event() {
+        let oracle = create_oracle();
+
+        let event_id = "test_base_10_numeric".to_string();
+        let num_digits = 6;
+
+        let event_maturity_epoch = 100;
+        let ann = oracle
+            .create_numeric_event(
+                event_id.clone(),
+                10,
+                num_digits,
+                true,
+                2,
+                "usd".into(),
+                event_maturity_epoch,
+            )
+            .await
+            .unwrap();
+
+        assert!(ann.validate(&oracle.secp).is_ok());
+        assert_eq!(
+            ann.oracle_event.event_descriptor,
+            EventDescriptor::DigitDecompositionEvent(DigitDecompositionEventDescriptor {
+                base: 10,
+                is_signed: true,
+                unit: "usd".into(),
+                precision: 2,
+                nb_digits: 6,
+            })
+        );
+
+        let res = oracle.sign_numeric_event(event_id.clone(), 1_000_000).await;
+        assert!(res.is_err());
+        let attestation = oracle
+            .sign_numeric_event(event_id.clone(), -98_765)
+            .await
+            .unwrap();
+        assert_eq!(
+            attestation.outcomes,
+            ["-", "0", "9", "8", "7", "6", "5"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(attestation.signatures.len(), 6 + 1);
+
+        for i in 0..attestation.signatures.len() {
+            let sig = attestation.signatures[i];
+
+            // check first 32 bytes of signature is expected nonce
+            let expected_nonce = ann.oracle_event.oracle_nonces[i].serialize();
+            let bytes = sig.encode();
+            let (rx, _sig) = bytes.split_at(32);
+
+            assert_eq!(rx, expected_nonce)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_base_16_numeric_event() {
+        let oracle = create_oracle();
+
+        let event_id = "test_base_16_numeric".to_string();
+        let num_digits = 4;
+
+        let event_maturity_epoch = 100;
+        let ann = oracle
+            .create_numeric_event(
+                event_id.clone(),
+                16,
+                num_digits,
+                false,
+                0,
+                "m/s".into(),
+                event_maturity_epoch,
+            )
+            .await
+            .unwrap();
+
+        let res = oracle.sign_numeric_event(event_id.clone(), 0x10000).await;
+        assert!(res.is_err());
+        let attestation = oracle
+            .sign_numeric_event(event_id.clone(), 0xf0a5)
+            .await
+            .unwrap();
+        assert_eq!(
+            attestation.outcomes,
+            ["15", "0", "10", "5"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(attestation.signatures.len(), 4);
+
+        for i in 0..attestation.signatures.len() {
+            let sig = attestation.signatures[i];
+
+            // check first 32 bytes of signature is expected nonce
+            let expected_nonce = ann.oracle_event.oracle_nonces[i].serialize();
+            let bytes = sig.encode();
+            let (rx, _sig) = bytes.split_at(32);
+
+            assert_eq!(rx, expected_nonce)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_decimal_numeric_event() {
+        let oracle = create_oracle();
+        let outcomes =
+            |outcomes: &[&str]| outcomes.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        for event_id in ["cents", "clamped"] {
+            oracle
+                .create_numeric_event(event_id.into(), 10, 4, true, -2, "usd".into(), 100)
+                .await
+                .unwrap();
+        }
+        oracle
+            .create_enum_event("enum".into(), vec!["a".into()], 100)
+            .await
+            .unwrap();
+
+        let value = "12.345".parse::<Decimal>().unwrap();
+        let attestation = oracle
+            .sign_numeric_event_decimal("cents".into(), value, Rounding::HalfEven, false)
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, outcomes(&["+", "1", "2", "3", "4"]));
+
+        let spike = "1000".parse::<Decimal>().unwrap();
+        let res = oracle
+            .sign_numeric_event_decimal("clamped".into(), spike, Rounding::HalfEven, false)
+            .await;
+        assert!(matches!(res, Err(Error::InvalidOutcome)));
+        let attestation = oracle
+            .sign_numeric_event_decimal("clamped".into(), spike, Rounding::HalfEven, true)
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, outcomes(&["+", "9", "9", "9", "9"]));
+
+        let res = oracle
+            .sign_numeric_event_decimal("enum".into(), value, Rounding::HalfEven, true)
+            .await;
+        assert!(matches!(res, Err(Error::InvalidOutcome)));
+        let res = oracle
+            .sign_numeric_event_decimal("missing".into(), value, Rounding::HalfEven, true)
+            .await;
+        assert!(matches!(res, Err(Error::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_create_numeric_event_invalid_base() {
+        let oracle = create_oracle();
+
+        let res = oracle
+            .create_numeric_event("test".to_string(), 1, 8, false, 0, "m/s".into(), 100)
+            .await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+    }
+
+    #[tokio::test]
+    async fn test_create_enum_event_no_outcomes() {
+        let oracle = create_oracle();
+
+        let res = oracle
+            .create_enum_event("test".to_string(), vec![], 100)
+            .await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+        assert_eq!(oracle.storage.get_nonce_counter().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_event_id() {
+        let oracle = create_oracle();
+
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let res = oracle
+            .create_enum_event("test".to_string(), vec!["c".into()], 200)
+            .await;
+        assert!(matches!(res, Err(Error::EventAlreadyExists)));
+        let res = oracle
+            .create_numeric_event("test".to_string(), 2, 8, false, 0, "m/s".into(), 100)
+            .await;
+        assert!(matches!(res, Err(Error::EventAlreadyExists)));
+
+        // the original event is kept and no nonces were burned
+        let data = oracle
+            .storage
+            .get_event("test".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.announcement, ann);
+        assert_eq!(
+            oracle.storage.get_next_nonce_indexes(1).await.unwrap(),
+            vec![1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_derived_nonces() {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let oracle = Oracle::from_xpriv(MemoryStorage::default(), xpriv)
+            .unwrap()
+            .with_nonce_derivation(NonceDerivation::EventDerived);
+
+        let event_id = "test_derived".to_string();
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        let ann = oracle
+            .create_enum_event(event_id.clone(), outcomes.clone(), 100)
+            .await
+            .unwrap();
+
+        // the same event id can never be announced twice
+        let res = oracle
+            .create_enum_event(event_id.clone(), outcomes.clone(), 100)
+            .await;
+        assert!(matches!(res, Err(Error::EventAlreadyExists)));
+
+        // an oracle that lost its events can announce the id again, with
+        // other contents it gets other nonces
+        let restored = || {
+            Oracle::from_xpriv(MemoryStorage::default(), xpriv)
+                .unwrap()
+                .with_nonce_derivation(NonceDerivation::EventDerived)
+        };
+        let other = restored()
+            .create_enum_event("other".to_string(), outcomes.clone(), 100)
+            .await
+            .unwrap();
+        let later = restored()
+            .create_enum_event(event_id.clone(), outcomes.clone(), 101)
+            .await
+            .unwrap();
+        let fewer = restored()
+            .create_enum_event(event_id.clone(), vec!["a".to_string()], 100)
+            .await
+            .unwrap();
+        let nonces = [&ann, &other, &later, &fewer].map(|a| a.oracle_event.oracle_nonces[0]);
+        for (i, nonce) in nonces.iter().enumerate() {
+            assert!(!nonces[..i].contains(nonce));
+        }
+        // and with the same contents the very same announcement
+        let same = restored()
+            .create_enum_event(event_id.clone(), outcomes, 100)
+            .await
+            .unwrap();
+        assert_eq!(same, ann);
+
+        let attestation = oracle
+            .sign_enum_event(event_id, "a".to_string())
+            .await
+            .unwrap();
+        let bytes = attestation.signatures[0].encode();
+        let (rx, _sig) = bytes.split_at(32);
+        assert_eq!(rx, ann.oracle_event.oracle_nonces[0].serialize());
+    }
+
+    #[tokio::test]
+    async fn test_sign_event_derived_numeric_event() {
+        let oracle = create_oracle().with_nonce_derivation(NonceDerivation::EventDerived);
+
+        let event_id = "test_derived_numeric".to_string();
+        let ann = oracle
+            .create_numeric_event(event_id.clone(), 2, 8, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+
+        let nonces = &ann.oracle_event.oracle_nonces;
+        for (i, nonce) in nonces.iter().enumerate() {
+            assert!(!nonces[..i].contains(nonce));
+        }
+
+        let attestation = oracle.sign_numeric_event(event_id, -0x55).await.unwrap();
+        for (sig, nonce) in attestation.signatures.iter().zip(nonces) {
+            let bytes = sig.encode();
+            let (rx, _sig) = bytes.split_at(32);
+            assert_eq!(rx, nonce.serialize());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_after_changing_nonce_derivation() {
+        let oracle = create_oracle();
+
+        let event_id = "test_indexed".to_string();
+        oracle
+            .create_enum_event(event_id.clone(), vec!["a".to_string()], 100)
+            .await
+            .unwrap();
+
+        let oracle = oracle.with_nonce_derivation(NonceDerivation::EventDerived);
+        let attestation = oracle
+            .sign_enum_event(event_id, "a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec!["a".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_tagged_attestation_scheme() {
+        let oracle = create_oracle();
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        let legacy = oracle
+            .create_enum_event("legacy".to_string(), outcomes.clone(), 100)
+            .await
+            .unwrap();
+
+        let oracle = oracle.with_attestation_scheme(AttestationScheme::Tagged);
+        let tagged = oracle
+            .create_enum_event("tagged".to_string(), outcomes, 100)
+            .await
+            .unwrap();
+        // the tagged announcement is not a legacy one
+        assert!(tagged.validate(&oracle.secp).is_err());
+        let data = oracle.storage.get_event("tagged".into()).await.unwrap();
+        assert_eq!(data.unwrap().attestation_scheme, AttestationScheme::Tagged);
+
+        let attestation = oracle
+            .sign_enum_event("tagged".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        verify::verify_attestation(&oracle.secp, &tagged, &attestation).unwrap();
+        let msg = signer::outcome_message("b", AttestationScheme::Legacy);
+        assert!(oracle
+            .secp
+            .verify_schnorr(&attestation.signatures[0], &msg, &oracle.public_key())
+            .is_err());
+
+        // the legacy event is still signed with the legacy scheme
+        let attestation = oracle
+            .sign_enum_event("legacy".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        assert!(legacy.validate(&oracle.secp).is_ok());
+        verify::verify_attestation(&oracle.secp, &legacy, &attestation).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_event_lifecycle() {
+        let oracle = create_oracle();
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let data = oracle
+            .storage
+            .get_event("test".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.state, EventState::Created);
+        assert_eq!(data.status(99), EventStatus::Pending);
+        assert_eq!(data.status(100), EventStatus::Matured);
+        assert_eq!(data.status(100 + OVERDUE_AFTER), EventStatus::Overdue);
+
+        let data = oracle.mark_announced("test".to_string()).await.unwrap();
+        assert_eq!(data.state, EventState::Announced);
+        // announcing again is a no-op
+        let data = oracle.mark_announced("test".to_string()).await.unwrap();
+        assert_eq!(data.state, EventState::Announced);
+        assert_eq!(data.announcement, ann);
+
+        oracle
+            .sign_enum_event("test".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        let data = oracle
+            .storage
+            .get_event("test".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.state, EventState::Signed);
+        assert_eq!(data.status(100 + OVERDUE_AFTER), EventStatus::Signed);
+
+        let res = oracle.mark_announced("test".to_string()).await;
+        assert!(matches!(res, Err(Error::InvalidStateTransition)));
+        let res = oracle.cancel_event("test".to_string()).await;
+        assert!(matches!(res, Err(Error::EventAlreadySigned)));
+    }
+
+    #[tokio::test]
+    async fn test_signing_policy() {
+        let clock = ManualClock::new(50);
+        let oracle = create_oracle()
+            .with_signing_policy(SigningPolicy::RequireOverride)
+            .with_clock(clock.clone());
+        oracle
+            .create_enum_event("enum".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let res = oracle
+            .sign_enum_event("enum".to_string(), "a".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::MaturityOverrideRequired)));
+        let attestation = oracle
+            .sign_enum_event_early("enum".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec!["a".to_string()]);
+
+        let oracle = oracle.with_signing_policy(SigningPolicy::AfterMaturity);
+        oracle
+            .create_numeric_event("numeric".to_string(), 2, 8, false, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        let res = oracle
+            .sign_numeric_event_early("numeric".to_string(), 7)
+            .await;
+        assert!(matches!(res, Err(Error::EventNotMatured)));
+
+        // nothing was committed to by the refused attempt
+        clock.set(100);
+        oracle
+            .sign_numeric_event("numeric".to_string(), 8)
+            .await
+            .unwrap();
+
+        // cancelling is not subject to the policy
+        oracle
+            .create_enum_event("cancel".to_string(), vec![CANCELLED_OUTCOME.into()], 200)
+            .await
+            .unwrap();
+        assert!(oracle
+            .cancel_event("cancel".to_string())
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_event_state_transitions() {
+        use EventState::*;
+        assert!(Created.can_transition_to(Announced));
+        assert!(Created.can_transition_to(Signed));
+        assert!(Announced.can_transition_to(Cancelled));
+        assert!(!Announced.can_transition_to(Created));
+        assert!(!Signed.can_transition_to(Cancelled));
+        assert!(!Cancelled.can_transition_to(Signed));
+        assert!(Signed.is_final() && Cancelled.is_final());
+
+        for state in [Created, Announced, Signed, Cancelled] {
+            assert_eq!(state.to_string().parse::<EventState>().unwrap(), state);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_event() {
+        let oracle = create_oracle();
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        oracle
+            .create_enum_event("no-void".to_string(), outcomes.clone(), 100)
+            .await
+            .unwrap();
+
+        let res = oracle.cancel_event("missing".to_string()).await;
+        assert!(matches!(res, Err(Error::NotFound)));
+
+        // without a reserved outcome there is nothing to attest
+        let res = oracle.cancel_event("no-void".to_string()).await.unwrap();
+        assert!(res.is_none());
+        let data = oracle
+            .storage
+            .get_event("no-void".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.state, EventState::Cancelled);
+        assert!(data.signatures.is_empty());
+
+        let res = oracle
+            .sign_enum_event("no-void".to_string(), "a".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::EventCancelled)));
+
+        let outcomes = vec!["a".to_string(), CANCELLED_OUTCOME.to_string()];
+        let ann = oracle
+            .create_enum_event("void".to_string(), outcomes, 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .cancel_event("void".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec![CANCELLED_OUTCOME.to_string()]);
+        assert!(crate::verify::verify_attestation(&oracle.secp, &ann, &attestation).is_ok());
+
+        // cancelling again returns the same attestation
+        let again = oracle
+            .cancel_event("void".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(again, attestation);
+
+        // signed events can't be cancelled
+        oracle
+            .create_numeric_event("signed".to_string(), 2, 8, false, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        oracle
+            .sign_numeric_event("signed".to_string(), 7)
+            .await
+            .unwrap();
+        let res = oracle.cancel_event("signed".to_string()).await;
+        assert!(matches!(res, Err(Error::EventAlreadySigned)));
+    }
+
+    /// Storage that "crashes" after the signatures are produced but before
+    /// they are persisted.
+    #[derive(Clone)]
+    struct CrashingStorage(MemoryStorage);
+
+    impl Storage for CrashingStorage {
+        async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
+            self.0.get_next_nonce_indexes(num).await
+        }
+
+        async fn save_announcement(
+            &self,
+            announcement: OracleAnnouncement,
+            indexes: Vec<u32>,
+            attestation_scheme: AttestationScheme,
+        ) -> Result<String, Error> {
+            self.0
+                .save_announcement(announcement, indexes, attestation_scheme)
+                .await
+        }
+
+        async fn save_sign_intent(
+            &self,
+            event_id: String,
+            outcomes: Vec<String>,
+        ) -> Result<Vec<String>, Error> {
+            self.0.save_sign_intent(event_id, outcomes).await
+        }
+
+        async fn save_signatures(
+            &self,
+            _event_id: String,
+            _sigs: Vec<(String, Signature)>,
+        ) -> Result<OracleEventData, Error> {
+            Err(Error::StorageFailure)
+        }
+
+        async fn set_event_state(
+            &self,
+            event_id: String,
+            state: EventState,
+        ) -> Result<OracleEventData, Error> {
+            self.0.set_event_state(event_id, state).await
+        }
+
+        async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
+            self.0.get_event(event_id).await
+        }
+
+        async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+            self.0.list_events().await
+        }
+
+        async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+            self.0.list_sign_intents().await
+        }
+
+        async fn get_nonce_counter(&self) -> Result<u32, Error> {
+            self.0.get_nonce_counter().await
+        }
+
+        async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+            self.0.set_nonce_counter(next).await
+        }
+
+        async fn restore_event(&self, data: OracleEventData) -> Result<(), Error> {
+            self.0.restore_event(data).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crash_before_saving_signatures() {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let storage = MemoryStorage::default();
+        let crashing = Oracle::from_xpriv(CrashingStorage(storage.clone()), xpriv).unwrap();
+
+        let event_id = "test_crash".to_string();
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        crashing
+            .create_enum_event(event_id.clone(), outcomes, 100)
+            .await
+            .unwrap();
+
+        let res = crashing
+            .sign_enum_event(event_id.clone(), "a".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::StorageFailure)));
+
+        // restart the oracle on the same storage
+        let oracle = Oracle::from_xpriv(storage, xpriv).unwrap();
+        let res = oracle
+            .sign_enum_event(event_id.clone(), "b".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+
+        let attestation = oracle
+            .sign_enum_event(event_id.clone(), "a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec!["a".to_string()]);
+
+        let res = oracle.sign_enum_event(event_id, "a".to_string()).await;
+        assert!(matches!(res, Err(Error::EventAlreadySigned)));
+    }
+
+    #[tokio::test]
+    async fn test_crash_after_sign_intent_numeric() {
+        let oracle = create_oracle();
+
+        let event_id = "test_crash_numeric".to_string();
+        oracle
+            .create_numeric_event(event_id.clone(), 2, 4, false, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+
+        // crash right after the intent for 0b0101 was committed
+        let committed = ["0", "1", "0", "1"].map(|x| x.to_string()).to_vec();
+        oracle
+            .storage
+            .save_sign_intent(event_id.clone(), committed.clone())
+            .await
+            .unwrap();
+
+        let res = oracle.sign_numeric_event(event_id.clone(), 0b1010).await;
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+
+        let attestation = oracle.sign_numeric_event(event_id, 0b0101).await.unwrap();
+        assert_eq!(attestation.outcomes, committed);
+    }
+
+    #[tokio::test]
+    async fn test_racing_signers() {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let storage = MemoryStorage::default();
+        let replica_a = Oracle::from_xpriv(storage.clone(), xpriv).unwrap();
+        let replica_b = Oracle::from_xpriv(storage, xpriv).unwrap();
+
+        let event_id = "test_race".to_string();
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        replica_a
+            .create_enum_event(event_id.clone(), outcomes, 100)
+            .await
+            .unwrap();
+
+        let (res_a, res_b) = tokio::join!(
+            replica_a.sign_enum_event(event_id.clone(), "a".to_string()),
+            replica_b.sign_enum_event(event_id.clone(), "b".to_string()),
+        );
+        assert!(res_a.is_ok() ^ res_b.is_ok());
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let secp = Secp256k1::new();
+        let xpriv = Xpriv::new_master(Network::Regtest, &[7; 64]).unwrap();
+        let oracle = Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap();
+
+        let signing_key = derive_signing_key(&secp, xpriv).unwrap();
+        let nonce_xpriv = signer::nonce_master(&signing_key, Network::Bitcoin).unwrap();
+        let secrets = [
+            hex::encode(signing_key.secret_bytes()),
+            hex::encode(nonce_xpriv.private_key.secret_bytes()),
+            hex::encode(nonce_xpriv.chain_code.as_bytes()),
+            nonce_xpriv.to_string(),
+        ];
+
+        let debug = format!("{oracle:?}");
+        assert!(debug.contains(&oracle.public_key().to_string()));
+        for secret in &secrets {
+            assert!(!debug.contains(secret.as_str()));
+        }
+        let debug = format!("{:?}", oracle.signer());
+        for secret in &secrets {
+            assert!(!debug.contains(secret.as_str()));
+        }
+    }
 }
@@ -0,0 +1,284 @@
+//! A document describing the oracle, signed with its key.
+//!
+//! The oracle signs its [`OracleMetadata`] with [`Oracle::sign_metadata`] and
+//! publishes the [`SignedOracleMetadata`], for example at [`WELL_KNOWN_PATH`].
+//! Clients that know the oracle's key check it with
+//! [`SignedOracleMetadata::verify`].
+//!
+//! The signature covers the RFC 8785 (JCS) canonical JSON of the metadata,
+//! see [`OracleMetadata::canonical_json`], so clients in any language can
+//! check it without this crate's serializer.
+
+use crate::error::Error;
+use crate::signer::Signer;
+use crate::storage::Storage;
+use crate::Oracle;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{Message, Secp256k1, Verification};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const METADATA_TAG: &str = "kormir/oracle-metadata";
+
+/// Where the signed metadata is served over HTTP
+pub const WELL_KNOWN_PATH: &str = "/.well-known/dlc-oracle.json";
+
+/// The kinds of events an oracle announces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    Enum,
+    Numeric,
+}
+
+/// Describes the oracle to its clients. Optional fields that are not set are
+/// left out of the JSON rather than written as null.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OracleMetadata {
+    pub public_key: XOnlyPublicKey,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// How to reach the oracle's operator, like an email address or a URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+    pub event_types: Vec<EventType>,
+    /// Version of the scheme new events are signed with, see
+    /// [`AttestationScheme::version`](crate::AttestationScheme::version)
+    pub attestation_scheme_version: u16,
+    /// Nostr relays the oracle publishes its events to
+    #[serde(default)]
+    pub relays: Vec<String>,
+    /// Unix timestamp of the document, a newer one replaces older ones
+    pub created_at: u32,
+    /// Fields this version does not know about, kept so that documents from
+    /// newer oracles still verify
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl OracleMetadata {
+    /// The RFC 8785 (JCS) canonical JSON of the document: no whitespace,
+    /// object keys sorted by their UTF-16 code units and strings escaped like
+    /// `JSON.stringify`. Numbers must be integers of at most 53 bits.
+    pub fn canonical_json(&self) -> Result<Vec<u8>, Error> {
+        let value = serde_json::to_value(self).map_err(|_| Error::Internal)?;
+        let mut json = Vec::new();
+        write_canonical(&value, &mut json)?;
+        Ok(json)
+    }
+
+    /// The message the oracle signs, a BIP 340 style tagged hash of the
+    /// document's [canonical JSON](Self::canonical_json).
+    pub fn message(&self) -> Result<Message, Error> {
+        let json = self.canonical_json()?;
+        let tag = sha256::Hash::hash(METADATA_TAG.as_bytes());
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_byte_array());
+        engine.input(tag.as_byte_array());
+        engine.input(&json);
+        Ok(Message::from_digest(
+            sha256::Hash::from_engine(engine).to_byte_array(),
+        ))
+    }
+}
+
+/// Writes `value` as JCS canonical JSON
+fn write_canonical(value: &Value, json: &mut Vec<u8>) -> Result<(), Error> {
+    const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+    match value {
+        Value::Number(n) => {
+            // floats would need ECMAScript number formatting
+            let safe = n.as_u64().is_some_and(|n| n <= MAX_SAFE_INTEGER)
+                || n.as_i64()
+                    .is_some_and(|n| n.unsigned_abs() <= MAX_SAFE_INTEGER);
+            if !safe {
+                return Err(Error::InvalidArgument);
+            }
+            json.extend_from_slice(n.to_string().as_bytes());
+        }
+        Value::Array(values) => {
+            json.push(b'[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    json.push(b',');
+                }
+                write_canonical(value, json)?;
+            }
+            json.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            json.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(b',');
+                }
+                serde_json::to_writer(&mut *json, key).map_err(|_| Error::Internal)?;
+                json.push(b':');
+                write_canonical(value, json)?;
+            }
+            json.push(b'}');
+        }
+        // serde_json escapes strings the way JCS does
+        Value::Null | Value::Bool(_) | Value::String(_) => {
+            serde_json::to_writer(&mut *json, value).map_err(|_| Error::Internal)?;
+        }
+    }
+    Ok(())
+}
+
+/// The oracle's metadata with its signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedOracleMetadata {
+    pub metadata: OracleMetadata,
+    /// Signature of the oracle's key over [`OracleMetadata::message`]
+    pub signature: Signature,
+}
+
+impl SignedOracleMetadata {
+    /// Checks the metadata was signed by the oracle's `public_key`
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        public_key: &XOnlyPublicKey,
+    ) -> Result<(), Error> {
+        if &self.metadata.public_key != public_key {
+            return Err(Error::InvalidArgument);
+        }
+        secp.verify_schnorr(&self.signature, &self.metadata.message()?, public_key)
+            .map_err(|_| Error::InvalidArgument)
+    }
+}
+
+impl<S: Storage, K: Signer> Oracle<S, K> {
+    /// The oracle's metadata with the given name, to be completed by the
+    /// caller and signed with [`Oracle::sign_metadata`].
+    pub fn metadata(&self, name: String) -> OracleMetadata {
+        OracleMetadata {
+            public_key: self.public_key(),
+            name,
+            description: None,
+            contact: None,
+            event_types: vec![EventType::Enum, EventType::Numeric],
+            attestation_scheme_version: self.attestation_scheme().version(),
+            relays: vec![],
+            created_at: self.clock.now(),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Signs the metadata with the oracle's current key
+    pub async fn sign_metadata(
+        &self,
+        metadata: OracleMetadata,
+    ) -> Result<SignedOracleMetadata, Error> {
+        if metadata.public_key != self.public_key() {
+            return Err(Error::InvalidArgument);
+        }
+        let signature = self.signer.sign_metadata(&metadata).await?;
+        let signed = SignedOracleMetadata {
+            metadata,
+            signature,
+        };
+        // the signer is not trusted to have signed the right thing
+        signed
+            .verify(&self.secp, &self.public_key())
+            .map_err(|_| Error::SignerFailure)?;
+
+        Ok(signed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::Network;
+
+    fn create_oracle(seed: u8) -> Oracle<MemoryStorage> {
+        let xpriv = Xpriv::new_master(Network::Regtest, &[seed; 32]).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_metadata() {
+        let oracle = create_oracle(1);
+        let mut metadata = oracle.metadata("Kormir".to_string());
+        metadata.contact = Some("oracle@example.com".to_string());
+        metadata.relays = vec!["wss://relay.example.com".to_string()];
+        let signed = oracle.sign_metadata(metadata).await.unwrap();
+
+        let secp = Secp256k1::verification_only();
+        let public_key = oracle.public_key();
+        assert!(signed.verify(&secp, &public_key).is_ok());
+
+        // survives a round trip through JSON
+        let json = serde_json::to_string(&signed).unwrap();
+        assert!(!json.contains("description"));
+        let decoded: SignedOracleMetadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, signed);
+        assert!(decoded.verify(&secp, &public_key).is_ok());
+
+        let mut tampered = signed.clone();
+        tampered.metadata.name = "Not Kormir".to_string();
+        assert!(tampered.verify(&secp, &public_key).is_err());
+
+        let other = create_oracle(2).public_key();
+        assert!(signed.verify(&secp, &other).is_err());
+
+        let foreign = create_oracle(2).metadata("Other".to_string());
+        assert!(matches!(
+            oracle.sign_metadata(foreign).await,
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_canonical_json() {
+        let oracle = create_oracle(1);
+        let mut metadata = oracle.metadata("Kormir \u{e9}\n".to_string());
+        metadata.created_at = 1700000000;
+        metadata.relays = vec!["wss://relay.example.com".to_string()];
+        let json = String::from_utf8(metadata.canonical_json().unwrap()).unwrap();
+        let expected = format!(
+            "{{\"attestation_scheme_version\":0,\"created_at\":1700000000,\
+             \"event_types\":[\"enum\",\"numeric\"],\"name\":\"Kormir \u{e9}\\n\",\
+             \"public_key\":\"{}\",\"relays\":[\"wss://relay.example.com\"]}}",
+            oracle.public_key()
+        );
+        assert_eq!(json, expected);
+
+        // fields from a newer version are signed and kept
+        let mut value = serde_json::to_value(&metadata).unwrap();
+        value["website"] = serde_json::json!({"url": "https://example.com", "a": [1, -2]});
+        let newer: OracleMetadata = serde_json::from_value(value).unwrap();
+        let signed = oracle.sign_metadata(newer).await.unwrap();
+        let json = serde_json::to_string(&signed).unwrap();
+        let decoded: SignedOracleMetadata = serde_json::from_str(&json).unwrap();
+        let secp = Secp256k1::verification_only();
+        assert!(decoded.verify(&secp, &oracle.public_key()).is_ok());
+        let canonical = String::from_utf8(decoded.metadata.canonical_json().unwrap()).unwrap();
+        assert!(canonical.ends_with("\"website\":{\"a\":[1,-2],\"url\":\"https://example.com\"}}"));
+
+        // keys are sorted by UTF-16 code units, not by code points
+        metadata.extra = BTreeMap::from([
+            ("\u{ff61}".to_string(), Value::Null),
+            ("\u{1f600}".to_string(), Value::Bool(true)),
+        ]);
+        let json = String::from_utf8(metadata.canonical_json().unwrap()).unwrap();
+        assert!(json.contains("\"\u{1f600}\":true,\"\u{ff61}\":null"));
+
+        // floats have no canonical encoding here
+        metadata.extra = BTreeMap::from([("fee".to_string(), serde_json::json!(0.5))]);
+        assert!(matches!(
+            metadata.canonical_json(),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
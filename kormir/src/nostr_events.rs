@@ -1,14 +1,16 @@
+use crate::metadata::SignedOracleMetadata;
+use crate::rotation::KeyHandover;
 use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
 use lightning::util::ser::Writeable;
-use nostr::event::builder::Error;
-use nostr::{Event, EventBuilder, EventId, Keys, Kind, Tag};
+use nostr::{EventBuilder, EventId, Kind, Metadata, PublicKey, Tag, UnsignedEvent};
 
-/// Creates an Oracle Attestation event for nostr.
+/// Creates an Oracle Announcement event for nostr, to be signed with
+/// [`Oracle::sign_nostr_event`](crate::Oracle::sign_nostr_event).
 pub fn create_announcement_event(
-    keys: &Keys,
+    pubkey: PublicKey,
     announcement: &OracleAnnouncement,
     relays: &[String],
-) -> Result<Event, Error> {
+) -> UnsignedEvent {
     let relays = relays.iter().map(|relay| relay.into()).collect::<Vec<_>>();
     let content = announcement.encode();
     EventBuilder::new(
@@ -16,15 +18,16 @@ pub fn create_announcement_event(
         base64::encode(content),
         [Tag::Relays(relays)],
     )
-    .to_event(keys)
+    .to_unsigned_event(pubkey)
 }
 
-/// Creates an Oracle Attestation event for nostr.
+/// Creates an Oracle Attestation event for nostr, to be signed with
+/// [`Oracle::sign_nostr_event`](crate::Oracle::sign_nostr_event).
 pub fn create_attestation_event(
-    keys: &Keys,
+    pubkey: PublicKey,
     attestation: &OracleAttestation,
     event_id: EventId,
-) -> Result<Event, Error> {
+) -> UnsignedEvent {
     let content = attestation.encode();
     EventBuilder::new(
         Kind::Custom(89),
@@ -35,5 +38,46 @@ pub fn create_attestation_event(
             marker: None,
         }],
     )
-    .to_event(keys)
+    .to_unsigned_event(pubkey)
+}
+
+/// Creates a notice for nostr that the event of the given announcement was cancelled.
+pub fn create_cancellation_event(
+    pubkey: PublicKey,
+    event_id: &str,
+    announcement_event_id: EventId,
+) -> UnsignedEvent {
+    EventBuilder::new(
+        Kind::TextNote,
+        format!("Oracle event {event_id} was cancelled and will not be resolved"),
+        [Tag::Event {
+            event_id: announcement_event_id,
+            relay_url: None,
+            marker: None,
+        }],
+    )
+    .to_unsigned_event(pubkey)
+}
+
+/// Creates an event for nostr announcing the oracle's key handover, to be
+/// signed by the old key so followers of it learn about the new one.
+pub fn create_key_handover_event(pubkey: PublicKey, handover: &KeyHandover) -> UnsignedEvent {
+    let new_public_key =
+        PublicKey::from_slice(&handover.new_public_key.serialize()).expect("just converting types");
+    let content = serde_json::to_string(handover).expect("handover is serializable");
+    EventBuilder::new(Kind::Custom(90), content, [Tag::public_key(new_public_key)])
+        .to_unsigned_event(pubkey)
+}
+
+/// Creates the oracle's nostr profile (kind 0) from its signed metadata, which
+/// is kept whole in the `dlc_oracle` field so clients can verify it.
+pub fn create_metadata_event(pubkey: PublicKey, metadata: &SignedOracleMetadata) -> UnsignedEvent {
+    let document = serde_json::to_value(metadata).expect("metadata is serializable");
+    let mut profile = Metadata::new()
+        .name(metadata.metadata.name.clone())
+        .custom_field("dlc_oracle", document);
+    if let Some(description) = &metadata.metadata.description {
+        profile = profile.about(description.clone());
+    }
+    EventBuilder::metadata(&profile).to_unsigned_event(pubkey)
 }
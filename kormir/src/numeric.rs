@@ -0,0 +1,350 @@
+//! Converting decimal values to the outcomes of numeric events.
+//!
+//! A numeric event's outcome `n` stands for the value `n * 10^precision`,
+//! with the precision of its announcement. [`numeric_outcome`] scales a
+//! [`Decimal`] value by it, rounding as asked, and can saturate values that
+//! do not fit the announced digits instead of failing.
+
+use crate::error::Error;
+use dlc_messages::oracle_msgs::DigitDecompositionEventDescriptor;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Most significant digits a [`Decimal`] can hold
+const MAX_DIGITS: usize = 38;
+
+/// An exact decimal value, `mantissa / 10^scale`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Self { mantissa, scale }
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Self::new(value as i128, 0)
+    }
+}
+
+impl TryFrom<f64> for Decimal {
+    type Error = Error;
+
+    /// Takes the shortest decimal that converts back to the same float
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        if !value.is_finite() {
+            return Err(Error::InvalidArgument);
+        }
+        value.to_string().parse()
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = Error;
+
+    /// Parses values like `42`, `-0.5` or `+1234.5678`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if int.is_empty() && frac.is_empty() {
+            return Err(Error::InvalidArgument);
+        }
+        let digits = int.chars().chain(frac.chars());
+        if !digits.clone().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidArgument);
+        }
+        let significant = digits.skip_while(|c| *c == '0').count();
+        if significant > MAX_DIGITS {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut mantissa = 0i128;
+        for c in int.chars().chain(frac.chars()) {
+            mantissa = mantissa * 10 + c.to_digit(10).expect("checked above") as i128;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        Ok(Self::new(mantissa, frac.len() as u32))
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{int}.{frac}")
+    }
+}
+
+/// How a value between two outcomes is rounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceil,
+    /// Towards zero, dropping the excess digits
+    TowardZero,
+    /// To the nearest outcome, ties away from zero
+    #[default]
+    HalfAwayFromZero,
+    /// To the nearest outcome, ties to the even one
+    HalfEven,
+}
+
+impl Display for Rounding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rounding::Floor => write!(f, "floor"),
+            Rounding::Ceil => write!(f, "ceil"),
+            Rounding::TowardZero => write!(f, "toward_zero"),
+            Rounding::HalfAwayFromZero => write!(f, "half_away_from_zero"),
+            Rounding::HalfEven => write!(f, "half_even"),
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "floor" => Ok(Rounding::Floor),
+            "ceil" => Ok(Rounding::Ceil),
+            "toward_zero" => Ok(Rounding::TowardZero),
+            "half_away_from_zero" => Ok(Rounding::HalfAwayFromZero),
+            "half_even" => Ok(Rounding::HalfEven),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+/// The lowest and highest outcome the descriptor's digits can represent
+pub fn numeric_range(descriptor: &DigitDecompositionEventDescriptor) -> (i64, i64) {
+    let max_value = (descriptor.base as i64)
+        .checked_pow(descriptor.nb_digits as u32)
+        .map(|v| v - 1)
+        .unwrap_or(i64::MAX);
+    let min_value = if descriptor.is_signed { -max_value } else { 0 };
+    (min_value, max_value)
+}
+
+/// Converts `value` to the outcome of an event with the descriptor, rounded
+/// to its precision. Values outside of the descriptor's range are clamped to
+/// the nearest end when `clamp` is set, and fail with
+/// [`Error::InvalidOutcome`] otherwise.
+pub fn numeric_outcome(
+    descriptor: &DigitDecompositionEventDescriptor,
+    value: Decimal,
+    rounding: Rounding,
+    clamp: bool,
+) -> Result<i64, Error> {
+    let (min_value, max_value) = numeric_range(descriptor);
+    // None when the outcome does not even fit an i64, so it is out of range
+    let outcome = scale(value, descriptor.precision, rounding)
+        .and_then(|outcome| i64::try_from(outcome).ok());
+
+    match outcome {
+        Some(outcome) if (min_value..=max_value).contains(&outcome) => Ok(outcome),
+        _ if !clamp => Err(Error::InvalidOutcome),
+        _ if value.mantissa < 0 => Ok(min_value),
+        _ => Ok(max_value),
+    }
+}
+
+/// Scales the value to `value / 10^precision`, rounded
+fn scale(value: Decimal, precision: i32, rounding: Rounding) -> Option<i128> {
+    if value.mantissa == 0 {
+        return Some(0);
+    }
+    // the outcome is mantissa * 10^exponent
+    let exponent = -(precision as i64) - value.scale as i64;
+    if exponent >= 0 {
+        let factor = 10i128.checked_pow(u32::try_from(exponent).ok()?)?;
+        return value.mantissa.checked_mul(factor);
+    }
+
+    let Some(divisor) = u32::try_from(-exponent)
+        .ok()
+        .and_then(|e| 10i128.checked_pow(e))
+    else {
+        // the divisor is larger than any mantissa, so the value is below half
+        return Some(round(0, value.mantissa.signum(), Ordering::Less, rounding));
+    };
+    let quotient = value.mantissa / divisor;
+    let remainder = value.mantissa % divisor;
+    let to_half = (remainder.unsigned_abs() * 2).cmp(&(divisor as u128));
+    Some(round(quotient, remainder.signum(), to_half, rounding))
+}
+
+/// Rounds the truncated `quotient` given the sign of the dropped remainder,
+/// and how the remainder compares to half of an outcome
+fn round(quotient: i128, sign: i128, to_half: Ordering, rounding: Rounding) -> i128 {
+    if sign == 0 {
+        return quotient;
+    }
+    let away = quotient + sign;
+    match (rounding, to_half) {
+        (Rounding::TowardZero, _) => quotient,
+        (Rounding::Floor, _) => quotient.min(away),
+        (Rounding::Ceil, _) => quotient.max(away),
+        (_, Ordering::Less) => quotient,
+        (_, Ordering::Greater) => away,
+        (Rounding::HalfAwayFromZero, Ordering::Equal) => away,
+        (Rounding::HalfEven, Ordering::Equal) if quotient % 2 != 0 => away,
+        (Rounding::HalfEven, Ordering::Equal) => quotient,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn descriptor(
+        nb_digits: u16,
+        is_signed: bool,
+        precision: i32,
+    ) -> DigitDecompositionEventDescriptor {
+        DigitDecompositionEventDescriptor {
+            base: 10,
+            is_signed,
+            unit: "usd".to_string(),
+            precision,
+            nb_digits,
+        }
+    }
+
+    fn outcome(desc: &DigitDecompositionEventDescriptor, value: &str, rounding: Rounding) -> i64 {
+        numeric_outcome(desc, value.parse().unwrap(), rounding, false).unwrap()
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!("42".parse::<Decimal>().unwrap(), Decimal::new(42, 0));
+        assert_eq!("-0.50".parse::<Decimal>().unwrap(), Decimal::new(-50, 2));
+        assert_eq!("+.5".parse::<Decimal>().unwrap(), Decimal::new(5, 1));
+        assert_eq!("7.".parse::<Decimal>().unwrap(), Decimal::new(7, 0));
+        for invalid in ["", "-", ".", "1.2.3", "1e3", "0x10", "- 1", "1_000"] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{invalid}");
+        }
+        let long = "9".repeat(39);
+        assert!(long.parse::<Decimal>().is_err());
+        assert!(format!("0.000{}", "9".repeat(38))
+            .parse::<Decimal>()
+            .is_ok());
+
+        assert_eq!(Decimal::new(-5, 3).to_string(), "-0.005");
+        assert_eq!(Decimal::new(12345, 2).to_string(), "123.45");
+        assert_eq!(Decimal::try_from(0.1).unwrap(), Decimal::new(1, 1));
+        assert!(Decimal::try_from(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_rounding() {
+        let cents = descriptor(8, true, -2);
+        let cases = [
+            // value, floor, ceil, toward zero, half away from zero, half even
+            ("1.234", [123, 124, 123, 123, 123]),
+            ("1.235", [123, 124, 123, 124, 124]),
+            ("1.245", [124, 125, 124, 125, 124]),
+            ("1.2451", [124, 125, 124, 125, 125]),
+            ("-1.235", [-124, -123, -123, -124, -124]),
+            ("-1.245", [-125, -124, -124, -125, -124]),
+            ("-0.001", [-1, 0, 0, 0, 0]),
+            ("1.2", [120, 120, 120, 120, 120]),
+        ];
+        let modes = [
+            Rounding::Floor,
+            Rounding::Ceil,
+            Rounding::TowardZero,
+            Rounding::HalfAwayFromZero,
+            Rounding::HalfEven,
+        ];
+        for (value, expected) in cases {
+            for (rounding, expected) in modes.iter().zip(expected) {
+                assert_eq!(
+                    outcome(&cents, value, *rounding),
+                    expected,
+                    "{value} {rounding}"
+                );
+            }
+        }
+
+        // a positive precision counts in thousands
+        let thousands = descriptor(8, false, 3);
+        assert_eq!(outcome(&thousands, "1500", Rounding::HalfEven), 2);
+        assert_eq!(outcome(&thousands, "2500", Rounding::HalfEven), 2);
+        assert_eq!(outcome(&thousands, "2500.01", Rounding::HalfEven), 3);
+        assert_eq!(outcome(&thousands, "0.4", Rounding::Ceil), 1);
+
+        let huge = descriptor(8, true, 40);
+        assert_eq!(outcome(&huge, "5", Rounding::HalfAwayFromZero), 0);
+        assert_eq!(outcome(&huge, "5", Rounding::Ceil), 1);
+        assert_eq!(outcome(&huge, "-5", Rounding::Floor), -1);
+    }
+
+    #[test]
+    fn test_clamping() {
+        let unsigned = descriptor(3, false, 0);
+        let signed = descriptor(3, true, -1);
+        let value = |s: &str| s.parse::<Decimal>().unwrap();
+
+        assert_eq!(numeric_range(&unsigned), (0, 999));
+        assert_eq!(numeric_range(&signed), (-999, 999));
+
+        for (desc, v, clamped) in [
+            (&unsigned, "1000", 999),
+            (&unsigned, "-1", 0),
+            (&signed, "100", 999),
+            (&signed, "-100", -999),
+        ] {
+            let res = numeric_outcome(desc, value(v), Rounding::HalfEven, false);
+            assert!(matches!(res, Err(Error::InvalidOutcome)), "{v}");
+            let res = numeric_outcome(desc, value(v), Rounding::HalfEven, true);
+            assert_eq!(res.unwrap(), clamped, "{v}");
+        }
+
+        // values too large for any integer saturate as well
+        let huge = value(&"9".repeat(38));
+        assert_eq!(
+            numeric_outcome(&signed, huge, Rounding::Floor, true).unwrap(),
+            999
+        );
+        let tiny = value(&format!("-{}", "9".repeat(38)));
+        assert_eq!(
+            numeric_outcome(&signed, tiny, Rounding::Floor, true).unwrap(),
+            -999
+        );
+        assert!(numeric_outcome(&signed, tiny, Rounding::Floor, false).is_err());
+        let zero = descriptor(3, false, -60);
+        assert_eq!(
+            numeric_outcome(&zero, value("0"), Rounding::Floor, false).unwrap(),
+            0
+        );
+
+        // rounding happens before the range check
+        assert_eq!(
+            numeric_outcome(&signed, value("99.94"), Rounding::HalfEven, false).unwrap(),
+            999
+        );
+        assert!(numeric_outcome(&signed, value("99.95"), Rounding::HalfEven, false).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! Signing outcomes on a host that is never online.
+//!
+//! The online host, which broadcasts the oracle's events, asks for an outcome
+//! to be signed with [`create_sign_request`]. The offline host holds the key
+//! and its own storage of the events, it checks the [`SignRequest`] against
+//! them and signs it with [`Oracle::sign_request`]. The online host verifies
+//! the returned [`SignResponse`] and saves it with [`import_sign_response`],
+//! then broadcasts the attestation. Both are exchanged as JSON files.
+//!
+//! With the `nostr` feature, the offline host also signs the attestation's
+//! nostr event when the request names the announcement's nostr event, so the
+//! online host can broadcast it without the oracle's key.
+
+use crate::error::Error;
+use crate::signer::Signer;
+use crate::storage::Storage;
+use crate::verify::verify_attestation;
+use crate::{check_signable, Oracle, OracleAnnouncement, OracleAttestation};
+use bitcoin::secp256k1::Secp256k1;
+use dlc_messages::oracle_msgs::EventDescriptor;
+use serde::{Deserialize, Serialize};
+
+/// The version of the sign request and response files
+pub const SIGN_REQUEST_VERSION: u8 = 1;
+
+/// The outcome the online host asks to be signed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposedOutcome {
+    Enum(String),
+    Numeric(i64),
+}
+
+/// Asks the offline host to sign an outcome of the event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub version: u8,
+    pub event_id: String,
+    /// The announcement the online host has, it must match the offline one
+    pub announcement: OracleAnnouncement,
+    pub outcome: ProposedOutcome,
+    /// Whether the operator asked to sign before the event's maturity
+    #[serde(default)]
+    pub early: bool,
+    /// The nostr event of the announcement, for the attestation to refer to
+    #[cfg(feature = "nostr")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announcement_event_id: Option<String>,
+}
+
+/// The attestation signed by the offline host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub version: u8,
+    pub event_id: String,
+    pub attestation: OracleAttestation,
+    /// The signed nostr event of the attestation, ready to broadcast
+    #[cfg(feature = "nostr")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation_event: Option<nostr::Event>,
+}
+
+/// Creates the request to sign `outcome` for an event in the online host's
+/// storage. Fails if the event can not be signed with the outcome.
+pub async fn create_sign_request<S: Storage>(
+    storage: &S,
+    event_id: String,
+    outcome: ProposedOutcome,
+    early: bool,
+) -> Result<SignRequest, Error> {
+    let Some(data) = storage.get_event(event_id.clone()).await? else {
+        return Err(Error::NotFound);
+    };
+    check_signable(&data)?;
+    match (&data.announcement.oracle_event.event_descriptor, &outcome) {
+        (EventDescriptor::EnumEvent(desc), ProposedOutcome::Enum(outcome)) => {
+            if !desc.outcomes.contains(outcome) {
+                return Err(Error::InvalidOutcome);
+            }
+        }
+        (EventDescriptor::DigitDecompositionEvent(_), ProposedOutcome::Numeric(_)) => {}
+        _ => return Err(Error::InvalidOutcome),
+    }
+
+    Ok(SignRequest {
+        version: SIGN_REQUEST_VERSION,
+        event_id,
+        announcement: data.announcement,
+        outcome,
+        early,
+        #[cfg(feature = "nostr")]
+        announcement_event_id: data.announcement_event_id,
+    })
+}
+
+/// Verifies the attestation of a response against the event's announcement
+/// and saves its signatures. Importing the same response again returns its
+/// attestation.
+pub async fn import_sign_response<S: Storage>(
+    storage: &S,
+    response: &SignResponse,
+) -> Result<OracleAttestation, Error> {
+    if response.version != SIGN_REQUEST_VERSION {
+        return Err(Error::InvalidArgument);
+    }
+    let Some(data) = storage.get_event(response.event_id.clone()).await? else {
+        return Err(Error::NotFound);
+    };
+    let attestation = &response.attestation;
+    if attestation.event_id != response.event_id {
+        return Err(Error::InvalidArgument);
+    }
+    verify_attestation(
+        &Secp256k1::verification_only(),
+        &data.announcement,
+        attestation,
+    )
+    .map_err(|_| Error::InvalidArgument)?;
+    #[cfg(feature = "nostr")]
+    if let Some(event) = &response.attestation_event {
+        verify_attestation_event(event, attestation, data.announcement_event_id.as_deref())?;
+    }
+
+    let sigs = attestation
+        .outcomes
+        .iter()
+        .cloned()
+        .zip(attestation.signatures.iter().copied())
+        .collect::<Vec<_>>();
+    if !data.signatures.is_empty() {
+        return match data.signatures == sigs {
+            true => Ok(attestation.clone()),
+            false => Err(Error::EventAlreadySigned),
+        };
+    }
+    check_signable(&data)?;
+
+    let committed = storage
+        .save_sign_intent(data.event_id.clone(), attestation.outcomes.clone())
+        .await?;
+    if committed != attestation.outcomes {
+        return Err(Error::ConflictingSignIntent);
+    }
+    storage.save_signatures(data.event_id, sigs).await?;
+
+    Ok(attestation.clone())
+}
+
+impl<S: Storage, K: Signer> Oracle<S, K> {
+    /// Signs the outcome of a request from the online host, after checking
+    /// that its announcement matches the event in the oracle's storage.
+    pub async fn sign_request(&self, request: &SignRequest) -> Result<SignResponse, Error> {
+        if request.version != SIGN_REQUEST_VERSION {
+            return Err(Error::InvalidArgument);
+        }
+        let Some(data) = self.storage.get_event(request.event_id.clone()).await? else {
+            return Err(Error::NotFound);
+        };
+        if data.announcement != request.announcement
+            || request.announcement.oracle_event.event_id != request.event_id
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let event_id = request.event_id.clone();
+        let attestation = match &request.outcome {
+            ProposedOutcome::Enum(outcome) => {
+                self.sign_enum_event_inner(event_id, outcome.clone(), request.early)
+                    .await?
+            }
+            ProposedOutcome::Numeric(outcome) => {
+                self.sign_numeric_event_inner(event_id, *outcome, request.early)
+                    .await?
+            }
+        };
+
+        #[cfg(feature = "nostr")]
+        let attestation_event = match &request.announcement_event_id {
+            Some(id) => {
+                let id = nostr::EventId::from_hex(id).map_err(|_| Error::InvalidArgument)?;
+                let event = crate::nostr_events::create_attestation_event(
+                    self.nostr_public_key(),
+                    &attestation,
+                    id,
+                );
+                Some(self.sign_nostr_event(event).await?)
+            }
+            None => None,
+        };
+
+        Ok(SignResponse {
+            version: SIGN_REQUEST_VERSION,
+            event_id: request.event_id.clone(),
+            attestation,
+            #[cfg(feature = "nostr")]
+            attestation_event,
+        })
+    }
+}
+
+/// Checks that the nostr event is the attestation's, signed by the oracle
+/// and referring to the event's announcement
+#[cfg(feature = "nostr")]
+fn verify_attestation_event(
+    event: &nostr::Event,
+    attestation: &OracleAttestation,
+    announcement_event_id: Option<&str>,
+) -> Result<(), Error> {
+    let announcement_event_id = announcement_event_id
+        .and_then(|id| nostr::EventId::from_hex(id).ok())
+        .ok_or(Error::InvalidArgument)?;
+    let expected = crate::nostr_events::create_attestation_event(
+        nostr::PublicKey::from_slice(&attestation.oracle_public_key.serialize())
+            .map_err(|_| Error::InvalidArgument)?,
+        attestation,
+        announcement_event_id,
+    );
+    if event.verify().is_err()
+        || event.author() != expected.pubkey
+        || event.kind() != expected.kind
+        || event.content() != expected.content
+        || event.tags() != expected.tags
+    {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::policy::SigningPolicy;
+    use crate::storage::{EventState, MemoryStorage};
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::Network;
+
+    fn create_oracle(seed: u8) -> Oracle<MemoryStorage> {
+        let xpriv = Xpriv::new_master(Network::Regtest, &[seed; 32]).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    /// Copies the offline host's event into the online host's storage
+    async fn copy_event(offline: &Oracle<MemoryStorage>, online: &MemoryStorage, event_id: &str) {
+        let data = offline
+            .storage
+            .get_event(event_id.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        online
+            .save_announcement(data.announcement, data.indexes, data.attestation_scheme)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing() {
+        let offline = create_oracle(1);
+        let online = MemoryStorage::default();
+        offline
+            .create_enum_event("enum".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        offline
+            .create_numeric_event("numeric".to_string(), 2, 8, true, 0, "m".into(), 100)
+            .await
+            .unwrap();
+        copy_event(&offline, &online, "enum").await;
+        copy_event(&offline, &online, "numeric").await;
+
+        for (event_id, outcome) in [
+            ("enum", ProposedOutcome::Enum("a".to_string())),
+            ("numeric", ProposedOutcome::Numeric(-42)),
+        ] {
+            let request = create_sign_request(&online, event_id.to_string(), outcome, false)
+                .await
+                .unwrap();
+            let json = serde_json::to_string(&request).unwrap();
+            let request: SignRequest = serde_json::from_str(&json).unwrap();
+
+            let response = offline.sign_request(&request).await.unwrap();
+            let json = serde_json::to_string(&response).unwrap();
+            let response: SignResponse = serde_json::from_str(&json).unwrap();
+
+            let attestation = import_sign_response(&online, &response).await.unwrap();
+            assert_eq!(attestation, response.attestation);
+            let data = online
+                .get_event(event_id.to_string())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(data.state, EventState::Signed);
+            assert_eq!(data.attestation(), Some(attestation.clone()));
+
+            // importing it again is harmless
+            let again = import_sign_response(&online, &response).await.unwrap();
+            assert_eq!(again, attestation);
+
+            // both hosts refuse to sign the event again
+            let res = create_sign_request(
+                &online,
+                event_id.to_string(),
+                request.outcome.clone(),
+                false,
+            )
+            .await;
+            assert!(matches!(res, Err(Error::EventAlreadySigned)));
+            let res = offline.sign_request(&request).await;
+            assert!(matches!(res, Err(Error::EventAlreadySigned)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing_rejects_bad_input() {
+        let offline = create_oracle(1);
+        let other = create_oracle(2);
+        let online = MemoryStorage::default();
+        for oracle in [&offline, &other] {
+            oracle
+                .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+                .await
+                .unwrap();
+        }
+        copy_event(&offline, &online, "test").await;
+
+        let res = create_sign_request(
+            &online,
+            "test".to_string(),
+            ProposedOutcome::Enum("c".to_string()),
+            false,
+        )
+        .await;
+        assert!(matches!(res, Err(Error::InvalidOutcome)));
+        let res = create_sign_request(
+            &online,
+            "test".to_string(),
+            ProposedOutcome::Numeric(1),
+            false,
+        )
+        .await;
+        assert!(matches!(res, Err(Error::InvalidOutcome)));
+
+        let request = create_sign_request(
+            &online,
+            "test".to_string(),
+            ProposedOutcome::Enum("a".to_string()),
+            false,
+        )
+        .await
+        .unwrap();
+
+        // the offline host only signs the announcement it made
+        let res = other.sign_request(&request).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+        let mut bad = request.clone();
+        bad.version = 2;
+        assert!(matches!(
+            offline.sign_request(&bad).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        // the online host only imports attestations of its announcement
+        let forged = SignResponse {
+            version: SIGN_REQUEST_VERSION,
+            event_id: "test".to_string(),
+            attestation: other
+                .sign_enum_event("test".to_string(), "a".to_string())
+                .await
+                .unwrap(),
+            #[cfg(feature = "nostr")]
+            attestation_event: None,
+        };
+        let res = import_sign_response(&online, &forged).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+        let data = online.get_event("test".to_string()).await.unwrap().unwrap();
+        assert!(data.signatures.is_empty());
+
+        // a response for another outcome than the one committed is refused
+        let response = offline.sign_request(&request).await.unwrap();
+        online
+            .save_sign_intent("test".to_string(), vec!["b".to_string()])
+            .await
+            .unwrap();
+        let res = import_sign_response(&online, &response).await;
+        assert!(matches!(res, Err(Error::ConflictingSignIntent)));
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing_maturity() {
+        let offline = create_oracle(1).with_signing_policy(SigningPolicy::RequireOverride);
+        let online = MemoryStorage::default();
+        offline
+            .create_enum_event("test".to_string(), vec!["a".into()], u32::MAX)
+            .await
+            .unwrap();
+        copy_event(&offline, &online, "test").await;
+
+        let outcome = ProposedOutcome::Enum("a".to_string());
+        let request = create_sign_request(&online, "test".to_string(), outcome.clone(), false)
+            .await
+            .unwrap();
+        let res = offline.sign_request(&request).await;
+        assert!(matches!(res, Err(Error::MaturityOverrideRequired)));
+
+        let request = create_sign_request(&online, "test".to_string(), outcome, true)
+            .await
+            .unwrap();
+        let response = offline.sign_request(&request).await.unwrap();
+        import_sign_response(&online, &response).await.unwrap();
+    }
+
+    #[cfg(feature = "nostr")]
+    #[tokio::test]
+    async fn test_offline_signing_nostr_event() {
+        let offline = create_oracle(1);
+        let other = create_oracle(2);
+        let online = MemoryStorage::default();
+        offline
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        let announcement_event_id = nostr::EventId::from_slice(&[1; 32]).unwrap().to_hex();
+        let mut data = offline
+            .storage
+            .get_event("test".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        data.announcement_event_id = Some(announcement_event_id.clone());
+        online.restore_event(data).await.unwrap();
+
+        let outcome = ProposedOutcome::Enum("a".to_string());
+        let request = create_sign_request(&online, "test".to_string(), outcome, false)
+            .await
+            .unwrap();
+        assert_eq!(request.announcement_event_id, Some(announcement_event_id));
+        let response = offline.sign_request(&request).await.unwrap();
+        let event = response.attestation_event.clone().unwrap();
+        assert_eq!(event.author(), offline.nostr_public_key());
+
+        // an event signed by another key is refused
+        let mut forged = response.clone();
+        let unsigned = crate::nostr_events::create_attestation_event(
+            other.nostr_public_key(),
+            &response.attestation,
+            nostr::EventId::all_zeros(),
+        );
+        forged.attestation_event = Some(other.sign_nostr_event(unsigned).await.unwrap());
+        let res = import_sign_response(&online, &forged).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+
+        // so is one that refers to another announcement
+        let unsigned = crate::nostr_events::create_attestation_event(
+            offline.nostr_public_key(),
+            &response.attestation,
+            nostr::EventId::all_zeros(),
+        );
+        forged.attestation_event = Some(offline.sign_nostr_event(unsigned).await.unwrap());
+        let res = import_sign_response(&online, &forged).await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+
+        import_sign_response(&online, &response).await.unwrap();
+    }
+}
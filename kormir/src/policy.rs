@@ -0,0 +1,121 @@
+use crate::error::Error;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// When the oracle is allowed to sign the outcome of an event, relative to
+/// its `event_maturity_epoch`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SigningPolicy {
+    /// Sign whenever asked to
+    #[default]
+    Permissive,
+    /// Refuse to sign before the event matured
+    AfterMaturity,
+    /// Allow signing at most `grace_period` seconds before the event matures
+    GraceWindow { grace_period: u32 },
+    /// Refuse to sign before the event matured, unless the signer explicitly
+    /// overrides it
+    RequireOverride,
+}
+
+impl SigningPolicy {
+    /// Checks whether an event maturing at `maturity` may be signed at `now`.
+    /// `override_maturity` only has an effect with [`SigningPolicy::RequireOverride`].
+    pub fn check(&self, maturity: u32, now: u32, override_maturity: bool) -> Result<(), Error> {
+        match self {
+            SigningPolicy::Permissive => Ok(()),
+            _ if now >= maturity => Ok(()),
+            SigningPolicy::AfterMaturity => Err(Error::EventNotMatured),
+            SigningPolicy::GraceWindow { grace_period } => {
+                if maturity - now <= *grace_period {
+                    Ok(())
+                } else {
+                    Err(Error::EventNotMatured)
+                }
+            }
+            SigningPolicy::RequireOverride if override_maturity => Ok(()),
+            SigningPolicy::RequireOverride => Err(Error::MaturityOverrideRequired),
+        }
+    }
+}
+
+/// Source of the current time, so the signing policy can be tested deterministically
+pub trait Clock: Debug + Send + Sync {
+    /// Seconds since the unix epoch
+    fn now(&self) -> u32;
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u32 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_secs() as u32
+    }
+}
+
+/// A clock that only moves when told to, mostly useful for testing
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Arc<AtomicU32>);
+
+impl ManualClock {
+    pub fn new(now: u32) -> Self {
+        Self(Arc::new(AtomicU32::new(now)))
+    }
+
+    pub fn set(&self, now: u32) {
+        self.0.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u32) {
+        self.0.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u32 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_signing_policy() {
+        let maturity = 1_000;
+
+        for policy in [
+            SigningPolicy::Permissive,
+            SigningPolicy::AfterMaturity,
+            SigningPolicy::GraceWindow { grace_period: 10 },
+            SigningPolicy::RequireOverride,
+        ] {
+            assert!(policy.check(maturity, maturity, false).is_ok());
+            assert!(policy.check(maturity, maturity + 1, false).is_ok());
+        }
+
+        assert!(SigningPolicy::Permissive.check(maturity, 0, false).is_ok());
+
+        let res = SigningPolicy::AfterMaturity.check(maturity, maturity - 1, true);
+        assert!(matches!(res, Err(Error::EventNotMatured)));
+
+        let grace = SigningPolicy::GraceWindow { grace_period: 10 };
+        assert!(grace.check(maturity, maturity - 10, false).is_ok());
+        let res = grace.check(maturity, maturity - 11, true);
+        assert!(matches!(res, Err(Error::EventNotMatured)));
+
+        let res = SigningPolicy::RequireOverride.check(maturity, maturity - 1, false);
+        assert!(matches!(res, Err(Error::MaturityOverrideRequired)));
+        assert!(SigningPolicy::RequireOverride
+            .check(maturity, maturity - 1, true)
+            .is_ok());
+    }
+}
@@ -0,0 +1,520 @@
+//! A [`Signer`] that lives in another process and is reached over a unix
+//! socket, so the oracle itself never holds any secret material.
+//!
+//! The signer process serves a [`LocalSigner`] with [`serve`], the oracle
+//! connects to it with [`RemoteSigner::connect`]. Requests and responses are
+//! newline delimited json.
+//!
+//! The signer does not trust the oracle to never ask for two different
+//! outcomes with the same nonce, which would leak its key. Every nonce it signs
+//! with is recorded in its own [`NonceJournal`] first.
+
+use crate::error::Error;
+use crate::journal::NonceJournal;
+use crate::metadata::OracleMetadata;
+use crate::rotation::KeyHandover;
+use crate::signer::{LocalSigner, Signer};
+use crate::{AttestationScheme, NonceDerivation};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::rand::{thread_rng, Rng};
+use bitcoin::secp256k1::schnorr::Signature;
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleEvent};
+use lightning::io::Cursor;
+use lightning::util::ser::{Readable, Writeable};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum SignerRequest {
+    PublicKey,
+    SignAnnouncement {
+        /// hex encoded [`OracleEvent`]
+        oracle_event: String,
+        #[serde(default)]
+        scheme: AttestationScheme,
+    },
+    NoncePublicKeys {
+        /// hex encoded [`OracleEvent`]
+        oracle_event: String,
+        indexes: Vec<u32>,
+        derivation: NonceDerivation,
+    },
+    SignOutcomes {
+        /// hex encoded [`OracleAnnouncement`]
+        announcement: String,
+        indexes: Vec<u32>,
+        outcomes: Vec<String>,
+        #[serde(default)]
+        scheme: AttestationScheme,
+    },
+    #[cfg(feature = "nostr")]
+    SignNostrEvent {
+        /// json encoded [`nostr::UnsignedEvent`]
+        event: String,
+    },
+    SignKeyHandover {
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    },
+    SignMetadata {
+        metadata: OracleMetadata,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SignerResponse {
+    PublicKey(XOnlyPublicKey),
+    Signature(Signature),
+    Nonces(Vec<XOnlyPublicKey>),
+    Signatures(Vec<Signature>),
+    KeyHandover(KeyHandover),
+    /// json encoded [`nostr::Event`]
+    NostrEvent(String),
+    Error(String),
+}
+
+fn decode<T: Readable>(hex: &str) -> Result<T, Error> {
+    let bytes = hex::decode(hex).map_err(|_| Error::InvalidArgument)?;
+    let mut cursor = Cursor::new(&bytes);
+    T::read(&mut cursor).map_err(|_| Error::InvalidArgument)
+}
+
+/// Talks to a signer process over a unix socket
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    path: PathBuf,
+    public_key: XOnlyPublicKey,
+}
+
+impl RemoteSigner {
+    /// Connects to the signer listening at `path` and fetches its public key
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        match send_request(&path, &SignerRequest::PublicKey).await? {
+            SignerResponse::PublicKey(public_key) => Ok(Self { path, public_key }),
+            _ => Err(Error::SignerFailure),
+        }
+    }
+
+    async fn request(&self, request: &SignerRequest) -> Result<SignerResponse, Error> {
+        send_request(&self.path, request).await
+    }
+}
+
+/// Sends a single request over a new connection
+async fn send_request(path: &Path, request: &SignerRequest) -> Result<SignerResponse, Error> {
+    let mut stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+        log::error!("Could not connect to signer at {}: {e}", path.display());
+        Error::SignerFailure
+    })?;
+    let mut line = serde_json::to_string(request).map_err(|_| Error::Internal)?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|_| Error::SignerFailure)?;
+
+    let mut line = String::new();
+    tokio::io::BufReader::new(stream)
+        .read_line(&mut line)
+        .await
+        .map_err(|_| Error::SignerFailure)?;
+    match serde_json::from_str(&line).map_err(|_| Error::SignerFailure)? {
+        SignerResponse::Error(e) => {
+            log::error!("Signer refused the request: {e}");
+            Err(Error::SignerFailure)
+        }
+        response => Ok(response),
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> XOnlyPublicKey {
+        self.public_key
+    }
+
+    async fn sign_announcement(
+        &self,
+        oracle_event: &OracleEvent,
+        scheme: AttestationScheme,
+    ) -> Result<Signature, Error> {
+        let request = SignerRequest::SignAnnouncement {
+            oracle_event: hex::encode(oracle_event.encode()),
+            scheme,
+        };
+        match self.request(&request).await? {
+            SignerResponse::Signature(sig) => Ok(sig),
+            _ => Err(Error::SignerFailure),
+        }
+    }
+
+    async fn nonce_public_keys(
+        &self,
+        oracle_event: &OracleEvent,
+        indexes: &[u32],
+        derivation: NonceDerivation,
+    ) -> Result<Vec<XOnlyPublicKey>, Error> {
+        let request = SignerRequest::NoncePublicKeys {
+            oracle_event: hex::encode(oracle_event.encode()),
+            indexes: indexes.to_vec(),
+            derivation,
+        };
+        match self.request(&request).await? {
+            SignerResponse::Nonces(nonces) if nonces.len() == indexes.len() => Ok(nonces),
+            _ => Err(Error::SignerFailure),
+        }
+    }
+
+    async fn sign_outcomes(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+        outcomes: &[String],
+        scheme: AttestationScheme,
+    ) -> Result<Vec<Signature>, Error> {
+        let request = SignerRequest::SignOutcomes {
+            announcement: hex::encode(announcement.encode()),
+            indexes: indexes.to_vec(),
+            outcomes: outcomes.to_vec(),
+            scheme,
+        };
+        match self.request(&request).await? {
+            SignerResponse::Signatures(sigs) if sigs.len() == outcomes.len() => Ok(sigs),
+            _ => Err(Error::SignerFailure),
+        }
+    }
+
+    #[cfg(feature = "nostr")]
+    async fn sign_nostr_event(&self, event: nostr::UnsignedEvent) -> Result<nostr::Event, Error> {
+        use nostr::JsonUtil;
+
+        let request = SignerRequest::SignNostrEvent {
+            event: event.as_json(),
+        };
+        let SignerResponse::NostrEvent(json) = self.request(&request).await? else {
+            return Err(Error::SignerFailure);
+        };
+        let event = nostr::Event::from_json(json).map_err(|_| Error::SignerFailure)?;
+        event.verify().map_err(|_| Error::SignerFailure)?;
+        Ok(event)
+    }
+
+    async fn sign_key_handover(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error> {
+        let request = SignerRequest::SignKeyHandover {
+            new_public_key,
+            effective_at,
+        };
+        let SignerResponse::KeyHandover(handover) = self.request(&request).await? else {
+            return Err(Error::SignerFailure);
+        };
+        let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+        if handover.old_public_key != self.public_key
+            || handover.new_public_key != new_public_key
+            || handover.effective_at != effective_at
+            || handover.verify(&secp).is_err()
+        {
+            return Err(Error::SignerFailure);
+        }
+        Ok(handover)
+    }
+
+    async fn sign_metadata(&self, metadata: &OracleMetadata) -> Result<Signature, Error> {
+        let request = SignerRequest::SignMetadata {
+            metadata: metadata.clone(),
+        };
+        match self.request(&request).await? {
+            SignerResponse::Signature(sig) => Ok(sig),
+            _ => Err(Error::SignerFailure),
+        }
+    }
+}
+
+/// Handles a single request with the local signer
+fn handle(
+    signer: &LocalSigner,
+    journal: &Mutex<NonceJournal>,
+    request: SignerRequest,
+) -> Result<SignerResponse, Error> {
+    match request {
+        SignerRequest::PublicKey => Ok(SignerResponse::PublicKey(signer.public_key())),
+        SignerRequest::SignAnnouncement {
+            oracle_event,
+            scheme,
+        } => {
+            let oracle_event: OracleEvent = decode(&oracle_event)?;
+            signer
+                .sign_announcement_sync(&oracle_event, scheme)
+                .map(SignerResponse::Signature)
+        }
+        SignerRequest::NoncePublicKeys {
+            oracle_event,
+            indexes,
+            derivation,
+        } => {
+            let oracle_event: OracleEvent = decode(&oracle_event)?;
+            signer
+                .nonce_public_keys_sync(&oracle_event, &indexes, derivation)
+                .map(SignerResponse::Nonces)
+        }
+        SignerRequest::SignOutcomes {
+            announcement,
+            indexes,
+            outcomes,
+            scheme,
+        } => {
+            let announcement: OracleAnnouncement = decode(&announcement)?;
+            // held until the entries are on disk, so racing requests can not
+            // both sign with a nonce
+            let mut journal = journal.lock().map_err(|_| Error::Internal)?;
+            journal
+                .sign_outcomes(&announcement, &outcomes, scheme, || {
+                    signer.sign_outcomes_sync(&announcement, &indexes, &outcomes, scheme)
+                })
+                .map(SignerResponse::Signatures)
+        }
+        #[cfg(feature = "nostr")]
+        SignerRequest::SignNostrEvent { event } => {
+            use nostr::JsonUtil;
+
+            let event =
+                nostr::UnsignedEvent::from_json(event).map_err(|_| Error::InvalidArgument)?;
+            signer
+                .sign_nostr_event_sync(event)
+                .map(|event| SignerResponse::NostrEvent(event.as_json()))
+        }
+        SignerRequest::SignKeyHandover {
+            new_public_key,
+            effective_at,
+        } => signer
+            .sign_key_handover_sync(new_public_key, effective_at)
+            .map(SignerResponse::KeyHandover),
+        SignerRequest::SignMetadata { metadata } => signer
+            .sign_metadata_sync(&metadata)
+            .map(SignerResponse::Signature),
+    }
+}
+
+fn serve_connection(
+    signer: &LocalSigner,
+    journal: &Mutex<NonceJournal>,
+    stream: UnixStream,
+) -> std::io::Result<()> {
+    let reader = BufReader::new(&stream);
+    for line in reader.lines() {
+        let response = match serde_json::from_str::<SignerRequest>(&line?) {
+            Ok(request) => handle(signer, journal, request)
+                .unwrap_or_else(|e| SignerResponse::Error(e.to_string())),
+            Err(e) => SignerResponse::Error(format!("Invalid request: {e}")),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        (&stream).write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Binds the signer's socket, only accessible to its owner. The socket is
+/// bound in a private directory next to `path` and moved into place once its
+/// permissions are set, so it is never reachable with the umask's.
+pub fn bind(path: impl AsRef<Path>) -> std::io::Result<UnixListener> {
+    let path = path.as_ref();
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = parent.join(format!(".kormir-signer-{}", thread_rng().gen::<u64>()));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let private_path = dir.join("signer.sock");
+    let bound = UnixListener::bind(&private_path).and_then(|listener| {
+        std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&private_path);
+    std::fs::remove_dir(&dir)?;
+    bound
+}
+
+/// Answers the requests of [`RemoteSigner`]s connecting to `listener` with
+/// the given signer, each connection on its own thread. Outcomes are only
+/// signed once they are recorded in the `journal`. Only returns if accepting
+/// a connection fails.
+pub fn serve(
+    listener: UnixListener,
+    signer: LocalSigner,
+    journal: NonceJournal,
+) -> std::io::Result<()> {
+    let signer = Arc::new(signer);
+    let journal = Arc::new(Mutex::new(journal));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let signer = signer.clone();
+        let journal = journal.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = serve_connection(&signer, &journal, stream) {
+                log::warn!("Signer connection failed: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::{MemoryStorage, Storage};
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    /// Starts a signer process, returns it with a client and its directory
+    async fn create_signer() -> (LocalSigner, RemoteSigner, PathBuf) {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        let signer = LocalSigner::from_xpriv(xpriv).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("kormir-signer-{}", hex::encode(&seed[..8])));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("signer.sock");
+        let listener = bind(&path).unwrap();
+        let journal = NonceJournal::open(dir.join("journal")).unwrap();
+        let local = signer.clone();
+        std::thread::spawn(move || serve(listener, local, journal));
+
+        (signer, RemoteSigner::connect(path).await.unwrap(), dir)
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer() {
+        let (local, remote, _) = create_signer().await;
+        assert_eq!(remote.public_key(), local.public_key());
+
+        let oracle = Oracle::from_signer(MemoryStorage::default(), remote);
+        let local_oracle = Oracle::from_signer(MemoryStorage::default(), local);
+
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        let local_ann = local_oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        assert_eq!(ann, local_ann);
+
+        let attestation = oracle
+            .sign_enum_event("test".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        crate::verify::verify_attestation(&oracle.secp, &ann, &attestation).unwrap();
+
+        oracle
+            .create_numeric_event("numeric".to_string(), 10, 3, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .sign_numeric_event("numeric".to_string(), -42)
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec!["-", "0", "4", "2"]);
+
+        let (next, _, _) = create_signer().await;
+        let handover = oracle.hand_over_to(next.public_key(), 1_000).await.unwrap();
+        assert_eq!(handover.old_public_key, oracle.public_key());
+        assert!(handover.verify(&oracle.secp).is_ok());
+
+        let metadata = oracle.metadata("Kormir".to_string());
+        let signed = oracle.sign_metadata(metadata).await.unwrap();
+        assert!(signed.verify(&oracle.secp, &oracle.public_key()).is_ok());
+
+        #[cfg(feature = "nostr")]
+        {
+            let event = crate::nostr_events::create_attestation_event(
+                oracle.nostr_public_key(),
+                &attestation,
+                nostr::EventId::all_zeros(),
+            );
+            let event = oracle.sign_nostr_event(event).await.unwrap();
+            assert!(event.verify().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_rejects_foreign_announcement() {
+        let (_, remote, _) = create_signer().await;
+        let (other, _, _) = create_signer().await;
+
+        let oracle = Oracle::from_signer(MemoryStorage::default(), other);
+        let ann = oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let res = remote
+            .sign_outcomes(&ann, &[0], &["a".to_string()], AttestationScheme::Legacy)
+            .await;
+        assert!(matches!(res, Err(Error::SignerFailure)));
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_journal() {
+        let (local, remote, dir) = create_signer().await;
+        let mode = std::fs::metadata(dir.join("signer.sock"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // the private directory it was bound in is gone
+        let entries = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(entries, 2);
+
+        let oracle = Oracle::from_signer(MemoryStorage::default(), local.clone());
+        let ann = oracle
+            .create_numeric_event("numeric".to_string(), 10, 2, false, 0, "m".into(), 100)
+            .await
+            .unwrap();
+        let indexes = oracle
+            .storage
+            .get_event("numeric".to_string())
+            .await
+            .unwrap()
+            .unwrap()
+            .indexes;
+        let scheme = AttestationScheme::Legacy;
+        let outcomes = vec!["4".to_string(), "2".to_string()];
+
+        let sigs = remote
+            .sign_outcomes(&ann, &indexes, &outcomes, scheme)
+            .await
+            .unwrap();
+        // the same outcomes get the same signatures
+        let again = remote
+            .sign_outcomes(&ann, &indexes, &outcomes, scheme)
+            .await
+            .unwrap();
+        assert_eq!(sigs, again);
+
+        // a nonce is never used for anything else
+        let other = vec!["4".to_string(), "3".to_string()];
+        let res = remote.sign_outcomes(&ann, &indexes, &other, scheme).await;
+        assert!(matches!(res, Err(Error::SignerFailure)));
+        let res = remote
+            .sign_outcomes(&ann, &indexes, &outcomes, AttestationScheme::Tagged)
+            .await;
+        assert!(matches!(res, Err(Error::SignerFailure)));
+    }
+}
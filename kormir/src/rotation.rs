@@ -0,0 +1,201 @@
+//! Moving the oracle to a new signing key.
+//!
+//! The old key signs a [`KeyHandover`] naming the new key and the time from
+//! which it announces events. Clients that know any of the oracle's keys can
+//! follow the chain of handovers to its current key with [`follow_handovers`].
+
+use crate::error::Error;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{Message, Secp256k1, Verification};
+use serde::{Deserialize, Serialize};
+
+const HANDOVER_TAG: &str = "kormir/key-handover";
+
+/// A statement signed by the oracle's old key that the new key takes over
+/// from `effective_at`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyHandover {
+    pub old_public_key: XOnlyPublicKey,
+    pub new_public_key: XOnlyPublicKey,
+    /// Unix timestamp from which events are announced with the new key
+    pub effective_at: u32,
+    /// Signature of the old key over [`KeyHandover::message`]
+    pub signature: Signature,
+}
+
+impl KeyHandover {
+    /// The message the old key signs, a BIP 340 style tagged hash of both
+    /// keys and the effective time.
+    pub fn message(
+        old_public_key: &XOnlyPublicKey,
+        new_public_key: &XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Message {
+        let tag = sha256::Hash::hash(HANDOVER_TAG.as_bytes());
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_byte_array());
+        engine.input(tag.as_byte_array());
+        engine.input(&old_public_key.serialize());
+        engine.input(&new_public_key.serialize());
+        engine.input(&effective_at.to_be_bytes());
+        Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
+    }
+
+    /// Checks the handover was signed by the old key
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>) -> Result<(), Error> {
+        if self.old_public_key == self.new_public_key {
+            return Err(Error::InvalidArgument);
+        }
+        let msg = Self::message(
+            &self.old_public_key,
+            &self.new_public_key,
+            self.effective_at,
+        );
+        secp.verify_schnorr(&self.signature, &msg, &self.old_public_key)
+            .map_err(|_| Error::InvalidArgument)
+    }
+}
+
+/// Follows the handovers starting from `public_key` and returns the oracle's
+/// latest key. Every handover on the way must be valid and take effect after
+/// the previous one.
+pub fn follow_handovers<C: Verification>(
+    secp: &Secp256k1<C>,
+    public_key: XOnlyPublicKey,
+    handovers: &[KeyHandover],
+) -> Result<XOnlyPublicKey, Error> {
+    let mut current = public_key;
+    let mut visited = vec![public_key];
+    let mut effective_at = 0;
+    while let Some(handover) = handovers.iter().find(|h| h.old_public_key == current) {
+        handover.verify(secp)?;
+        if handover.effective_at < effective_at || visited.contains(&handover.new_public_key) {
+            return Err(Error::InvalidArgument);
+        }
+        effective_at = handover.effective_at;
+        current = handover.new_public_key;
+        visited.push(current);
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signer::{LocalSigner, Signer};
+    use crate::storage::{MemoryStorage, Storage};
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    fn create_signer() -> LocalSigner {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        LocalSigner::from_xpriv(xpriv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation() {
+        let secp = Secp256k1::verification_only();
+        let old = create_signer();
+        let new = create_signer();
+
+        let storage = MemoryStorage::default();
+        let oracle = Oracle::from_signer(storage.clone(), old.clone());
+        let pending = oracle
+            .create_enum_event("pending".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let handover = oracle.hand_over_to(new.public_key(), 1_000).await.unwrap();
+        assert_eq!(handover.old_public_key, old.public_key());
+        assert_eq!(handover.new_public_key, new.public_key());
+        assert!(handover.verify(&secp).is_ok());
+
+        // the rotated oracle still attests the events of the old key
+        let oracle = Oracle::from_signer(storage, new.clone()).with_retired_signer(old.clone());
+        assert_eq!(oracle.public_key(), new.public_key());
+        let attestation = oracle
+            .sign_enum_event("pending".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(attestation.oracle_public_key, old.public_key());
+        crate::verify::verify_attestation(&secp, &pending, &attestation).unwrap();
+
+        let ann = oracle
+            .create_enum_event("new".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        assert_eq!(ann.oracle_public_key, new.public_key());
+        let attestation = oracle
+            .sign_enum_event("new".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        crate::verify::verify_attestation(&secp, &ann, &attestation).unwrap();
+
+        // events of unknown keys can not be signed
+        let oracle = Oracle::from_signer(oracle.storage.clone(), new);
+        let other = Oracle::from_signer(MemoryStorage::default(), create_signer());
+        let ann = other
+            .create_enum_event("unknown".to_string(), vec!["a".into()], 100)
+            .await
+            .unwrap();
+        let indexes = other
+            .storage
+            .get_event("unknown".to_string())
+            .await
+            .unwrap()
+            .unwrap()
+            .indexes;
+        oracle
+            .storage
+            .save_announcement(ann, indexes, crate::AttestationScheme::Legacy)
+            .await
+            .unwrap();
+        let res = oracle
+            .sign_enum_event("unknown".to_string(), "a".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+    }
+
+    #[tokio::test]
+    async fn test_follow_handovers() {
+        let secp = Secp256k1::verification_only();
+        let keys = (0..3).map(|_| create_signer()).collect::<Vec<_>>();
+
+        let first = keys[0]
+            .sign_key_handover(keys[1].public_key(), 100)
+            .await
+            .unwrap();
+        let second = keys[1]
+            .sign_key_handover(keys[2].public_key(), 200)
+            .await
+            .unwrap();
+        let handovers = vec![second.clone(), first.clone()];
+
+        let latest = follow_handovers(&secp, keys[0].public_key(), &handovers).unwrap();
+        assert_eq!(latest, keys[2].public_key());
+        let latest = follow_handovers(&secp, keys[1].public_key(), &handovers).unwrap();
+        assert_eq!(latest, keys[2].public_key());
+        let latest = follow_handovers(&secp, keys[2].public_key(), &handovers).unwrap();
+        assert_eq!(latest, keys[2].public_key());
+
+        let mut tampered = second.clone();
+        tampered.effective_at = 300;
+        assert!(tampered.verify(&secp).is_err());
+        let res = follow_handovers(&secp, keys[0].public_key(), &[first.clone(), tampered]);
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+
+        // handovers must not go back in time
+        let early = keys[1]
+            .sign_key_handover(keys[2].public_key(), 50)
+            .await
+            .unwrap();
+        let res = follow_handovers(&secp, keys[0].public_key(), &[first, early]);
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+    }
+}
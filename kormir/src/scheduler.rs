@@ -0,0 +1,325 @@
+use crate::data_source::{DataSource, Outcome};
+use crate::error::Error;
+use crate::signer::{LocalSigner, Signer};
+use crate::storage::{EventState, OracleEventData, Storage};
+use crate::{Oracle, OracleAttestation};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Configuration for the [`Scheduler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerConfig {
+    /// How many times attesting an event may fail before the scheduler gives up on it
+    pub max_attempts: u32,
+    /// Seconds to wait before retrying a failed attestation
+    pub retry_interval: u32,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            retry_interval: 60,
+        }
+    }
+}
+
+/// What happened to a matured event during a [`Scheduler::tick`]
+#[derive(Debug, Clone)]
+pub enum SchedulerReport {
+    /// The event was attested to
+    Attested(OracleAttestation),
+    /// The data source does not know the outcome yet
+    Unresolved { event_id: String },
+    /// Attesting failed, it will be retried after the retry interval
+    Failed {
+        event_id: String,
+        error: Error,
+        attempts: u32,
+    },
+    /// Attesting failed for the last time, the event will not be retried
+    GaveUp {
+        event_id: String,
+        error: Error,
+        attempts: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    count: u32,
+    next_attempt: u32,
+}
+
+/// Watches the oracle's stored events and attests to them with the outcome
+/// from a [`DataSource`] once their `event_maturity_epoch` has passed.
+#[derive(Debug, Clone)]
+pub struct Scheduler<S: Storage, D: DataSource, K: Signer = LocalSigner> {
+    oracle: Oracle<S, K>,
+    data_source: D,
+    config: SchedulerConfig,
+    attempts: Arc<Mutex<HashMap<String, Attempts>>>,
+}
+
+impl<S: Storage, D: DataSource, K: Signer> Scheduler<S, D, K> {
+    pub fn new(oracle: Oracle<S, K>, data_source: D, config: SchedulerConfig) -> Self {
+        Self {
+            oracle,
+            data_source,
+            config,
+            attempts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn oracle(&self) -> &Oracle<S, K> {
+        &self.oracle
+    }
+
+    /// Runs [`Scheduler::tick`] with the current time of the oracle's
+    /// [`Clock`](crate::policy::Clock).
+    pub async fn tick_now(&self) -> Result<Vec<SchedulerReport>, Error> {
+        self.tick(self.oracle.clock.now()).await
+    }
+
+    /// Tries to attest every unsigned, not cancelled event that matured at or before `now`,
+    /// oldest first, and reports what happened to each of them. Events that
+    /// are waiting for a retry or that were given up on are skipped.
+    pub async fn tick(&self, now: u32) -> Result<Vec<SchedulerReport>, Error> {
+        let mut events = self
+            .oracle
+            .storage
+            .list_events()
+            .await?
+            .into_iter()
+            .filter(|e| {
+                matches!(e.state, EventState::Created | EventState::Announced)
+                    && e.announcement.oracle_event.event_maturity_epoch <= now
+            })
+            .collect::<Vec<_>>();
+        events.sort_by_key(|e| e.announcement.oracle_event.event_maturity_epoch);
+
+        let mut reports = Vec::with_capacity(events.len());
+        for event in events {
+            let attempts = self.attempts.lock().unwrap().get(&event.event_id).copied();
+            if let Some(attempts) = attempts {
+                if attempts.count >= self.config.max_attempts || attempts.next_attempt > now {
+                    continue;
+                }
+            }
+
+            let event_id = event.event_id.clone();
+            let report = match self.attest(event).await {
+                Ok(Some(attestation)) => {
+                    self.attempts.lock().unwrap().remove(&event_id);
+                    SchedulerReport::Attested(attestation)
+                }
+                Ok(None) => SchedulerReport::Unresolved { event_id },
+                Err(error) => {
+                    let count = attempts.map(|a| a.count).unwrap_or(0) + 1;
+                    self.attempts.lock().unwrap().insert(
+                        event_id.clone(),
+                        Attempts {
+                            count,
+                            next_attempt: now.saturating_add(self.config.retry_interval),
+                        },
+                    );
+                    log::warn!("Failed to attest event {event_id} (attempt {count}): {error}");
+                    if count >= self.config.max_attempts {
+                        SchedulerReport::GaveUp {
+                            event_id,
+                            error,
+                            attempts: count,
+                        }
+                    } else {
+                        SchedulerReport::Failed {
+                            event_id,
+                            error,
+                            attempts: count,
+                        }
+                    }
+                }
+            };
+            reports.push(report);
+        }
+
+        Ok(reports)
+    }
+
+    async fn attest(&self, event: OracleEventData) -> Result<Option<OracleAttestation>, Error> {
+        let Some(outcome) = self.data_source.resolve(&event).await? else {
+            return Ok(None);
+        };
+
+        let attestation = match outcome {
+            Outcome::Enum(outcome) => self.oracle.sign_enum_event(event.event_id, outcome).await?,
+            Outcome::Numeric(outcome) => {
+                self.oracle
+                    .sign_numeric_event(event.event_id, outcome)
+                    .await?
+            }
+        };
+
+        Ok(Some(attestation))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data_source::{FileDataSource, MockDataSource};
+    use crate::policy::ManualClock;
+    use crate::storage::MemoryStorage;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    fn create_oracle() -> Oracle<MemoryStorage> {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_attests_matured_events() {
+        let oracle = create_oracle();
+        oracle
+            .create_enum_event("early".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        oracle
+            .create_numeric_event("late".to_string(), 2, 8, false, 0, "m/s".into(), 200)
+            .await
+            .unwrap();
+
+        let data_source = MockDataSource::new();
+        data_source.set_outcome("early", Outcome::Enum("b".to_string()));
+        data_source.set_outcome("late", Outcome::Numeric(42));
+        let scheduler = Scheduler::new(oracle, data_source, SchedulerConfig::default());
+
+        assert!(scheduler.tick(99).await.unwrap().is_empty());
+
+        let reports = scheduler.tick(150).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        let SchedulerReport::Attested(attestation) = &reports[0] else {
+            panic!("expected attestation, got {:?}", reports[0]);
+        };
+        assert_eq!(attestation.event_id, "early");
+        assert_eq!(attestation.outcomes, vec!["b".to_string()]);
+
+        let reports = scheduler.tick(250).await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(&reports[0], SchedulerReport::Attested(a) if a.event_id == "late"));
+
+        assert!(scheduler.tick(300).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_uses_oracle_clock() {
+        let clock = ManualClock::new(99);
+        let oracle = create_oracle().with_clock(clock.clone());
+        oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let data_source = MockDataSource::new();
+        data_source.set_outcome("test", Outcome::Enum("a".to_string()));
+        let scheduler = Scheduler::new(oracle, data_source, SchedulerConfig::default());
+
+        assert!(scheduler.tick_now().await.unwrap().is_empty());
+        clock.set(100);
+        let reports = scheduler.tick_now().await.unwrap();
+        assert!(matches!(&reports[..], [SchedulerReport::Attested(a)] if a.event_id == "test"));
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_retries_and_gives_up() {
+        let oracle = create_oracle();
+        oracle
+            .create_enum_event("test".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+
+        let data_source = MockDataSource::new();
+        let config = SchedulerConfig {
+            max_attempts: 2,
+            retry_interval: 10,
+        };
+        let scheduler = Scheduler::new(oracle, data_source.clone(), config);
+
+        // unknown outcomes don't count as attempts
+        let reports = scheduler.tick(100).await.unwrap();
+        assert!(matches!(&reports[0], SchedulerReport::Unresolved { .. }));
+
+        data_source.set_error("test", Error::StorageFailure);
+        let reports = scheduler.tick(100).await.unwrap();
+        assert!(matches!(
+            &reports[0],
+            SchedulerReport::Failed { attempts: 1, .. }
+        ));
+
+        // waiting for the retry interval
+        assert!(scheduler.tick(105).await.unwrap().is_empty());
+
+        // an outcome that isn't in the announcement is a failure too
+        data_source.set_outcome("test", Outcome::Enum("c".to_string()));
+        let reports = scheduler.tick(110).await.unwrap();
+        assert!(matches!(
+            &reports[0],
+            SchedulerReport::GaveUp {
+                error: Error::InvalidOutcome,
+                attempts: 2,
+                ..
+            }
+        ));
+
+        data_source.set_outcome("test", Outcome::Enum("a".to_string()));
+        assert!(scheduler.tick(200).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_data_source() {
+        let oracle = create_oracle();
+        oracle
+            .create_enum_event("enum".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        oracle
+            .create_numeric_event("numeric".to_string(), 10, 4, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "kormir-outcomes-{}.json",
+            thread_rng().gen::<u64>()
+        ));
+        let scheduler = Scheduler::new(
+            oracle,
+            FileDataSource::new(&path),
+            SchedulerConfig::default(),
+        );
+
+        let reports = scheduler.tick(100).await.unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports
+            .iter()
+            .all(|r| matches!(r, SchedulerReport::Unresolved { .. })));
+
+        std::fs::write(&path, r#"{"enum": "a", "numeric": -1234}"#).unwrap();
+        let reports = scheduler.tick(100).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reports.len(), 2);
+        for report in reports {
+            let SchedulerReport::Attested(attestation) = report else {
+                panic!("expected attestation, got {report:?}");
+            };
+            if attestation.event_id == "numeric" {
+                assert_eq!(attestation.outcomes, vec!["-", "1", "2", "3", "4"]);
+            } else {
+                assert_eq!(attestation.outcomes, vec!["a"]);
+            }
+        }
+    }
+}
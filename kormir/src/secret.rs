@@ -0,0 +1,87 @@
+//! Handling of secret material outside of the [`Signer`](crate::signer::Signer)s.
+
+#[cfg(feature = "nostr")]
+use crate::error::Error;
+#[cfg(feature = "nostr")]
+use bitcoin::secp256k1::SecretKey;
+use std::fmt::{Debug, Formatter};
+use zeroize::Zeroize;
+
+/// Holds a secret, keeps it out of debug output and wipes it when dropped
+#[derive(Clone, Default)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+
+    pub fn expose_secret_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(secret: T) -> Self {
+        Self(secret)
+    }
+}
+
+impl<T: Zeroize> Debug for Secret<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Parses a nostr secret key, either bech32 `nsec` or hex encoded
+#[cfg(feature = "nostr")]
+pub fn parse_nsec(nsec: &Secret<String>) -> Result<SecretKey, Error> {
+    let keys = nostr::Keys::parse(nsec.expose_secret()).map_err(|_| Error::InvalidArgument)?;
+    let bytes = Secret::new(
+        keys.secret_key()
+            .map_err(|_| Error::InvalidArgument)?
+            .secret_bytes(),
+    );
+    SecretKey::from_slice(bytes.expose_secret()).map_err(|_| Error::InvalidArgument)
+}
+
+/// Reads a secret from the environment and removes it, so it is not
+/// inherited by child processes. Only call it while the process has a single
+/// thread, before starting an async runtime: other threads may be reading the
+/// environment.
+pub fn take_env_secret(name: &str) -> Option<Secret<String>> {
+    let secret = std::env::var(name).ok().map(Secret::new);
+    std::env::remove_var(name);
+    secret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secret_debug() {
+        let secret = Secret::new("supersecret".to_string());
+        assert_eq!(format!("{secret:?}"), "Secret(..)");
+        assert_eq!(secret.expose_secret(), "supersecret");
+    }
+
+    #[cfg(feature = "nostr")]
+    #[test]
+    fn test_parse_nsec() {
+        let key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let nsec = Secret::new(hex::encode(key.secret_bytes()));
+        assert_eq!(parse_nsec(&nsec).unwrap(), key);
+        assert!(parse_nsec(&Secret::new("nsec1".to_string())).is_err());
+    }
+}
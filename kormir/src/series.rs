@@ -0,0 +1,344 @@
+use crate::error::Error;
+use crate::signer::Signer;
+use crate::storage::{OracleEventData, Storage};
+use crate::{Oracle, OracleAnnouncement};
+use serde::{Deserialize, Serialize};
+
+/// The shape of every event in a series
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeriesDescriptor {
+    Enum {
+        outcomes: Vec<String>,
+    },
+    Numeric {
+        base: u16,
+        num_digits: u16,
+        is_signed: bool,
+        precision: i32,
+        unit: String,
+    },
+}
+
+/// A template for events that recur on a fixed cadence, e.g. an hourly
+/// BTC/USD close price.
+///
+/// Event ids are built from `id_pattern`, where `{name}` is replaced with the
+/// series name, `{index}` with the index of the event in the series and
+/// `{maturity}` with its maturity epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventSeries {
+    pub name: String,
+    pub descriptor: SeriesDescriptor,
+    pub id_pattern: String,
+    /// Maturity epoch of the first event in the series
+    pub start: u32,
+    /// Seconds between the maturities of two consecutive events
+    pub cadence: u32,
+    /// How many seconds before its maturity an event is announced
+    pub lead_time: u32,
+    /// Maturity epoch after which the series has no more events
+    pub end: Option<u32>,
+}
+
+/// A single event of a series
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeriesEvent {
+    pub index: u32,
+    pub event_id: String,
+    pub event_maturity_epoch: u32,
+}
+
+/// A series' events and their stored data, if they were announced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeriesEvents {
+    /// Events that matured at or before the given time
+    pub past: Vec<(SeriesEvent, Option<OracleEventData>)>,
+    /// The next events to mature
+    pub upcoming: Vec<(SeriesEvent, Option<OracleEventData>)>,
+}
+
+impl EventSeries {
+    /// Checks the series can produce valid events
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() || self.cadence == 0 {
+            return Err(Error::InvalidArgument);
+        }
+        // every event in the series needs a unique id
+        if !self.id_pattern.contains("{index}") && !self.id_pattern.contains("{maturity}") {
+            return Err(Error::InvalidArgument);
+        }
+        if self.end.is_some_and(|end| end < self.start) {
+            return Err(Error::InvalidArgument);
+        }
+        match &self.descriptor {
+            SeriesDescriptor::Enum { outcomes } if outcomes.is_empty() => {
+                Err(Error::InvalidArgument)
+            }
+            SeriesDescriptor::Numeric {
+                base, num_digits, ..
+            } if *base < 2 || *num_digits == 0 => Err(Error::InvalidArgument),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the event at the given index, if the series has not ended by then
+    pub fn event(&self, index: u32) -> Option<SeriesEvent> {
+        let maturity = self.start as u64 + index as u64 * self.cadence as u64;
+        let maturity = u32::try_from(maturity).ok()?;
+        if self.end.is_some_and(|end| maturity > end) {
+            return None;
+        }
+
+        let event_id = self
+            .id_pattern
+            .replace("{name}", &self.name)
+            .replace("{index}", &index.to_string())
+            .replace("{maturity}", &maturity.to_string());
+
+        Some(SeriesEvent {
+            index,
+            event_id,
+            event_maturity_epoch: maturity,
+        })
+    }
+
+    /// Index of the first event that matures at or after `time`
+    fn first_index_from(&self, time: u32) -> u32 {
+        if time <= self.start || self.cadence == 0 {
+            return 0;
+        }
+        (time - self.start).div_ceil(self.cadence)
+    }
+
+    /// Events that mature between `from` and `to`, inclusive
+    pub fn events_between(&self, from: u32, to: u32) -> Vec<SeriesEvent> {
+        if self.cadence == 0 {
+            return self
+                .event(0)
+                .filter(|e| e.event_maturity_epoch >= from && e.event_maturity_epoch <= to)
+                .into_iter()
+                .collect();
+        }
+
+        (self.first_index_from(from)..)
+            .map_while(|index| self.event(index))
+            .take_while(|e| e.event_maturity_epoch <= to)
+            .collect()
+    }
+
+    /// Events that should be announced at `now`: their maturity is in the
+    /// future, but no further away than the lead time.
+    pub fn due_events(&self, now: u32) -> Vec<SeriesEvent> {
+        self.events_between(now.saturating_add(1), now.saturating_add(self.lead_time))
+    }
+
+    /// Creates the announcements of every due event that was not announced yet.
+    pub async fn announce_due<S: Storage, K: Signer>(
+        &self,
+        oracle: &Oracle<S, K>,
+        now: u32,
+    ) -> Result<Vec<OracleAnnouncement>, Error> {
+        self.validate()?;
+
+        let mut announcements = vec![];
+        for event in self.due_events(now) {
+            if oracle
+                .storage
+                .get_event(event.event_id.clone())
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+
+            let ann = match &self.descriptor {
+                SeriesDescriptor::Enum { outcomes } => {
+                    oracle
+                        .create_enum_event(
+                            event.event_id,
+                            outcomes.clone(),
+                            event.event_maturity_epoch,
+                        )
+                        .await?
+                }
+                SeriesDescriptor::Numeric {
+                    base,
+                    num_digits,
+                    is_signed,
+                    precision,
+                    unit,
+                } => {
+                    oracle
+                        .create_numeric_event(
+                            event.event_id,
+                            *base,
+                            *num_digits,
+                            *is_signed,
+                            *precision,
+                            unit.clone(),
+                            event.event_maturity_epoch,
+                        )
+                        .await?
+                }
+            };
+            announcements.push(ann);
+        }
+
+        Ok(announcements)
+    }
+
+    /// Lists every event of the series that matured at or before `now`, and the
+    /// next `upcoming` ones, along with their stored data.
+    pub async fn list_events<S: Storage>(
+        &self,
+        storage: &S,
+        now: u32,
+        upcoming: usize,
+    ) -> Result<SeriesEvents, Error> {
+        self.validate()?;
+
+        let mut past = vec![];
+        for event in self.events_between(self.start, now) {
+            let data = storage.get_event(event.event_id.clone()).await?;
+            past.push((event, data));
+        }
+
+        let next = (self.first_index_from(now.saturating_add(1))..)
+            .map_while(|index| self.event(index))
+            .take(upcoming)
+            .collect::<Vec<_>>();
+        let mut upcoming = vec![];
+        for event in next {
+            let data = storage.get_event(event.event_id.clone()).await?;
+            upcoming.push((event, data));
+        }
+
+        Ok(SeriesEvents { past, upcoming })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    const HOUR: u32 = 60 * 60;
+
+    fn create_oracle() -> Oracle<MemoryStorage> {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    fn btc_usd_series() -> EventSeries {
+        EventSeries {
+            name: "btc-usd".to_string(),
+            descriptor: SeriesDescriptor::Numeric {
+                base: 2,
+                num_digits: 20,
+                is_signed: false,
+                precision: 0,
+                unit: "usd".to_string(),
+            },
+            id_pattern: "{name}-{maturity}".to_string(),
+            start: 1_700_000_000,
+            cadence: HOUR,
+            lead_time: 3 * HOUR,
+            end: None,
+        }
+    }
+
+    #[test]
+    fn test_series_events() {
+        let series = btc_usd_series();
+        assert!(series.validate().is_ok());
+
+        let event = series.event(2).unwrap();
+        assert_eq!(event.event_maturity_epoch, series.start + 2 * HOUR);
+        assert_eq!(
+            event.event_id,
+            format!("btc-usd-{}", series.start + 2 * HOUR)
+        );
+
+        let events = series.events_between(series.start + 1, series.start + 3 * HOUR);
+        assert_eq!(
+            events.iter().map(|e| e.index).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let due = series.due_events(series.start + HOUR);
+        assert_eq!(
+            due.iter().map(|e| e.index).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+
+        let mut ended = btc_usd_series();
+        ended.end = Some(series.start + HOUR);
+        assert!(ended.event(2).is_none());
+        assert_eq!(ended.due_events(series.start - HOUR).len(), 2);
+
+        let mut invalid = btc_usd_series();
+        invalid.cadence = 0;
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_announce_series() {
+        let oracle = create_oracle();
+        let series = EventSeries {
+            name: "weather".to_string(),
+            descriptor: SeriesDescriptor::Enum {
+                outcomes: vec!["sun".to_string(), "rain".to_string()],
+            },
+            id_pattern: "{name}-day-{index}".to_string(),
+            start: 1_700_000_000,
+            cadence: 24 * HOUR,
+            lead_time: 24 * HOUR,
+            end: None,
+        };
+
+        let now = series.start - 12 * HOUR;
+        let anns = series.announce_due(&oracle, now).await.unwrap();
+        assert_eq!(anns.len(), 1);
+        assert_eq!(anns[0].oracle_event.event_id, "weather-day-0");
+        assert_eq!(anns[0].oracle_event.event_maturity_epoch, series.start);
+
+        // already announced events are skipped
+        assert!(series.announce_due(&oracle, now).await.unwrap().is_empty());
+
+        let now = series.start + 12 * HOUR;
+        let anns = series.announce_due(&oracle, now).await.unwrap();
+        assert_eq!(anns.len(), 1);
+        assert_eq!(anns[0].oracle_event.event_id, "weather-day-1");
+
+        let events = series.list_events(&oracle.storage, now, 2).await.unwrap();
+        assert_eq!(events.past.len(), 1);
+        assert_eq!(events.past[0].0.event_id, "weather-day-0");
+        assert!(events.past[0].1.is_some());
+        assert_eq!(events.upcoming.len(), 2);
+        assert_eq!(events.upcoming[0].0.event_id, "weather-day-1");
+        assert!(events.upcoming[0].1.is_some());
+        assert_eq!(events.upcoming[1].0.event_id, "weather-day-2");
+        assert!(events.upcoming[1].1.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_announce_numeric_series() {
+        let oracle = create_oracle();
+        let series = btc_usd_series();
+
+        let anns = series.announce_due(&oracle, series.start).await.unwrap();
+        assert_eq!(anns.len(), 3);
+        for (i, ann) in anns.iter().enumerate() {
+            assert_eq!(
+                ann.oracle_event.event_maturity_epoch,
+                series.start + (i as u32 + 1) * HOUR
+            );
+            assert_eq!(ann.oracle_event.oracle_nonces.len(), 20);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use crate::error::Error;
+use crate::metadata::OracleMetadata;
+use crate::rotation::KeyHandover;
+use crate::{AttestationScheme, NonceDerivation};
+use bitcoin::bip32::{ChainCode, ChildNumber, Xpriv};
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{All, Message, Secp256k1, SecretKey};
+use bitcoin::Network;
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleEvent};
+use lightning::util::ser::Writeable;
+use secp256k1_zkp::Keypair;
+use std::fmt::{Debug, Formatter};
+use zeroize::Zeroize;
+
+/// Holds the oracle's secret material and produces every signature the
+/// [`Oracle`](crate::Oracle) needs, so that the keys can live outside of the
+/// oracle's process.
+pub trait Signer {
+    /// The oracle's public key, announcements and attestations are signed with it
+    fn public_key(&self) -> XOnlyPublicKey;
+
+    /// Signs the announcement of the given event with the given scheme
+    async fn sign_announcement(
+        &self,
+        oracle_event: &OracleEvent,
+        scheme: AttestationScheme,
+    ) -> Result<Signature, Error>;
+
+    /// Derives the public nonces for a new event, the nonces of `oracle_event`
+    /// are ignored. With [`NonceDerivation::Indexed`] a nonce is derived for
+    /// each of the `indexes`, with [`NonceDerivation::EventDerived`] one is
+    /// derived from the event for each of its digit positions.
+    async fn nonce_public_keys(
+        &self,
+        oracle_event: &OracleEvent,
+        indexes: &[u32],
+        derivation: NonceDerivation,
+    ) -> Result<Vec<XOnlyPublicKey>, Error>;
+
+    /// Signs each outcome with the matching nonce of the announcement, with
+    /// the scheme the announcement was signed with. The caller is responsible
+    /// for never asking to sign two different sets of outcomes for the same
+    /// announcement.
+    async fn sign_outcomes(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+        outcomes: &[String],
+        scheme: AttestationScheme,
+    ) -> Result<Vec<Signature>, Error>;
+
+    /// Signs a nostr event published by the oracle
+    #[cfg(feature = "nostr")]
+    async fn sign_nostr_event(&self, event: nostr::UnsignedEvent) -> Result<nostr::Event, Error>;
+
+    /// Signs a statement that `new_public_key` replaces this signer's key from
+    /// `effective_at`
+    async fn sign_key_handover(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error>;
+
+    /// Signs the oracle's metadata document
+    async fn sign_metadata(&self, metadata: &OracleMetadata) -> Result<Signature, Error>;
+}
+
+const ANNOUNCEMENT_TAG: &str = "DLC/oracle/announcement/v0";
+const ATTESTATION_TAG: &str = "DLC/oracle/attestation/v0";
+
+/// The sha256 of `data`, or its BIP 340 tagged hash with [`AttestationScheme::Tagged`]
+fn scheme_hash(scheme: AttestationScheme, tag: &str, data: &[u8]) -> Message {
+    let hash = match scheme {
+        AttestationScheme::Legacy => sha256::Hash::hash(data),
+        AttestationScheme::Tagged => {
+            let tag = sha256::Hash::hash(tag.as_bytes());
+            let mut engine = sha256::Hash::engine();
+            engine.input(tag.as_byte_array());
+            engine.input(tag.as_byte_array());
+            engine.input(data);
+            sha256::Hash::from_engine(engine)
+        }
+    };
+    Message::from_digest(hash.to_byte_array())
+}
+
+/// The message an outcome is signed over
+pub(crate) fn outcome_message(outcome: &str, scheme: AttestationScheme) -> Message {
+    scheme_hash(scheme, ATTESTATION_TAG, outcome.as_bytes())
+}
+
+/// The message an announcement is signed over
+pub(crate) fn announcement_message(
+    oracle_event: &OracleEvent,
+    scheme: AttestationScheme,
+) -> Result<Message, Error> {
+    let mut data = Vec::new();
+    oracle_event.write(&mut data).map_err(|_| Error::Internal)?;
+    Ok(scheme_hash(scheme, ANNOUNCEMENT_TAG, &data))
+}
+
+/// The event without its nonces, what [`NonceDerivation::EventDerived`] nonces
+/// are derived from
+pub(crate) fn nonce_commitment(oracle_event: &OracleEvent) -> Result<Vec<u8>, Error> {
+    let event = OracleEvent {
+        oracle_nonces: vec![],
+        ..oracle_event.clone()
+    };
+    let mut data = Vec::new();
+    event.write(&mut data).map_err(|_| Error::Internal)?;
+    Ok(data)
+}
+
+/// The nonce master derived from the signing key, sha256 of it as the seed
+pub(crate) fn nonce_master(signing_key: &SecretKey, network: Network) -> Result<Xpriv, Error> {
+    let xpriv_bytes = sha256::Hash::hash(&signing_key.secret_bytes()).to_byte_array();
+    Xpriv::new_master(network, &xpriv_bytes).map_err(|_| Error::Internal)
+}
+
+/// Signs with keys held in memory, which are wiped when it is dropped
+#[derive(Clone)]
+pub struct LocalSigner {
+    key_pair: Keypair,
+    nonce_xpriv: Xpriv,
+    secp: Secp256k1<All>,
+}
+
+impl Debug for LocalSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalSigner")
+            .field("public_key", &format_args!("{}", self.public_key()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for LocalSigner {
+    fn drop(&mut self) {
+        self.key_pair.non_secure_erase();
+        self.nonce_xpriv.private_key.non_secure_erase();
+        self.nonce_xpriv.chain_code = ChainCode::from([0; 32]);
+    }
+}
+
+impl LocalSigner {
+    pub fn new(signing_key: SecretKey, nonce_xpriv: Xpriv) -> Self {
+        let secp = Secp256k1::new();
+        Self {
+            key_pair: Keypair::from_secret_key(&secp, &signing_key),
+            nonce_xpriv,
+            secp,
+        }
+    }
+
+    /// Uses the signing key derived from the xpriv, see [`crate::derive_signing_key`]
+    pub fn from_xpriv(xpriv: Xpriv) -> Result<Self, Error> {
+        let secp = Secp256k1::new();
+
+        let signing_key = crate::derive_signing_key(&secp, xpriv)?;
+        Self::from_signing_key(signing_key)
+    }
+
+    /// Derives the nonce xpriv from the signing key
+    pub fn from_signing_key(signing_key: SecretKey) -> Result<Self, Error> {
+        let nonce_xpriv = nonce_master(&signing_key, Network::Bitcoin)?;
+        Ok(Self::new(signing_key, nonce_xpriv))
+    }
+
+    /// Returns the keys for the oracle, used for Nostr.
+    #[cfg(feature = "nostr")]
+    pub fn nostr_keys(&self) -> nostr::Keys {
+        let sec = nostr::key::SecretKey::from_slice(&self.key_pair.secret_key().secret_bytes()[..])
+            .expect("just converting types");
+        nostr::Keys::new(sec)
+    }
+
+    pub(crate) fn signing_key(&self) -> SecretKey {
+        self.key_pair.secret_key()
+    }
+
+    pub(crate) fn nonce_xpriv(&self) -> Xpriv {
+        self.nonce_xpriv
+    }
+
+    fn get_nonce_key(&self, index: u32) -> Result<SecretKey, Error> {
+        let child = ChildNumber::from_hardened_idx(index).map_err(|_| Error::InvalidArgument)?;
+        let xpriv = self
+            .nonce_xpriv
+            .derive_priv(&self.secp, &[child])
+            .map_err(|_| Error::Internal)?;
+        Ok(xpriv.private_key)
+    }
+
+    /// Derives the nonce key for the digit at `position` of an event,
+    /// HMAC-SHA256(nonce_xpriv, position || commitment) with the event's
+    /// [`nonce_commitment`]. Announcing an event id again with different
+    /// contents derives different nonces.
+    fn get_event_nonce_key(&self, commitment: &[u8], position: u32) -> Result<SecretKey, Error> {
+        let mut engine =
+            hmac::HmacEngine::<sha256::Hash>::new(&self.nonce_xpriv.private_key.secret_bytes());
+        engine.input(&position.to_be_bytes());
+        engine.input(commitment);
+        let hmac = hmac::Hmac::<sha256::Hash>::from_engine(engine);
+        SecretKey::from_slice(hmac.as_byte_array()).map_err(|_| Error::Internal)
+    }
+
+    pub(crate) fn nonce_public_keys_sync(
+        &self,
+        oracle_event: &OracleEvent,
+        indexes: &[u32],
+        derivation: NonceDerivation,
+    ) -> Result<Vec<XOnlyPublicKey>, Error> {
+        let keys = match derivation {
+            NonceDerivation::Indexed => indexes
+                .iter()
+                .map(|i| self.get_nonce_key(*i))
+                .collect::<Result<Vec<_>, Error>>()?,
+            NonceDerivation::EventDerived => {
+                let commitment = nonce_commitment(oracle_event)?;
+                (0..indexes.len() as u32)
+                    .map(|position| self.get_event_nonce_key(&commitment, position))
+                    .collect::<Result<Vec<_>, Error>>()?
+            }
+        };
+        Ok(keys
+            .into_iter()
+            .map(|mut key| {
+                let public_key = key.x_only_public_key(&self.secp).0;
+                key.non_secure_erase();
+                public_key
+            })
+            .collect())
+    }
+
+    pub(crate) fn sign_announcement_sync(
+        &self,
+        oracle_event: &OracleEvent,
+        scheme: AttestationScheme,
+    ) -> Result<Signature, Error> {
+        oracle_event
+            .validate()
+            .map_err(|_| Error::InvalidArgument)?;
+        let msg = announcement_message(oracle_event, scheme)?;
+        Ok(self.secp.sign_schnorr_no_aux_rand(&msg, &self.key_pair))
+    }
+
+    /// Returns the nonce keys of an announcement, whichever [`NonceDerivation`]
+    /// it was created with, checking each one against its nonces.
+    fn get_announcement_nonce_keys(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+    ) -> Result<Vec<SecretKey>, Error> {
+        let oracle_event = &announcement.oracle_event;
+        if announcement.oracle_public_key != self.public_key()
+            || indexes.len() != oracle_event.oracle_nonces.len()
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let commitment = nonce_commitment(oracle_event)?;
+        indexes
+            .iter()
+            .zip(oracle_event.oracle_nonces.iter())
+            .enumerate()
+            .map(|(position, (index, nonce))| {
+                let mut indexed = self.get_nonce_key(*index)?;
+                if indexed.x_only_public_key(&self.secp).0 == *nonce {
+                    return Ok(indexed);
+                }
+                indexed.non_secure_erase();
+                let mut derived = self.get_event_nonce_key(&commitment, position as u32)?;
+                if derived.x_only_public_key(&self.secp).0 == *nonce {
+                    return Ok(derived);
+                }
+                derived.non_secure_erase();
+                Err(Error::InvalidArgument)
+            })
+            .collect()
+    }
+
+    pub(crate) fn sign_outcomes_sync(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+        outcomes: &[String],
+        scheme: AttestationScheme,
+    ) -> Result<Vec<Signature>, Error> {
+        if outcomes.len() != indexes.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let nonce_keys = self.get_announcement_nonce_keys(announcement, indexes)?;
+
+        // nonce keys are wiped as soon as they were used
+        Ok(outcomes
+            .iter()
+            .zip(nonce_keys)
+            .map(|(outcome, mut nonce_key)| {
+                let mut nonce_bytes = nonce_key.secret_bytes();
+                nonce_key.non_secure_erase();
+                let sig = dlc::secp_utils::schnorrsig_sign_with_nonce(
+                    &self.secp,
+                    &outcome_message(outcome, scheme),
+                    &self.key_pair,
+                    &nonce_bytes,
+                );
+                nonce_bytes.zeroize();
+                sig
+            })
+            .collect())
+    }
+
+    pub(crate) fn sign_key_handover_sync(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error> {
+        let old_public_key = self.public_key();
+        if new_public_key == old_public_key {
+            return Err(Error::InvalidArgument);
+        }
+        let msg = KeyHandover::message(&old_public_key, &new_public_key, effective_at);
+        Ok(KeyHandover {
+            old_public_key,
+            new_public_key,
+            effective_at,
+            signature: self.secp.sign_schnorr_no_aux_rand(&msg, &self.key_pair),
+        })
+    }
+
+    pub(crate) fn sign_metadata_sync(&self, metadata: &OracleMetadata) -> Result<Signature, Error> {
+        if metadata.public_key != self.public_key() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(self
+            .secp
+            .sign_schnorr_no_aux_rand(&metadata.message()?, &self.key_pair))
+    }
+
+    #[cfg(feature = "nostr")]
+    pub(crate) fn sign_nostr_event_sync(
+        &self,
+        event: nostr::UnsignedEvent,
+    ) -> Result<nostr::Event, Error> {
+        let keys = self.nostr_keys();
+        if event.pubkey != keys.public_key() {
+            return Err(Error::InvalidArgument);
+        }
+        event.sign(&keys).map_err(|_| Error::InvalidArgument)
+    }
+}
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> XOnlyPublicKey {
+        self.key_pair.x_only_public_key().0
+    }
+
+    async fn sign_announcement(
+        &self,
+        oracle_event: &OracleEvent,
+        scheme: AttestationScheme,
+    ) -> Result<Signature, Error> {
+        self.sign_announcement_sync(oracle_event, scheme)
+    }
+
+    async fn nonce_public_keys(
+        &self,
+        oracle_event: &OracleEvent,
+        indexes: &[u32],
+        derivation: NonceDerivation,
+    ) -> Result<Vec<XOnlyPublicKey>, Error> {
+        self.nonce_public_keys_sync(oracle_event, indexes, derivation)
+    }
+
+    async fn sign_outcomes(
+        &self,
+        announcement: &OracleAnnouncement,
+        indexes: &[u32],
+        outcomes: &[String],
+        scheme: AttestationScheme,
+    ) -> Result<Vec<Signature>, Error> {
+        self.sign_outcomes_sync(announcement, indexes, outcomes, scheme)
+    }
+
+    async fn sign_key_handover(
+        &self,
+        new_public_key: XOnlyPublicKey,
+        effective_at: u32,
+    ) -> Result<KeyHandover, Error> {
+        self.sign_key_handover_sync(new_public_key, effective_at)
+    }
+
+    async fn sign_metadata(&self, metadata: &OracleMetadata) -> Result<Signature, Error> {
+        self.sign_metadata_sync(metadata)
+    }
+
+    #[cfg(feature = "nostr")]
+    async fn sign_nostr_event(&self, event: nostr::UnsignedEvent) -> Result<nostr::Event, Error> {
+        self.sign_nostr_event_sync(event)
+    }
+}
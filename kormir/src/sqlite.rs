@@ -0,0 +1,903 @@
+use crate::error::Error;
+use crate::rotation::KeyHandover;
+use crate::storage::{EventState, OracleEventData, Storage};
+use crate::{AttestationScheme, OracleAnnouncement, Readable, Signature, Writeable};
+use bitcoin::key::XOnlyPublicKey;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Schema migrations, the database's `user_version` is the number of
+/// migrations that were applied to it.
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS oracle_metadata
+(
+    id     INTEGER PRIMARY KEY CHECK (id = 0),
+    pubkey BLOB    NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS nonce_counter
+(
+    id         INTEGER PRIMARY KEY CHECK (id = 0),
+    next_index INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events
+(
+    event_id              TEXT    PRIMARY KEY,
+    announcement          BLOB    NOT NULL,
+    announcement_event_id TEXT,
+    attestation_event_id  TEXT,
+    created_at            INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS event_nonces
+(
+    idx       INTEGER PRIMARY KEY,
+    event_id  TEXT    NOT NULL REFERENCES events (event_id),
+    position  INTEGER NOT NULL,
+    outcome   TEXT,
+    signature BLOB
+);
+
+CREATE INDEX IF NOT EXISTS event_nonces_event_id_index ON event_nonces (event_id);
+
+CREATE TABLE IF NOT EXISTS sign_intents
+(
+    event_id TEXT PRIMARY KEY REFERENCES events (event_id),
+    outcomes TEXT NOT NULL
+);
+",
+    "
+ALTER TABLE events ADD COLUMN state TEXT NOT NULL DEFAULT 'created';
+UPDATE events SET state = 'announced' WHERE announcement_event_id IS NOT NULL;
+UPDATE events SET state = 'signed'
+WHERE event_id IN (SELECT event_id FROM event_nonces WHERE signature IS NOT NULL);
+",
+    "
+CREATE TABLE IF NOT EXISTS key_handovers
+(
+    old_pubkey   BLOB    PRIMARY KEY,
+    new_pubkey   BLOB    NOT NULL,
+    effective_at INTEGER NOT NULL,
+    signature    BLOB    NOT NULL
+);
+",
+    "
+ALTER TABLE oracle_metadata ADD COLUMN name TEXT NOT NULL DEFAULT 'Kormir';
+",
+    "ALTER TABLE events ADD COLUMN attestation_scheme TEXT NOT NULL DEFAULT 'legacy';",
+];
+
+fn storage_failure(e: impl std::fmt::Display) -> Error {
+    log::error!("SQLite storage failure: {e}");
+    Error::StorageFailure
+}
+
+/// [`Storage`] backed by a single SQLite database file.
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Opens or creates the database at the given path
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let conn = Connection::open(path).map_err(storage_failure)?;
+        Self::from_connection(conn)
+    }
+
+    /// Creates a database that only lives in memory, mostly useful for testing
+    pub fn open_in_memory() -> Result<Self, Error> {
+        let conn = Connection::open_in_memory().map_err(storage_failure)?;
+        Self::from_connection(conn)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self, Error> {
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(storage_failure)?;
+        run_migrations(&mut conn)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// The oracle public key the database was created for, if any
+    pub fn oracle_public_key(&self) -> Result<Option<XOnlyPublicKey>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let pubkey: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT pubkey FROM oracle_metadata WHERE id = 0",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_failure)?;
+
+        pubkey
+            .map(|bytes| XOnlyPublicKey::from_slice(&bytes).map_err(|_| Error::StorageFailure))
+            .transpose()
+    }
+
+    /// Binds the database to the given oracle public key
+    pub fn set_oracle_public_key(&self, pubkey: XOnlyPublicKey) -> Result<(), Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        conn.execute(
+            "INSERT INTO oracle_metadata (id, pubkey) VALUES (0, ?1)
+             ON CONFLICT (id) DO UPDATE SET pubkey = excluded.pubkey",
+            params![pubkey.serialize().to_vec()],
+        )
+        .map_err(storage_failure)?;
+        Ok(())
+    }
+
+    /// The oracle's name, if the database was bound to its key
+    pub fn oracle_name(&self) -> Result<Option<String>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        conn.query_row("SELECT name FROM oracle_metadata WHERE id = 0", [], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(storage_failure)
+    }
+
+    /// Renames the oracle
+    pub fn set_oracle_name(&self, name: &str) -> Result<(), Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        conn.execute(
+            "UPDATE oracle_metadata SET name = ?1 WHERE id = 0",
+            params![name],
+        )
+        .map_err(storage_failure)?;
+        Ok(())
+    }
+
+    /// Records a handover and binds the database to its new key
+    pub fn save_key_handover(&self, handover: &KeyHandover) -> Result<(), Error> {
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn.transaction().map_err(storage_failure)?;
+        tx.execute(
+            "INSERT INTO key_handovers (old_pubkey, new_pubkey, effective_at, signature)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                handover.old_public_key.serialize().to_vec(),
+                handover.new_public_key.serialize().to_vec(),
+                handover.effective_at,
+                handover.signature.serialize().to_vec(),
+            ],
+        )
+        .map_err(storage_failure)?;
+        tx.execute(
+            "UPDATE oracle_metadata SET pubkey = ?1 WHERE id = 0",
+            params![handover.new_public_key.serialize().to_vec()],
+        )
+        .map_err(storage_failure)?;
+        tx.commit().map_err(storage_failure)
+    }
+
+    /// All key handovers of the oracle, oldest first
+    pub fn key_handovers(&self) -> Result<Vec<KeyHandover>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT old_pubkey, new_pubkey, effective_at, signature
+                 FROM key_handovers ORDER BY effective_at",
+            )
+            .map_err(storage_failure)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Vec<u8>>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, u32>(2)?,
+                    row.get::<_, Vec<u8>>(3)?,
+                ))
+            })
+            .map_err(storage_failure)?;
+
+        rows.map(|row| {
+            let (old, new, effective_at, signature) = row.map_err(storage_failure)?;
+            Ok(KeyHandover {
+                old_public_key: XOnlyPublicKey::from_slice(&old)
+                    .map_err(|_| Error::StorageFailure)?,
+                new_public_key: XOnlyPublicKey::from_slice(&new)
+                    .map_err(|_| Error::StorageFailure)?,
+                effective_at,
+                signature: Signature::from_slice(&signature).map_err(|_| Error::StorageFailure)?,
+            })
+        })
+        .collect()
+    }
+
+    #[cfg(feature = "nostr")]
+    pub async fn add_announcement_event_id(
+        &self,
+        event_id: String,
+        nostr_event_id: String,
+    ) -> Result<(), Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let updated = conn
+            .execute(
+                "UPDATE events SET announcement_event_id = ?1 WHERE event_id = ?2",
+                params![nostr_event_id, event_id],
+            )
+            .map_err(storage_failure)?;
+        if updated == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "nostr")]
+    pub async fn add_attestation_event_id(
+        &self,
+        event_id: String,
+        nostr_event_id: String,
+    ) -> Result<(), Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let updated = conn
+            .execute(
+                "UPDATE events SET attestation_event_id = ?1 WHERE event_id = ?2",
+                params![nostr_event_id, event_id],
+            )
+            .map_err(storage_failure)?;
+        if updated == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+}
+
+fn run_migrations(conn: &mut Connection) -> Result<(), Error> {
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Exclusive)
+        .map_err(storage_failure)?;
+    let version: usize = tx
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(storage_failure)?;
+    if version > MIGRATIONS.len() {
+        log::error!("SQLite database version {version} is newer than this version of kormir");
+        return Err(Error::StorageFailure);
+    }
+
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration).map_err(storage_failure)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())
+        .map_err(storage_failure)?;
+    tx.commit().map_err(storage_failure)
+}
+
+/// Announcement, state and nostr event ids of an `events` row
+type EventRow = (Vec<u8>, String, String, Option<String>, Option<String>);
+
+fn read_event(conn: &Connection, event_id: &str) -> Result<Option<OracleEventData>, Error> {
+    let row: Option<EventRow> = conn
+        .query_row(
+            "SELECT announcement, state, attestation_scheme, announcement_event_id,
+                    attestation_event_id
+             FROM events WHERE event_id = ?1",
+            params![event_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(storage_failure)?;
+    #[allow(unused_variables)]
+    let Some((
+        announcement,
+        state,
+        attestation_scheme,
+        announcement_event_id,
+        attestation_event_id,
+    )) = row
+    else {
+        return Ok(None);
+    };
+
+    let state = state.parse().map_err(|_| Error::StorageFailure)?;
+    let attestation_scheme = attestation_scheme
+        .parse()
+        .map_err(|_| Error::StorageFailure)?;
+    let mut cursor = lightning::io::Cursor::new(&announcement);
+    let announcement = OracleAnnouncement::read(&mut cursor).map_err(storage_failure)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT idx, outcome, signature FROM event_nonces
+             WHERE event_id = ?1 ORDER BY position ASC",
+        )
+        .map_err(storage_failure)?;
+    let nonces = stmt
+        .query_map(params![event_id], |row| {
+            let idx: u32 = row.get(0)?;
+            let outcome: Option<String> = row.get(1)?;
+            let signature: Option<Vec<u8>> = row.get(2)?;
+            Ok((idx, outcome, signature))
+        })
+        .map_err(storage_failure)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(storage_failure)?;
+
+    let indexes = nonces.iter().map(|(idx, _, _)| *idx).collect();
+    let signatures = nonces
+        .into_iter()
+        .filter_map(|(_, outcome, sig)| outcome.zip(sig))
+        .map(|(outcome, sig)| {
+            let sig = Signature::from_slice(&sig).map_err(storage_failure)?;
+            Ok((outcome, sig))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(Some(OracleEventData {
+        event_id: event_id.to_string(),
+        announcement,
+        indexes,
+        signatures,
+        state,
+        attestation_scheme,
+        #[cfg(feature = "nostr")]
+        announcement_event_id,
+        #[cfg(feature = "nostr")]
+        attestation_event_id,
+    }))
+}
+
+impl Storage for SqliteStorage {
+    async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+
+        let current_index: u32 = tx
+            .query_row(
+                "SELECT next_index FROM nonce_counter WHERE id = 0",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_failure)?
+            .unwrap_or(0);
+        let next_index = current_index
+            .checked_add(num as u32)
+            .ok_or(Error::Internal)?;
+        tx.execute(
+            "INSERT INTO nonce_counter (id, next_index) VALUES (0, ?1)
+             ON CONFLICT (id) DO UPDATE SET next_index = excluded.next_index",
+            params![next_index],
+        )
+        .map_err(storage_failure)?;
+        tx.commit().map_err(storage_failure)?;
+
+        Ok((current_index..next_index).collect())
+    }
+
+    async fn save_announcement(
+        &self,
+        announcement: OracleAnnouncement,
+        indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<String, Error> {
+        let mut event_ids = self
+            .save_announcements(vec![(announcement, indexes)], attestation_scheme)
+            .await?;
+        event_ids.pop().ok_or(Error::Internal)
+    }
+
+    async fn save_announcements(
+        &self,
+        announcements: Vec<(OracleAnnouncement, Vec<u32>)>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<Vec<String>, Error> {
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+
+        let mut event_ids = Vec::with_capacity(announcements.len());
+        for (announcement, indexes) in announcements {
+            let event_id = announcement.oracle_event.event_id.clone();
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM events WHERE event_id = ?1",
+                    params![event_id],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(storage_failure)?
+                .is_some();
+            if exists {
+                return Err(Error::EventAlreadyExists);
+            }
+            tx.execute(
+                "INSERT INTO events (event_id, announcement, attestation_scheme)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    event_id,
+                    announcement.encode(),
+                    attestation_scheme.to_string()
+                ],
+            )
+            .map_err(storage_failure)?;
+            for (position, index) in indexes.into_iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO event_nonces (idx, event_id, position) VALUES (?1, ?2, ?3)",
+                    params![index, event_id, position as u32],
+                )
+                .map_err(storage_failure)?;
+            }
+            event_ids.push(event_id);
+        }
+        tx.commit().map_err(storage_failure)?;
+
+        Ok(event_ids)
+    }
+
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        let outcomes = serde_json::to_string(&outcomes).map_err(|_| Error::Internal)?;
+
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM events WHERE event_id = ?1",
+                params![event_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(storage_failure)?
+            .is_some();
+        if !exists {
+            return Err(Error::NotFound);
+        }
+
+        tx.execute(
+            "INSERT OR IGNORE INTO sign_intents (event_id, outcomes) VALUES (?1, ?2)",
+            params![event_id, outcomes],
+        )
+        .map_err(storage_failure)?;
+        let committed: String = tx
+            .query_row(
+                "SELECT outcomes FROM sign_intents WHERE event_id = ?1",
+                params![event_id],
+                |row| row.get(0),
+            )
+            .map_err(storage_failure)?;
+        tx.commit().map_err(storage_failure)?;
+
+        serde_json::from_str(&committed).map_err(|_| Error::StorageFailure)
+    }
+
+    async fn save_signatures(
+        &self,
+        event_id: String,
+        sigs: Vec<(String, Signature)>,
+    ) -> Result<OracleEventData, Error> {
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+
+        let Some(event) = read_event(&tx, &event_id)? else {
+            return Err(Error::NotFound);
+        };
+        if !event.signatures.is_empty() {
+            return Err(Error::EventAlreadySigned);
+        }
+        if event.indexes.len() != sigs.len() {
+            return Err(Error::InvalidArgument);
+        }
+
+        for (position, (outcome, sig)) in sigs.iter().enumerate() {
+            tx.execute(
+                "UPDATE event_nonces SET outcome = ?1, signature = ?2
+                 WHERE event_id = ?3 AND position = ?4",
+                params![outcome, sig.encode(), event_id, position as u32],
+            )
+            .map_err(storage_failure)?;
+        }
+        let state = match event.state {
+            EventState::Cancelled => EventState::Cancelled,
+            _ => EventState::Signed,
+        };
+        tx.execute(
+            "UPDATE events SET state = ?1 WHERE event_id = ?2",
+            params![state.to_string(), event_id],
+        )
+        .map_err(storage_failure)?;
+        tx.commit().map_err(storage_failure)?;
+
+        Ok(OracleEventData {
+            signatures: sigs,
+            state,
+            ..event
+        })
+    }
+
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let updated = conn
+            .execute(
+                "UPDATE events SET state = ?1 WHERE event_id = ?2",
+                params![state.to_string(), event_id],
+            )
+            .map_err(storage_failure)?;
+        if updated == 0 {
+            return Err(Error::NotFound);
+        }
+        read_event(&conn, &event_id)?.ok_or(Error::Internal)
+    }
+
+    async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        read_event(&conn, &event_id)
+    }
+
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let mut stmt = conn
+            .prepare("SELECT event_id FROM events ORDER BY created_at ASC, rowid ASC")
+            .map_err(storage_failure)?;
+        let event_ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(storage_failure)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(storage_failure)?;
+
+        event_ids
+            .iter()
+            .filter_map(|event_id| read_event(&conn, event_id).transpose())
+            .collect()
+    }
+
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let mut stmt = conn
+            .prepare("SELECT event_id, outcomes FROM sign_intents")
+            .map_err(storage_failure)?;
+        let intents = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(storage_failure)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(storage_failure)?;
+
+        intents
+            .into_iter()
+            .map(|(event_id, outcomes)| {
+                let outcomes =
+                    serde_json::from_str(&outcomes).map_err(|_| Error::StorageFailure)?;
+                Ok((event_id, outcomes))
+            })
+            .collect()
+    }
+
+    async fn get_nonce_counter(&self) -> Result<u32, Error> {
+        let conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let next_index: Option<u32> = conn
+            .query_row(
+                "SELECT next_index FROM nonce_counter WHERE id = 0",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_failure)?;
+        Ok(next_index.unwrap_or(0))
+    }
+
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+
+        let current_index: u32 = tx
+            .query_row(
+                "SELECT next_index FROM nonce_counter WHERE id = 0",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_failure)?
+            .unwrap_or(0);
+        if current_index > next {
+            return Err(Error::InvalidArgument);
+        }
+        tx.execute(
+            "INSERT INTO nonce_counter (id, next_index) VALUES (0, ?1)
+             ON CONFLICT (id) DO UPDATE SET next_index = excluded.next_index",
+            params![next],
+        )
+        .map_err(storage_failure)?;
+        tx.commit().map_err(storage_failure)
+    }
+
+    async fn restore_event(&self, data: OracleEventData) -> Result<(), Error> {
+        if !data.signatures.is_empty() && data.signatures.len() != data.indexes.len() {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut conn = self.conn.lock().map_err(|_| Error::Internal)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(storage_failure)?;
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM events WHERE event_id = ?1",
+                params![data.event_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(storage_failure)?
+            .is_some();
+        if exists {
+            return Err(Error::EventAlreadyExists);
+        }
+
+        #[cfg(feature = "nostr")]
+        let (announcement_event_id, attestation_event_id) =
+            (&data.announcement_event_id, &data.attestation_event_id);
+        #[cfg(not(feature = "nostr"))]
+        let (announcement_event_id, attestation_event_id): (
+            Option<String>,
+            Option<String>,
+        ) = (None, None);
+        tx.execute(
+            "INSERT INTO events
+             (event_id, announcement, state, attestation_scheme, announcement_event_id,
+              attestation_event_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                data.event_id,
+                data.announcement.encode(),
+                data.state.to_string(),
+                data.attestation_scheme.to_string(),
+                announcement_event_id,
+                attestation_event_id,
+            ],
+        )
+        .map_err(storage_failure)?;
+        for (position, index) in data.indexes.iter().enumerate() {
+            let (outcome, signature) = match data.signatures.get(position) {
+                Some((outcome, sig)) => (Some(outcome.clone()), Some(sig.encode())),
+                None => (None, None),
+            };
+            tx.execute(
+                "INSERT INTO event_nonces (idx, event_id, position, outcome, signature)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![index, data.event_id, position as u32, outcome, signature],
+            )
+            .map_err(storage_failure)?;
+        }
+        if !data.signatures.is_empty() {
+            let outcomes = data.signatures.iter().map(|(o, _)| o).collect::<Vec<_>>();
+            let outcomes = serde_json::to_string(&outcomes).map_err(|_| Error::Internal)?;
+            tx.execute(
+                "INSERT INTO sign_intents (event_id, outcomes) VALUES (?1, ?2)",
+                params![data.event_id, outcomes],
+            )
+            .map_err(storage_failure)?;
+        }
+        tx.commit().map_err(storage_failure)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signer::{LocalSigner, Signer};
+    use crate::test_utils::{run_storage_conformance_tests, StorageFactory};
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::Network;
+
+    fn temp_db_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("kormir-{}.sqlite", thread_rng().gen::<u64>()))
+    }
+
+    /// Creates temporary databases, they are deleted when it is dropped
+    #[derive(Default)]
+    struct SqliteStorageFactory {
+        paths: Mutex<Vec<std::path::PathBuf>>,
+    }
+
+    impl Drop for SqliteStorageFactory {
+        fn drop(&mut self) {
+            for path in self.paths.get_mut().unwrap().drain(..) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    impl StorageFactory for SqliteStorageFactory {
+        type Storage = SqliteStorage;
+
+        async fn create(&self) -> SqliteStorage {
+            let path = temp_db_path();
+            self.paths.lock().unwrap().push(path.clone());
+            SqliteStorage::open(path).unwrap()
+        }
+
+        async fn reopen(&self, storage: SqliteStorage) -> SqliteStorage {
+            let path = storage.conn.lock().unwrap().path().unwrap().to_string();
+            drop(storage);
+            SqliteStorage::open(path).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage_conformance() {
+        run_storage_conformance_tests(&SqliteStorageFactory::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_round_trip() {
+        let path = temp_db_path();
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        let oracle = Oracle::from_xpriv(storage, xpriv).unwrap();
+        assert!(oracle.storage.oracle_public_key().unwrap().is_none());
+        oracle
+            .storage
+            .set_oracle_public_key(oracle.public_key())
+            .unwrap();
+        assert_eq!(oracle.storage.oracle_name().unwrap().unwrap(), "Kormir");
+        oracle.storage.set_oracle_name("Test Oracle").unwrap();
+
+        let ann = oracle
+            .create_numeric_event("numeric".to_string(), 2, 8, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        oracle
+            .create_enum_event("enum".to_string(), vec!["a".into(), "b".into()], 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .sign_numeric_event("numeric".to_string(), -42)
+            .await
+            .unwrap();
+        let res = oracle.sign_numeric_event("numeric".to_string(), 42).await;
+        assert!(res.is_err());
+
+        // reopen the database
+        drop(oracle);
+        let storage = SqliteStorage::open(&path).unwrap();
+        let oracle = Oracle::from_xpriv(storage, xpriv).unwrap();
+        assert_eq!(
+            oracle.storage.oracle_public_key().unwrap(),
+            Some(oracle.public_key())
+        );
+        assert_eq!(
+            oracle.storage.oracle_name().unwrap().unwrap(),
+            "Test Oracle"
+        );
+
+        let data = oracle
+            .storage
+            .get_event("numeric".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.announcement, ann);
+        assert_eq!(data.indexes, (0..9).collect::<Vec<_>>());
+        assert_eq!(
+            data.signatures,
+            attestation
+                .outcomes
+                .into_iter()
+                .zip(attestation.signatures)
+                .collect::<Vec<_>>()
+        );
+
+        // the nonce counter survived the reopen
+        assert_eq!(
+            oracle.storage.get_next_nonce_indexes(2).await.unwrap(),
+            vec![10, 11]
+        );
+
+        let events = oracle.storage.list_events().await.unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| e.event_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["numeric", "enum"]
+        );
+
+        let attestation = oracle
+            .sign_enum_event("enum".to_string(), "b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(attestation.outcomes, vec!["b".to_string()]);
+
+        drop(oracle);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_key_handovers() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let old = LocalSigner::from_signing_key(SecretKey::from_slice(&[1; 32]).unwrap()).unwrap();
+        let new = LocalSigner::from_signing_key(SecretKey::from_slice(&[2; 32]).unwrap()).unwrap();
+        storage.set_oracle_public_key(old.public_key()).unwrap();
+        assert!(storage.key_handovers().unwrap().is_empty());
+
+        let handover = old
+            .sign_key_handover(new.public_key(), 1_000)
+            .await
+            .unwrap();
+        storage.save_key_handover(&handover).unwrap();
+        assert_eq!(storage.key_handovers().unwrap(), vec![handover.clone()]);
+        assert_eq!(storage.oracle_public_key().unwrap(), Some(new.public_key()));
+
+        // a key can only be handed over once
+        assert!(storage.save_key_handover(&handover).is_err());
+        assert_eq!(storage.oracle_public_key().unwrap(), Some(new.public_key()));
+    }
+
+    #[cfg(feature = "nostr")]
+    #[tokio::test]
+    async fn test_sqlite_nostr_event_ids() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let oracle = Oracle::from_signing_key(
+            storage,
+            bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+        )
+        .unwrap();
+        oracle
+            .create_enum_event("enum".to_string(), vec!["a".into()], 100)
+            .await
+            .unwrap();
+
+        oracle
+            .storage
+            .add_announcement_event_id("enum".to_string(), "ann".to_string())
+            .await
+            .unwrap();
+        oracle
+            .storage
+            .add_attestation_event_id("enum".to_string(), "att".to_string())
+            .await
+            .unwrap();
+        let res = oracle
+            .storage
+            .add_attestation_event_id("missing".to_string(), "att".to_string())
+            .await;
+        assert!(matches!(res, Err(Error::NotFound)));
+
+        let data = oracle
+            .storage
+            .get_event("enum".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.announcement_event_id, Some("ann".to_string()));
+        assert_eq!(data.attestation_event_id, Some("att".to_string()));
+    }
+}
@@ -1,8 +1,11 @@
 use crate::error::Error;
+use crate::AttestationScheme;
 use bitcoin::secp256k1::schnorr::Signature;
-use dlc_messages::oracle_msgs::OracleAnnouncement;
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, RwLock};
 
@@ -10,23 +13,187 @@ pub trait Storage {
     /// Get the next `num` nonce indexes
     async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error>;
 
-    /// Save the announcement and return the identifier
-    /// for the announcement
+    /// Save the announcement, signed with the given scheme, and return the
+    /// identifier for the announcement
     async fn save_announcement(
         &self,
         announcement: OracleAnnouncement,
         indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
     ) -> Result<String, Error>;
 
-    /// Save signatures and outcomes for a given event
+    /// Save several announcements with their nonce indexes and return their
+    /// identifiers. Storages that can should save all of them or none, the
+    /// default saves them one by one and stops at the first failure.
+    async fn save_announcements(
+        &self,
+        announcements: Vec<(OracleAnnouncement, Vec<u32>)>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<Vec<String>, Error> {
+        let mut event_ids = Vec::with_capacity(announcements.len());
+        for (announcement, indexes) in announcements {
+            let event_id = self
+                .save_announcement(announcement, indexes, attestation_scheme)
+                .await?;
+            event_ids.push(event_id);
+        }
+        Ok(event_ids)
+    }
+
+    /// Commit to signing the given outcomes for an event, before any signature
+    /// is produced. This must be atomic: if an intent was already saved for
+    /// the event it must not be overwritten, and the existing outcomes are
+    /// returned instead.
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error>;
+
+    /// Save signatures and outcomes for a given event, and move it to the
+    /// [`EventState::Signed`] state unless it was cancelled
     async fn save_signatures(
         &self,
         event_id: String,
         sigs: Vec<(String, Signature)>,
     ) -> Result<OracleEventData, Error>;
 
+    /// Persist the lifecycle state of an event. Transitions are checked by
+    /// the [`Oracle`](crate::Oracle) before calling this.
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error>;
+
     /// Get the announcement data for the given id
     async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error>;
+
+    /// List the data of every stored event
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error>;
+
+    /// List the event ids and committed outcomes of every saved sign intent
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error>;
+
+    /// The nonce index [`Storage::get_next_nonce_indexes`] hands out next
+    async fn get_nonce_counter(&self) -> Result<u32, Error>;
+
+    /// Move the nonce counter forward so that `next` is handed out next. Must
+    /// fail with [`Error::InvalidArgument`] instead of moving it backwards.
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error>;
+
+    /// Save an event restored from a backup as is, including its signatures,
+    /// state and nostr event ids. Signed events also get a sign intent for
+    /// their outcomes. Fails with [`Error::EventAlreadyExists`] if the event
+    /// id is taken.
+    async fn restore_event(&self, data: OracleEventData) -> Result<(), Error>;
+}
+
+/// Seconds after its maturity an unsigned event is considered overdue
+pub const OVERDUE_AFTER: u32 = 60 * 60;
+
+/// Where an event is in its lifecycle, as persisted by the [`Storage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventState {
+    /// The announcement was created but not broadcast yet
+    #[default]
+    Created,
+    /// The announcement was broadcast
+    Announced,
+    /// The event was attested to
+    Signed,
+    /// The event was cancelled, no outcome will be signed for it
+    Cancelled,
+}
+
+impl EventState {
+    /// Whether an event in this state may be moved to `next`
+    pub fn can_transition_to(self, next: EventState) -> bool {
+        matches!(
+            (self, next),
+            (
+                EventState::Created,
+                EventState::Announced | EventState::Signed | EventState::Cancelled
+            ) | (
+                EventState::Announced,
+                EventState::Signed | EventState::Cancelled
+            )
+        )
+    }
+
+    /// Whether no further transition is possible
+    pub fn is_final(self) -> bool {
+        matches!(self, EventState::Signed | EventState::Cancelled)
+    }
+}
+
+impl Display for EventState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventState::Created => write!(f, "created"),
+            EventState::Announced => write!(f, "announced"),
+            EventState::Signed => write!(f, "signed"),
+            EventState::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl FromStr for EventState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(EventState::Created),
+            "announced" => Ok(EventState::Announced),
+            "signed" => Ok(EventState::Signed),
+            "cancelled" => Ok(EventState::Cancelled),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
+/// An event's [`EventState`] refined with its maturity, for display and filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    /// Waiting for the event to mature
+    Pending,
+    /// Matured and waiting to be signed
+    Matured,
+    /// Still not signed [`OVERDUE_AFTER`] seconds after maturity
+    Overdue,
+    /// The event was attested to
+    Signed,
+    /// The event was cancelled
+    Cancelled,
+}
+
+impl Display for EventStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventStatus::Pending => write!(f, "pending"),
+            EventStatus::Matured => write!(f, "matured"),
+            EventStatus::Overdue => write!(f, "overdue"),
+            EventStatus::Signed => write!(f, "signed"),
+            EventStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl FromStr for EventStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(EventStatus::Pending),
+            "matured" => Ok(EventStatus::Matured),
+            "overdue" => Ok(EventStatus::Overdue),
+            "signed" => Ok(EventStatus::Signed),
+            "cancelled" => Ok(EventStatus::Cancelled),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
 }
 
 /// Data saved for an oracle announcement
@@ -36,16 +203,55 @@ pub struct OracleEventData {
     pub announcement: OracleAnnouncement,
     pub indexes: Vec<u32>,
     pub signatures: Vec<(String, Signature)>,
+    #[serde(default)]
+    pub state: EventState,
+    /// How the announcement and outcomes are signed
+    #[serde(default)]
+    pub attestation_scheme: AttestationScheme,
     #[cfg(feature = "nostr")]
     pub announcement_event_id: Option<String>,
     #[cfg(feature = "nostr")]
     pub attestation_event_id: Option<String>,
 }
 
+impl OracleEventData {
+    /// The status of the event at `now`
+    pub fn status(&self, now: u32) -> EventStatus {
+        let maturity = self.announcement.oracle_event.event_maturity_epoch;
+        match self.state {
+            EventState::Signed => EventStatus::Signed,
+            EventState::Cancelled => EventStatus::Cancelled,
+            EventState::Created | EventState::Announced => {
+                if now < maturity {
+                    EventStatus::Pending
+                } else if now - maturity < OVERDUE_AFTER {
+                    EventStatus::Matured
+                } else {
+                    EventStatus::Overdue
+                }
+            }
+        }
+    }
+
+    /// The attestation of the saved signatures, if the event was signed
+    pub fn attestation(&self) -> Option<OracleAttestation> {
+        if self.signatures.is_empty() {
+            return None;
+        }
+        Some(OracleAttestation {
+            event_id: self.announcement.oracle_event.event_id.clone(),
+            oracle_public_key: self.announcement.oracle_public_key,
+            signatures: self.signatures.iter().map(|x| x.1).collect(),
+            outcomes: self.signatures.iter().map(|x| x.0.clone()).collect(),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MemoryStorage {
     current_index: Arc<AtomicU32>,
     data: Arc<RwLock<HashMap<String, OracleEventData>>>,
+    sign_intents: Arc<RwLock<HashMap<String, Vec<String>>>>,
 }
 
 impl MemoryStorage {
@@ -53,16 +259,9 @@ impl MemoryStorage {
         Self {
             current_index: Arc::new(AtomicU32::new(0)),
             data: Arc::new(RwLock::new(HashMap::new())),
+            sign_intents: Arc::new(RwLock::new(HashMap::new())),
         }
     }
-
-    pub fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
-        let Ok(guard) = self.data.try_read() else {
-            return Err(Error::Internal);
-        };
-
-        Ok(guard.values().cloned().collect())
-    }
 }
 
 impl Default for MemoryStorage {
@@ -72,6 +271,7 @@ impl Default for MemoryStorage {
 }
 
 impl Storage for MemoryStorage {
+    #[allow(clippy::explicit_counter_loop)]
     async fn get_next_nonce_indexes(&self, num: usize) -> Result<Vec<u32>, Error> {
         let mut current_index = self.current_index.fetch_add(num as u32, Ordering::Relaxed);
         let mut indexes = Vec::with_capacity(num);
@@ -86,23 +286,59 @@ impl Storage for MemoryStorage {
         &self,
         announcement: OracleAnnouncement,
         indexes: Vec<u32>,
+        attestation_scheme: AttestationScheme,
     ) -> Result<String, Error> {
-        let event_id = announcement.oracle_event.event_id.clone();
-        let event = OracleEventData {
-            event_id: event_id.clone(),
-            announcement,
-            indexes,
-            signatures: Default::default(),
-            #[cfg(feature = "nostr")]
-            announcement_event_id: None,
-            #[cfg(feature = "nostr")]
-            attestation_event_id: None,
-        };
+        let mut event_ids = self
+            .save_announcements(vec![(announcement, indexes)], attestation_scheme)
+            .await?;
+        event_ids.pop().ok_or(Error::Internal)
+    }
 
+    async fn save_announcements(
+        &self,
+        announcements: Vec<(OracleAnnouncement, Vec<u32>)>,
+        attestation_scheme: AttestationScheme,
+    ) -> Result<Vec<String>, Error> {
         let mut data = self.data.try_write().unwrap();
-        data.insert(event_id.clone(), event);
+        let mut event_ids = Vec::with_capacity(announcements.len());
+        for (announcement, _) in &announcements {
+            let event_id = &announcement.oracle_event.event_id;
+            if data.contains_key(event_id) || event_ids.contains(event_id) {
+                return Err(Error::EventAlreadyExists);
+            }
+            event_ids.push(event_id.clone());
+        }
+
+        for (announcement, indexes) in announcements {
+            let event = OracleEventData {
+                event_id: announcement.oracle_event.event_id.clone(),
+                announcement,
+                indexes,
+                signatures: Default::default(),
+                state: EventState::Created,
+                attestation_scheme,
+                #[cfg(feature = "nostr")]
+                announcement_event_id: None,
+                #[cfg(feature = "nostr")]
+                attestation_event_id: None,
+            };
+            data.insert(event.event_id.clone(), event);
+        }
 
-        Ok(event_id)
+        Ok(event_ids)
+    }
+
+    async fn save_sign_intent(
+        &self,
+        event_id: String,
+        outcomes: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        if !self.data.try_read().unwrap().contains_key(&event_id) {
+            return Err(Error::NotFound);
+        }
+
+        let mut sign_intents = self.sign_intents.try_write().unwrap();
+        Ok(sign_intents.entry(event_id).or_insert(outcomes).clone())
     }
 
     async fn save_signatures(
@@ -120,13 +356,100 @@ impl Storage for MemoryStorage {
         }
 
         event.signatures = sigs;
+        if event.state != EventState::Cancelled {
+            event.state = EventState::Signed;
+        }
         data.insert(id, event.clone());
 
         Ok(event)
     }
 
+    async fn set_event_state(
+        &self,
+        event_id: String,
+        state: EventState,
+    ) -> Result<OracleEventData, Error> {
+        let mut data = self.data.try_write().unwrap();
+        let Some(event) = data.get_mut(&event_id) else {
+            return Err(Error::NotFound);
+        };
+
+        event.state = state;
+
+        Ok(event.clone())
+    }
+
     async fn get_event(&self, event_id: String) -> Result<Option<OracleEventData>, Error> {
         let data = self.data.try_read().unwrap();
         Ok(data.get(&event_id).cloned())
     }
+
+    async fn list_events(&self) -> Result<Vec<OracleEventData>, Error> {
+        let Ok(guard) = self.data.try_read() else {
+            return Err(Error::Internal);
+        };
+
+        Ok(guard.values().cloned().collect())
+    }
+
+    async fn list_sign_intents(&self) -> Result<Vec<(String, Vec<String>)>, Error> {
+        let Ok(guard) = self.sign_intents.try_read() else {
+            return Err(Error::Internal);
+        };
+
+        Ok(guard.clone().into_iter().collect())
+    }
+
+    async fn get_nonce_counter(&self) -> Result<u32, Error> {
+        Ok(self.current_index.load(Ordering::SeqCst))
+    }
+
+    async fn set_nonce_counter(&self, next: u32) -> Result<(), Error> {
+        let previous = self.current_index.fetch_max(next, Ordering::SeqCst);
+        if previous > next {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    async fn restore_event(&self, event: OracleEventData) -> Result<(), Error> {
+        let mut data = self.data.try_write().unwrap();
+        if data.contains_key(&event.event_id) {
+            return Err(Error::EventAlreadyExists);
+        }
+
+        if !event.signatures.is_empty() {
+            let outcomes = event.signatures.iter().map(|(o, _)| o.clone()).collect();
+            let mut sign_intents = self.sign_intents.try_write().unwrap();
+            sign_intents.insert(event.event_id.clone(), outcomes);
+        }
+        data.insert(event.event_id.clone(), event);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{run_storage_conformance_tests, StorageFactory};
+
+    struct MemoryStorageFactory;
+
+    impl StorageFactory for MemoryStorageFactory {
+        type Storage = MemoryStorage;
+
+        async fn create(&self) -> MemoryStorage {
+            MemoryStorage::default()
+        }
+
+        async fn reopen(&self, storage: MemoryStorage) -> MemoryStorage {
+            storage
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage_conformance() {
+        run_storage_conformance_tests(&MemoryStorageFactory).await;
+    }
 }
@@ -0,0 +1,451 @@
+//! A conformance test kit for [`Storage`] implementations.
+//!
+//! Every storage backend is expected to behave the same way, implementors can
+//! run the whole battery with [`run_storage_conformance_tests`] from their own
+//! test suite:
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn test_conformance() {
+//!     kormir::test_utils::run_storage_conformance_tests(&MyStorageFactory).await;
+//! }
+//! ```
+//!
+//! The tests panic on the first violation, like regular assertions.
+
+use crate::error::Error;
+use crate::storage::{EventState, MemoryStorage, OracleEventData, Storage};
+use crate::{AttestationScheme, Oracle, OracleAnnouncement, Signature};
+use bitcoin::bip32::Xpriv;
+use bitcoin::Network;
+
+/// Creates the [`Storage`] under test
+pub trait StorageFactory {
+    type Storage: Storage;
+
+    /// Creates a new, empty storage
+    async fn create(&self) -> Self::Storage;
+
+    /// Closes the storage and opens it again, as if the process restarted
+    async fn reopen(&self, storage: Self::Storage) -> Self::Storage;
+}
+
+/// An oracle used to produce valid announcements and signatures for the tests
+fn fixture_oracle(seed: u8) -> Oracle<MemoryStorage> {
+    let xpriv = Xpriv::new_master(Network::Regtest, &[seed; 64]).unwrap();
+    Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+}
+
+async fn enum_announcement(oracle: &Oracle<MemoryStorage>, event_id: &str) -> OracleAnnouncement {
+    oracle
+        .create_enum_event(
+            event_id.to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            100,
+        )
+        .await
+        .unwrap()
+}
+
+async fn numeric_announcement(
+    oracle: &Oracle<MemoryStorage>,
+    event_id: &str,
+    num_digits: u16,
+) -> OracleAnnouncement {
+    oracle
+        .create_numeric_event(
+            event_id.to_string(),
+            2,
+            num_digits,
+            false,
+            0,
+            "m/s".to_string(),
+            100,
+        )
+        .await
+        .unwrap()
+}
+
+async fn enum_signatures(
+    oracle: &Oracle<MemoryStorage>,
+    event_id: &str,
+    outcome: &str,
+) -> Vec<(String, Signature)> {
+    let attestation = oracle
+        .sign_enum_event(event_id.to_string(), outcome.to_string())
+        .await
+        .unwrap();
+    attestation
+        .outcomes
+        .into_iter()
+        .zip(attestation.signatures)
+        .collect()
+}
+
+/// Reserves nonce indexes for the announcement and saves it
+async fn save<S: Storage>(storage: &S, announcement: &OracleAnnouncement) -> Vec<u32> {
+    let indexes = storage
+        .get_next_nonce_indexes(announcement.oracle_event.oracle_nonces.len())
+        .await
+        .unwrap();
+    let event_id = storage
+        .save_announcement(
+            announcement.clone(),
+            indexes.clone(),
+            AttestationScheme::Legacy,
+        )
+        .await
+        .unwrap();
+    assert_eq!(event_id, announcement.oracle_event.event_id);
+    indexes
+}
+
+fn assert_event_eq(data: &OracleEventData, announcement: &OracleAnnouncement, indexes: &[u32]) {
+    assert_eq!(data.event_id, announcement.oracle_event.event_id);
+    assert_eq!(&data.announcement, announcement);
+    assert_eq!(data.indexes, indexes);
+}
+
+/// Saving an announcement for an existing event id must fail and keep the
+/// original event untouched.
+pub async fn test_duplicate_event_id<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let first = enum_announcement(&fixture_oracle(1), "duplicate").await;
+    let second = enum_announcement(&fixture_oracle(2), "duplicate").await;
+
+    let indexes = save(&storage, &first).await;
+
+    let second_indexes = storage.get_next_nonce_indexes(1).await.unwrap();
+    let res = storage
+        .save_announcement(second, second_indexes, AttestationScheme::Legacy)
+        .await;
+    assert!(matches!(res, Err(Error::EventAlreadyExists)), "got {res:?}");
+
+    let data = storage
+        .get_event("duplicate".to_string())
+        .await
+        .unwrap()
+        .expect("original event was removed");
+    assert_event_eq(&data, &first, &indexes);
+
+    let events = storage.list_events().await.unwrap();
+    assert_eq!(events.len(), 1);
+}
+
+/// Saving several announcements at once stores each of them with the given
+/// attestation scheme, and a batch with an existing event id is rejected
+/// without touching the stored events.
+pub async fn test_save_announcements<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let oracle = fixture_oracle(1);
+    let first = enum_announcement(&oracle, "batch-1").await;
+    let second = numeric_announcement(&oracle, "batch-2", 4).await;
+
+    let first_indexes = storage.get_next_nonce_indexes(1).await.unwrap();
+    let second_indexes = storage.get_next_nonce_indexes(4).await.unwrap();
+    let event_ids = storage
+        .save_announcements(
+            vec![
+                (first.clone(), first_indexes.clone()),
+                (second.clone(), second_indexes.clone()),
+            ],
+            AttestationScheme::Tagged,
+        )
+        .await
+        .unwrap();
+    assert_eq!(event_ids, vec!["batch-1", "batch-2"]);
+
+    let data = storage.get_event("batch-1".to_string()).await.unwrap();
+    let data = data.unwrap();
+    assert_event_eq(&data, &first, &first_indexes);
+    assert_eq!(data.attestation_scheme, AttestationScheme::Tagged);
+    let data = storage.get_event("batch-2".to_string()).await.unwrap();
+    assert_event_eq(&data.unwrap(), &second, &second_indexes);
+    let events = storage.list_events().await.unwrap();
+    assert!(events
+        .iter()
+        .all(|event| event.attestation_scheme == AttestationScheme::Tagged));
+
+    let duplicate = enum_announcement(&fixture_oracle(2), "batch-1").await;
+    let third = enum_announcement(&oracle, "batch-3").await;
+    let indexes = storage.get_next_nonce_indexes(2).await.unwrap();
+    let res = storage
+        .save_announcements(
+            vec![(duplicate, vec![indexes[0]]), (third, vec![indexes[1]])],
+            AttestationScheme::Legacy,
+        )
+        .await;
+    assert!(matches!(res, Err(Error::EventAlreadyExists)), "got {res:?}");
+
+    let data = storage.get_event("batch-1".to_string()).await.unwrap();
+    assert_event_eq(&data.unwrap(), &first, &first_indexes);
+    assert!(storage
+        .get_event("batch-3".to_string())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Signatures can only be saved once per event.
+pub async fn test_double_signing<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let oracle = fixture_oracle(1);
+    let announcement = enum_announcement(&oracle, "double-sign").await;
+    let sigs = enum_signatures(&oracle, "double-sign", "a").await;
+    let other = sigs
+        .iter()
+        .map(|(_, sig)| ("b".to_string(), *sig))
+        .collect::<Vec<_>>();
+    save(&storage, &announcement).await;
+
+    let res = storage
+        .save_signatures("missing".to_string(), sigs.clone())
+        .await;
+    assert!(matches!(res, Err(Error::NotFound)), "got {res:?}");
+
+    let data = storage
+        .save_signatures("double-sign".to_string(), sigs.clone())
+        .await
+        .unwrap();
+    assert_eq!(data.signatures, sigs);
+
+    let res = storage
+        .save_signatures("double-sign".to_string(), other)
+        .await;
+    assert!(matches!(res, Err(Error::EventAlreadySigned)), "got {res:?}");
+
+    let data = storage
+        .get_event("double-sign".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(data.signatures, sigs);
+}
+
+/// Nonce indexes are never handed out twice, even after the storage is
+/// reopened.
+pub async fn test_nonce_monotonicity<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let oracle = fixture_oracle(1);
+
+    let first = save(&storage, &numeric_announcement(&oracle, "first", 4).await).await;
+    let second = save(&storage, &enum_announcement(&oracle, "second").await).await;
+    let mut used = first.into_iter().chain(second).collect::<Vec<_>>();
+    assert!(
+        used.windows(2).all(|w| w[0] < w[1]),
+        "nonce indexes are not increasing: {used:?}"
+    );
+
+    let storage = factory.reopen(storage).await;
+    let third = save(&storage, &numeric_announcement(&oracle, "third", 3).await).await;
+    for index in &third {
+        assert!(
+            used.iter().all(|used| used < index),
+            "nonce index {index} was reused after reopen, already used: {used:?}"
+        );
+    }
+    used.extend(third);
+
+    let storage = factory.reopen(storage).await;
+    let next = storage.get_next_nonce_indexes(2).await.unwrap();
+    assert_eq!(next.len(), 2);
+    for index in &next {
+        assert!(
+            used.iter().all(|used| used < index),
+            "nonce index {index} was reused after reopen, already used: {used:?}"
+        );
+    }
+}
+
+/// Announcements, signatures and sign intents are returned as they were saved.
+pub async fn test_event_round_trip<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let oracle = fixture_oracle(1);
+
+    assert!(storage
+        .get_event("enum".to_string())
+        .await
+        .unwrap()
+        .is_none());
+    assert!(storage.list_events().await.unwrap().is_empty());
+
+    let enum_ann = enum_announcement(&oracle, "enum").await;
+    let enum_indexes = save(&storage, &enum_ann).await;
+    let numeric_ann = numeric_announcement(&oracle, "numeric", 8).await;
+    let numeric_indexes = save(&storage, &numeric_ann).await;
+
+    let data = storage
+        .get_event("enum".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_event_eq(&data, &enum_ann, &enum_indexes);
+    assert!(data.signatures.is_empty());
+
+    // the sign intent is committed once
+    let res = storage
+        .save_sign_intent("missing".to_string(), vec!["a".to_string()])
+        .await;
+    assert!(matches!(res, Err(Error::NotFound)), "got {res:?}");
+    let committed = storage
+        .save_sign_intent("enum".to_string(), vec!["a".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(committed, vec!["a".to_string()]);
+    let committed = storage
+        .save_sign_intent("enum".to_string(), vec!["b".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(committed, vec!["a".to_string()]);
+    assert_eq!(
+        storage.list_sign_intents().await.unwrap(),
+        vec![("enum".to_string(), vec!["a".to_string()])]
+    );
+
+    let sigs = enum_signatures(&oracle, "enum", "a").await;
+    storage
+        .save_signatures("enum".to_string(), sigs.clone())
+        .await
+        .unwrap();
+
+    let storage = factory.reopen(storage).await;
+
+    let data = storage
+        .get_event("enum".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_event_eq(&data, &enum_ann, &enum_indexes);
+    assert_eq!(data.signatures, sigs);
+
+    let mut events = storage.list_events().await.unwrap();
+    events.sort_by(|a, b| a.event_id.cmp(&b.event_id));
+    assert_eq!(events.len(), 2);
+    assert_event_eq(&events[0], &enum_ann, &enum_indexes);
+    assert_eq!(events[0].signatures, sigs);
+    assert_event_eq(&events[1], &numeric_ann, &numeric_indexes);
+    assert!(events[1].signatures.is_empty());
+}
+
+/// Lifecycle states are persisted, and signing a cancelled event keeps it
+/// cancelled.
+pub async fn test_event_state<F: StorageFactory>(factory: &F) {
+    let storage = factory.create().await;
+    let oracle = fixture_oracle(1);
+    let signed = enum_announcement(&oracle, "signed").await;
+    let sigs = enum_signatures(&oracle, "signed", "a").await;
+    let cancelled = enum_announcement(&oracle, "cancelled").await;
+    let cancelled_sigs = enum_signatures(&oracle, "cancelled", "b").await;
+    save(&storage, &signed).await;
+    save(&storage, &cancelled).await;
+
+    let res = storage
+        .set_event_state("missing".to_string(), EventState::Announced)
+        .await;
+    assert!(matches!(res, Err(Error::NotFound)), "got {res:?}");
+
+    let data = storage
+        .get_event("signed".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(data.state, EventState::Created);
+
+    let data = storage
+        .set_event_state("signed".to_string(), EventState::Announced)
+        .await
+        .unwrap();
+    assert_eq!(data.state, EventState::Announced);
+    assert_eq!(data.announcement, signed);
+    let data = storage
+        .save_signatures("signed".to_string(), sigs)
+        .await
+        .unwrap();
+    assert_eq!(data.state, EventState::Signed);
+
+    let data = storage
+        .set_event_state("cancelled".to_string(), EventState::Cancelled)
+        .await
+        .unwrap();
+    assert_eq!(data.state, EventState::Cancelled);
+    let data = storage
+        .save_signatures("cancelled".to_string(), cancelled_sigs)
+        .await
+        .unwrap();
+    assert_eq!(data.state, EventState::Cancelled);
+
+    let storage = factory.reopen(storage).await;
+    let data = storage
+        .get_event("signed".to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(data.state, EventState::Signed);
+    let mut events = storage.list_events().await.unwrap();
+    events.sort_by(|a, b| a.event_id.cmp(&b.event_id));
+    assert_eq!(events[0].state, EventState::Cancelled);
+    assert_eq!(events[1].state, EventState::Signed);
+}
+
+/// Restored events come back as they were backed up, and the nonce counter
+/// only moves forward.
+pub async fn test_restore<F: StorageFactory>(factory: &F) {
+    let source = factory.create().await;
+    let oracle = fixture_oracle(1);
+    let signed = enum_announcement(&oracle, "signed").await;
+    let unsigned = numeric_announcement(&oracle, "unsigned", 4).await;
+    save(&source, &signed).await;
+    save(&source, &unsigned).await;
+    source
+        .save_signatures(
+            "signed".to_string(),
+            enum_signatures(&oracle, "signed", "a").await,
+        )
+        .await
+        .unwrap();
+    let next = source.get_nonce_counter().await.unwrap();
+    let mut events = source.list_events().await.unwrap();
+    events.sort_by(|a, b| a.event_id.cmp(&b.event_id));
+
+    let storage = factory.create().await;
+    storage.set_nonce_counter(next).await.unwrap();
+    for event in events.clone() {
+        storage.restore_event(event).await.unwrap();
+    }
+    let res = storage.restore_event(events[0].clone()).await;
+    assert!(matches!(res, Err(Error::EventAlreadyExists)), "got {res:?}");
+
+    let storage = factory.reopen(storage).await;
+    assert!(storage.get_nonce_counter().await.unwrap() >= next);
+    let res = storage.set_nonce_counter(next - 1).await;
+    assert!(matches!(res, Err(Error::InvalidArgument)), "got {res:?}");
+    let indexes = storage.get_next_nonce_indexes(1).await.unwrap();
+    assert!(indexes[0] >= next, "nonce index {} was reused", indexes[0]);
+
+    let mut restored = storage.list_events().await.unwrap();
+    restored.sort_by(|a, b| a.event_id.cmp(&b.event_id));
+    assert_eq!(restored.len(), 2);
+    for (restored, event) in restored.iter().zip(&events) {
+        assert_event_eq(restored, &event.announcement, &event.indexes);
+        assert_eq!(restored.signatures, event.signatures);
+        assert_eq!(restored.state, event.state);
+    }
+
+    // restored signatures keep the event from being signed again
+    let committed = storage
+        .save_sign_intent("signed".to_string(), vec!["b".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(committed, vec!["a".to_string()]);
+}
+
+/// Runs every conformance test, each against a fresh storage.
+pub async fn run_storage_conformance_tests<F: StorageFactory>(factory: &F) {
+    test_duplicate_event_id(factory).await;
+    test_save_announcements(factory).await;
+    test_double_signing(factory).await;
+    test_nonce_monotonicity(factory).await;
+    test_event_round_trip(factory).await;
+    test_event_state(factory).await;
+    test_restore(factory).await;
+}
@@ -0,0 +1,418 @@
+use crate::error::VerificationError;
+use crate::signer::{announcement_message, outcome_message};
+use crate::AttestationScheme;
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use dlc_messages::oracle_msgs::{
+    DigitDecompositionEventDescriptor, EventDescriptor, OracleAnnouncement, OracleAttestation,
+};
+
+/// Finds the scheme the announcement was signed with, the one its outcomes
+/// are signed with too.
+pub fn announcement_scheme<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+) -> Result<AttestationScheme, VerificationError> {
+    [AttestationScheme::Legacy, AttestationScheme::Tagged]
+        .into_iter()
+        .find(|scheme| {
+            announcement_message(&announcement.oracle_event, *scheme).is_ok_and(|msg| {
+                secp.verify_schnorr(
+                    &announcement.announcement_signature,
+                    &msg,
+                    &announcement.oracle_public_key,
+                )
+                .is_ok()
+            })
+        })
+        .ok_or(VerificationError::InvalidAnnouncementSignature)
+}
+
+/// Verifies the announcement signature, under either scheme, and that the
+/// event descriptor is one the oracle can attest to.
+pub fn verify_announcement<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+) -> Result<(), VerificationError> {
+    announcement_scheme(secp, announcement)?;
+    verify_event_descriptor(announcement)
+}
+
+/// The number of nonces an event with the descriptor is announced with: one
+/// for an enum event, one per digit and one for the sign of a numeric event.
+/// `None` if no outcome of the descriptor could be attested to.
+pub(crate) fn event_nonce_count(descriptor: &EventDescriptor) -> Option<usize> {
+    match descriptor {
+        EventDescriptor::EnumEvent(desc) if desc.outcomes.is_empty() => None,
+        EventDescriptor::EnumEvent(_) => Some(1),
+        EventDescriptor::DigitDecompositionEvent(desc) if desc.base < 2 || desc.nb_digits == 0 => {
+            None
+        }
+        EventDescriptor::DigitDecompositionEvent(desc) => {
+            Some(desc.nb_digits as usize + usize::from(desc.is_signed))
+        }
+    }
+}
+
+/// Checks the descriptor can be attested to with the announced nonces.
+fn verify_event_descriptor(announcement: &OracleAnnouncement) -> Result<(), VerificationError> {
+    let oracle_event = &announcement.oracle_event;
+    let expected_nonces = event_nonce_count(&oracle_event.event_descriptor)
+        .ok_or(VerificationError::InvalidEventDescriptor)?;
+    if oracle_event.oracle_nonces.len() != expected_nonces {
+        return Err(VerificationError::InvalidEventDescriptor);
+    }
+
+    Ok(())
+}
+
+/// Fully verifies that the attestation is valid for the given announcement:
+/// the announcement itself must be valid, every signature must use the
+/// announced nonce and sign its outcome with the announcement's scheme, and
+/// the outcomes must fit the announced event descriptor.
+pub fn verify_attestation<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+    attestation: &OracleAttestation,
+) -> Result<(), VerificationError> {
+    let scheme = announcement_scheme(secp, announcement)?;
+    verify_event_descriptor(announcement)?;
+
+    let oracle_event = &announcement.oracle_event;
+    if attestation.event_id != oracle_event.event_id {
+        return Err(VerificationError::EventIdMismatch);
+    }
+    if attestation.oracle_public_key != announcement.oracle_public_key {
+        return Err(VerificationError::OraclePublicKeyMismatch);
+    }
+    if attestation.signatures.len() != attestation.outcomes.len() {
+        return Err(VerificationError::OutcomeCountMismatch {
+            expected: attestation.signatures.len(),
+            actual: attestation.outcomes.len(),
+        });
+    }
+    if attestation.signatures.len() != oracle_event.oracle_nonces.len() {
+        return Err(VerificationError::SignatureCountMismatch {
+            expected: oracle_event.oracle_nonces.len(),
+            actual: attestation.signatures.len(),
+        });
+    }
+
+    match &oracle_event.event_descriptor {
+        EventDescriptor::EnumEvent(desc) => {
+            let outcome = &attestation.outcomes[0];
+            if !desc.outcomes.contains(outcome) {
+                return Err(VerificationError::UnknownOutcome(outcome.clone()));
+            }
+        }
+        EventDescriptor::DigitDecompositionEvent(desc) => {
+            verify_digit_outcomes(desc, &attestation.outcomes)?;
+        }
+    }
+
+    for (index, ((sig, outcome), nonce)) in attestation
+        .signatures
+        .iter()
+        .zip(attestation.outcomes.iter())
+        .zip(oracle_event.oracle_nonces.iter())
+        .enumerate()
+    {
+        if sig[..32] != nonce.serialize() {
+            return Err(VerificationError::NonceMismatch { index });
+        }
+
+        let msg = outcome_message(outcome, scheme);
+        secp.verify_schnorr(sig, &msg, &attestation.oracle_public_key)
+            .map_err(|_| VerificationError::InvalidSignature { index })?;
+    }
+
+    Ok(())
+}
+
+/// Checks the outcomes are a sign prefix (for signed events) followed by one
+/// canonical digit of the descriptor's base per announced digit.
+fn verify_digit_outcomes(
+    desc: &DigitDecompositionEventDescriptor,
+    outcomes: &[String],
+) -> Result<(), VerificationError> {
+    let digits = if desc.is_signed {
+        let sign = &outcomes[0];
+        if sign != "+" && sign != "-" {
+            return Err(VerificationError::InvalidSign(sign.clone()));
+        }
+        &outcomes[1..]
+    } else {
+        outcomes
+    };
+
+    if digits.len() != desc.nb_digits as usize {
+        return Err(VerificationError::OutcomeCountMismatch {
+            expected: desc.nb_digits as usize,
+            actual: digits.len(),
+        });
+    }
+
+    let offset = outcomes.len() - digits.len();
+    for (index, digit) in digits.iter().enumerate() {
+        let valid = digit
+            .parse::<u16>()
+            .is_ok_and(|d| d < desc.base && d.to_string() == *digit);
+        if !valid {
+            return Err(VerificationError::InvalidDigit {
+                index: index + offset,
+                digit: digit.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::signer::Signer;
+    use crate::storage::MemoryStorage;
+    use crate::Oracle;
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::rand::{thread_rng, Rng};
+    use bitcoin::Network;
+
+    fn create_oracle() -> Oracle<MemoryStorage> {
+        let mut seed: [u8; 64] = [0; 64];
+        thread_rng().fill(&mut seed);
+        let xpriv = Xpriv::new_master(Network::Regtest, &seed).unwrap();
+        Oracle::from_xpriv(MemoryStorage::default(), xpriv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verify_enum_attestation() {
+        let secp = Secp256k1::verification_only();
+        let oracle = create_oracle();
+
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        let ann = oracle
+            .create_enum_event("test".to_string(), outcomes, 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .sign_enum_event("test".to_string(), "a".to_string())
+            .await
+            .unwrap();
+
+        assert!(verify_announcement(&secp, &ann).is_ok());
+        assert!(verify_attestation(&secp, &ann, &attestation).is_ok());
+
+        let mut tampered = attestation.clone();
+        tampered.outcomes = vec!["b".to_string()];
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::InvalidSignature { index: 0 })
+        ));
+
+        let mut tampered = attestation.clone();
+        tampered.outcomes = vec!["c".to_string()];
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::UnknownOutcome(_))
+        ));
+
+        let mut tampered = attestation;
+        tampered.event_id = "other".to_string();
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::EventIdMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_numeric_attestation() {
+        let secp = Secp256k1::verification_only();
+        let oracle = create_oracle();
+
+        let ann = oracle
+            .create_numeric_event("test".to_string(), 10, 4, true, 0, "m/s".into(), 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .sign_numeric_event("test".to_string(), -1234)
+            .await
+            .unwrap();
+
+        assert!(verify_attestation(&secp, &ann, &attestation).is_ok());
+
+        let mut tampered = attestation.clone();
+        tampered.outcomes[0] = "*".to_string();
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::InvalidSign(_))
+        ));
+
+        let mut tampered = attestation.clone();
+        tampered.outcomes[2] = "12".to_string();
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::InvalidDigit { index: 2, .. })
+        ));
+
+        let mut tampered = attestation.clone();
+        tampered.signatures.swap(1, 2);
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::NonceMismatch { index: 1 })
+        ));
+
+        let mut tampered = attestation;
+        tampered.signatures.pop();
+        tampered.outcomes.pop();
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::SignatureCountMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_tagged_attestation() {
+        let secp = Secp256k1::verification_only();
+        let oracle = create_oracle().with_attestation_scheme(AttestationScheme::Tagged);
+
+        let ann = oracle
+            .create_numeric_event("test".to_string(), 10, 2, false, 0, "m".into(), 100)
+            .await
+            .unwrap();
+        let attestation = oracle
+            .sign_numeric_event("test".to_string(), 42)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            announcement_scheme(&secp, &ann).unwrap(),
+            AttestationScheme::Tagged
+        );
+        assert!(verify_attestation(&secp, &ann, &attestation).is_ok());
+
+        let mut tampered = attestation;
+        tampered.outcomes[0] = "5".to_string();
+        assert!(matches!(
+            verify_attestation(&secp, &ann, &tampered),
+            Err(VerificationError::InvalidSignature { index: 0 })
+        ));
+
+        let legacy = create_oracle();
+        let legacy_ann = legacy
+            .create_enum_event("legacy".to_string(), vec!["a".to_string()], 100)
+            .await
+            .unwrap();
+        assert_eq!(
+            announcement_scheme(&secp, &legacy_ann).unwrap(),
+            AttestationScheme::Legacy
+        );
+
+        // outcomes signed with the other scheme are rejected
+        let legacy_attestation = legacy
+            .sign_enum_event("legacy".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        let mut tagged_ann = legacy_ann.clone();
+        tagged_ann.announcement_signature = legacy
+            .signer()
+            .sign_announcement(&legacy_ann.oracle_event, AttestationScheme::Tagged)
+            .await
+            .unwrap();
+        assert!(matches!(
+            verify_attestation(&secp, &tagged_ann, &legacy_attestation),
+            Err(VerificationError::InvalidSignature { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_verify_announcement_nonce_count() {
+        use bitcoin::secp256k1::rand::thread_rng;
+        use bitcoin::secp256k1::Keypair;
+        use dlc_messages::oracle_msgs::{EnumEventDescriptor, OracleEvent};
+
+        let secp = Secp256k1::new();
+        let key_pair = Keypair::new(&secp, &mut thread_rng());
+        let nonce = Keypair::new(&secp, &mut thread_rng()).x_only_public_key().0;
+        // anyone can sign an announcement with their own key
+        let announce = |oracle_nonces: Vec<_>, event_descriptor| {
+            let oracle_event = OracleEvent {
+                oracle_nonces,
+                event_maturity_epoch: 100,
+                event_descriptor,
+                event_id: "test".to_string(),
+            };
+            let msg = announcement_message(&oracle_event, AttestationScheme::Legacy).unwrap();
+            OracleAnnouncement {
+                announcement_signature: secp.sign_schnorr(&msg, &key_pair),
+                oracle_public_key: key_pair.x_only_public_key().0,
+                oracle_event,
+            }
+        };
+        let enum_descriptor = EventDescriptor::EnumEvent(EnumEventDescriptor {
+            outcomes: vec!["a".to_string(), "b".to_string()],
+        });
+        let signed_descriptor =
+            EventDescriptor::DigitDecompositionEvent(DigitDecompositionEventDescriptor {
+                base: 2,
+                is_signed: true,
+                unit: "m".to_string(),
+                precision: 0,
+                nb_digits: 2,
+            });
+
+        for ann in [
+            announce(vec![], enum_descriptor.clone()),
+            announce(vec![nonce, nonce], enum_descriptor),
+            announce(vec![], signed_descriptor.clone()),
+            announce(vec![nonce, nonce], signed_descriptor),
+        ] {
+            assert_eq!(
+                verify_announcement(&secp, &ann),
+                Err(VerificationError::InvalidEventDescriptor)
+            );
+            // an empty attestation must not panic
+            let attestation = OracleAttestation {
+                event_id: "test".to_string(),
+                oracle_public_key: ann.oracle_public_key,
+                signatures: vec![],
+                outcomes: vec![],
+            };
+            assert_eq!(
+                verify_attestation(&secp, &ann, &attestation),
+                Err(VerificationError::InvalidEventDescriptor)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_attestation_wrong_announcement() {
+        let secp = Secp256k1::verification_only();
+        let oracle = create_oracle();
+
+        let outcomes = vec!["a".to_string(), "b".to_string()];
+        oracle
+            .create_enum_event("first".to_string(), outcomes.clone(), 100)
+            .await
+            .unwrap();
+        let mut other = oracle
+            .create_enum_event("second".to_string(), outcomes, 100)
+            .await
+            .unwrap();
+        let mut attestation = oracle
+            .sign_enum_event("first".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        attestation.event_id = "second".to_string();
+
+        assert!(matches!(
+            verify_attestation(&secp, &other, &attestation),
+            Err(VerificationError::NonceMismatch { index: 0 })
+        ));
+
+        other.oracle_event.event_maturity_epoch += 1;
+        assert!(matches!(
+            verify_announcement(&secp, &other),
+            Err(VerificationError::InvalidAnnouncementSignature)
+        ));
+    }
+}